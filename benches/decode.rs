@@ -0,0 +1,46 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Times decoding with the `unchecked` feature.
+//!
+//! Run with `cargo bench --features unchecked` and compare against the same
+//! loop built without the feature.
+//!
+//! Decoding looks chars up in a reverse table as it reaches them instead of
+//! collecting them into a `Vec` first, so the loop times decoding without a
+//! per-label allocation. The checksum is the same either way.
+
+use std::time::Instant;
+
+use amazon_cloudfront_client_routing_lib::decoder::LabelDecoder;
+
+const NUM_ITERATIONS: u32 = 1_000_000;
+
+fn main() {
+    let domains = [
+        "abfku6xaaaaaaaamotptyubibrji6.example.com",
+        "abydhs4fyq6iaaaykudpmaxncecqs.example.com",
+        "abc4aydaaaaaaaamaaaaaaaaaaaaa.test.example2.com",
+    ];
+    let mut decoder = LabelDecoder::default();
+
+    // Folding every cgid into a checksum that gets printed keeps the decodes
+    // from being optimized away.
+    let mut checksum: u64 = 0;
+    let start = Instant::now();
+    for i in 0..NUM_ITERATIONS {
+        let domain = domains[i as usize % domains.len()];
+        if let Ok(decoded) = decoder.decode(domain) {
+            checksum ^= decoded.cgid.rotate_left(i % 64);
+        }
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "decode: {} iterations in {:?} ({:.1} ns/iter)",
+        NUM_ITERATIONS,
+        elapsed,
+        elapsed.as_nanos() as f64 / NUM_ITERATIONS as f64
+    );
+    println!("checksum: {:x}", checksum);
+}
@@ -0,0 +1,18 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Fuzzes decoding untrusted domains.
+//!
+//! Run with `cargo +nightly fuzz run decode` from the repository root. Any
+//! panic is a bug, decoding should only ever return a `DecodeError`.
+
+#![no_main]
+
+use amazon_cloudfront_client_routing_lib::decode_request_data;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let domain = String::from_utf8_lossy(data);
+
+    let _ = decode_request_data(&domain);
+});
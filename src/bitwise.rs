@@ -1,6 +1,7 @@
 // Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+#[inline]
 pub fn get_mask(num_bits: u8) -> u64 {
     ((1_u128 << num_bits) - 1) as u64
 }
\ No newline at end of file
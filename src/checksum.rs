@@ -0,0 +1,227 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Check chars for catching and repairing hand-copied labels.
+//!
+//! A checksummed label is a Base32 label followed by [`CHECKSUM_LEN`] check
+//! chars holding two sums of its char values modulo the prime 67: a plain sum
+//! and a sum weighted by position. Changing any one char changes both sums in
+//! a way no other single change can, so a single wrong char is always found
+//! and can be repaired with [`try_repair`].
+
+use crate::encode_decode::{base32_char, base32_value, MAX_DNS_LABEL_SIZE};
+
+/// Number of check chars appended by [`append_checksum`].
+pub const CHECKSUM_LEN: usize = 4;
+
+/// Modulus of both sums. Prime and more than both the difference between any
+/// two char values and the [`MAX_DNS_LABEL_SIZE`] positions of a checksummed
+/// label, so no two positions share a weight and weighted differences never
+/// cancel. At most 66, so each sum still fits in 2 check chars.
+const CHECKSUM_MODULUS: u32 = 67;
+
+/// Returns the plain and position weighted sums of `label`, or [`None`] if a
+/// char isn't Base32.
+fn sums(label: &[u8]) -> Option<(u32, u32)> {
+    let mut sum = 0;
+    let mut weighted_sum = 0;
+    for (index, c) in label.iter().enumerate() {
+        let value = base32_value(*c)? as u32;
+        sum = (sum + value) % CHECKSUM_MODULUS;
+        weighted_sum = (weighted_sum + (index as u32 + 1) * value) % CHECKSUM_MODULUS;
+    }
+
+    Some((sum, weighted_sum))
+}
+
+/// Returns the check chars for `label`, each sum split over 2 chars so a
+/// single wrong check char can't hide a change to both.
+fn check_chars(label: &[u8]) -> Option<[u8; CHECKSUM_LEN]> {
+    let (sum, weighted_sum) = sums(label)?;
+
+    Some([
+        base32_char((sum >> 5) as u8),
+        base32_char(sum as u8),
+        base32_char((weighted_sum >> 5) as u8),
+        base32_char(weighted_sum as u8),
+    ])
+}
+
+/// Returns `label` with [`CHECKSUM_LEN`] check chars appended.
+///
+/// `label` is lowercased first. Returns [`None`] if `label` has a char outside
+/// the Base32 alphabet, or if the checksummed label would be longer than
+/// [`MAX_DNS_LABEL_SIZE`].
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::checksum::{append_checksum, verify_checksum};
+///
+/// let checksummed_label = append_checksum("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+/// assert_eq!(33, checksummed_label.len());
+/// assert!(verify_checksum(&checksummed_label));
+/// ```
+pub fn append_checksum(label: &str) -> Option<String> {
+    if label.len() + CHECKSUM_LEN > MAX_DNS_LABEL_SIZE as usize {
+        return None;
+    }
+
+    let label = label.to_ascii_lowercase();
+    let check_chars = check_chars(label.as_bytes())?;
+
+    let mut checksummed_label = label;
+    checksummed_label.extend(check_chars.iter().map(|c| *c as char));
+
+    Some(checksummed_label)
+}
+
+/// Returns whether the last [`CHECKSUM_LEN`] chars of `checksummed_label` are
+/// the check chars of the rest of it, ignoring case.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::checksum::verify_checksum;
+///
+/// assert!(verify_checksum("abacaqdaaaaaaaamnjg3oubcyvrgmafb5"));
+/// assert!(!verify_checksum("abacaqdaaaaaaaamnjg3oubcyvrgnafb5"));
+/// ```
+pub fn verify_checksum(checksummed_label: &str) -> bool {
+    let checksummed_label = checksummed_label.as_bytes();
+    if checksummed_label.len() < CHECKSUM_LEN {
+        return false;
+    }
+
+    let (label, expected_check_chars) = checksummed_label.split_at(checksummed_label.len() - CHECKSUM_LEN);
+    match check_chars(label) {
+        Some(check_chars) => check_chars.eq_ignore_ascii_case(expected_check_chars),
+        None => false,
+    }
+}
+
+/// Returns `checksummed_label` with a single wrong char repaired, or [`None`]
+/// if it can't be repaired.
+///
+/// Tries every single char substitution, each position against all 32 Base32
+/// chars, and returns the one that passes [`verify_checksum`]. The check chars
+/// make that substitution unique. A label that already verifies is returned
+/// lowercased as is. Labels with more than one wrong char usually return
+/// [`None`] but can be repaired into the wrong label, so treat a repair as a
+/// suggestion.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::checksum::try_repair;
+///
+/// // 'm' mistyped as 'n'
+/// assert_eq!(
+///     Some(String::from("abacaqdaaaaaaaamnjg3oubcyvrgmafb5")),
+///     try_repair("abacaqdaaaaaaaamnjg3oubcyvrgnafb5")
+/// );
+/// ```
+pub fn try_repair(checksummed_label: &str) -> Option<String> {
+    if !checksummed_label.is_ascii() {
+        return None;
+    }
+
+    let mut candidate = checksummed_label.to_ascii_lowercase().into_bytes();
+    if verify_checksum(std::str::from_utf8(&candidate).ok()?) {
+        return String::from_utf8(candidate).ok();
+    }
+
+    let mut repaired_label = None;
+    for index in 0..candidate.len() {
+        let original_char = candidate[index];
+        for value in 0..32 {
+            candidate[index] = base32_char(value);
+            if candidate[index] == original_char {
+                continue;
+            }
+
+            // swapping one ascii char for another keeps the candidate ascii
+            if verify_checksum(std::str::from_utf8(&candidate).ok()?) {
+                if repaired_label.is_some() {
+                    return None;
+                }
+                repaired_label = Some(candidate.clone());
+            }
+        }
+        candidate[index] = original_char;
+    }
+
+    repaired_label.and_then(|repaired_label| String::from_utf8(repaired_label).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append_checksum, try_repair, verify_checksum, CHECKSUM_LEN, MAX_DNS_LABEL_SIZE};
+
+    #[test]
+    fn validate_checksum_round_trip() {
+        for label in [
+            "abfku6xaaaaaaaamotptyubibrji6",
+            "abydhs4fyq6iaaaykudpmaxncecqs",
+            "abaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        ] {
+            let checksummed_label = match append_checksum(label) {
+                Some(checksummed_label) => checksummed_label,
+                None => panic!("Couldn't checksum {}", label),
+            };
+            assert_eq!(label.len() + CHECKSUM_LEN, checksummed_label.len());
+            assert!(checksummed_label.starts_with(label));
+            assert!(verify_checksum(&checksummed_label));
+            assert!(verify_checksum(&checksummed_label.to_ascii_uppercase()));
+        }
+
+        assert!(append_checksum("abfku6xaaaaaaaamotptyubibrji0").is_none());
+        assert!(!verify_checksum("abc"));
+    }
+
+    #[test]
+    fn validate_try_repair_every_single_char_substitution() {
+        let checksummed_label = append_checksum("abfku6xaaaaaaaamotptyubibrji6").unwrap();
+
+        for index in 0..checksummed_label.len() {
+            for c in b"abcdefghijklmnopqrstuvwxyz2345670" {
+                let mut corrupted_label = checksummed_label.clone().into_bytes();
+                if corrupted_label[index] == *c {
+                    continue;
+                }
+                corrupted_label[index] = *c;
+                let corrupted_label = String::from_utf8(corrupted_label).unwrap();
+
+                assert!(!verify_checksum(&corrupted_label));
+                assert_eq!(Some(checksummed_label.clone()), try_repair(&corrupted_label));
+            }
+        }
+    }
+
+    #[test]
+    fn validate_try_repair_label_longer_than_37_chars() {
+        let checksummed_label = append_checksum("abfku6xaaaaaaaamotptyubibrji6abydhs4fyq6iaaaykudpmaxncecqsa").unwrap();
+        assert_eq!(MAX_DNS_LABEL_SIZE as usize, checksummed_label.len());
+
+        for index in 0..checksummed_label.len() {
+            for c in b"abcdefghijklmnopqrstuvwxyz234567" {
+                let mut corrupted_label = checksummed_label.clone().into_bytes();
+                if corrupted_label[index] == *c {
+                    continue;
+                }
+                corrupted_label[index] = *c;
+                let corrupted_label = String::from_utf8(corrupted_label).unwrap();
+
+                assert!(!verify_checksum(&corrupted_label));
+                assert_eq!(Some(checksummed_label.clone()), try_repair(&corrupted_label));
+            }
+        }
+
+        assert!(append_checksum("abfku6xaaaaaaaamotptyubibrji6abydhs4fyq6iaaaykudpmaxncecqsaq").is_none());
+    }
+
+    #[test]
+    fn validate_try_repair_valid_label_unchanged() {
+        let checksummed_label = append_checksum("abydhs4fyq6iaaaykudpmaxncecqs").unwrap();
+
+        assert_eq!(Some(checksummed_label.clone()), try_repair(&checksummed_label.to_ascii_uppercase()));
+        assert_eq!(None, try_repair("ab"));
+    }
+}
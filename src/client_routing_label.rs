@@ -1,12 +1,94 @@
 // Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
 use crate::bitwise::get_mask;
-use crate::encode_decode::Base32;
-use crate::errors::DecodeLengthError;
-use crate::ip::ClientSubnetEncodingData;
+use crate::encode_decode::{num_chars_for_bits, Base32};
+use crate::errors::{AddBitsOverflowError, DecodeError};
+use crate::hash::hash_cgid;
+use crate::ip::{
+    parse_client_ip, ClientSubnetEncodingData, MAX_IPV4_SUBNET_MASK, MAX_IPV6_SUBNET_MASK,
+};
+
+pub(crate) const CLIENT_ROUTING_LABEL_VERSION: u16 = 1;
+
+/// `(name, num_bits)` of each field of the default [`ClientRoutingLabel`]
+/// layout, in the order they're encoded.
+pub const LABEL_FIELDS: [(&str, u8); 5] = [
+    ("client_sdk_version", 10),
+    ("is_ipv6", 1),
+    ("client_subnet", 64),
+    ("subnet_mask", 6),
+    ("cgid", 64),
+];
+
+/// 2^64 divided by the golden ratio, rounded to odd, used by
+/// [`DecodedClientRoutingLabel::shard`] to spread routing keys.
+const FIBONACCI_HASH_MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Bit offset from the start of a label to each field in [`LABEL_FIELDS`].
+pub const LABEL_FIELD_OFFSETS: [u8; 5] = field_offsets(LABEL_FIELDS);
+
+/// Number of chars in a client routing label encoded with the default
+/// [`ClientRoutingLabel`] layout of 145 bits.
+pub const ENCODED_LABEL_LEN: usize = 29;
+
+// Fails the build if LABEL_FIELDS is edited without updating
+// ENCODED_LABEL_LEN.
+const _: () = assert!(
+    ENCODED_LABEL_LEN == (LABEL_FIELD_OFFSETS[4] as usize + LABEL_FIELDS[4].1 as usize + 4) / 5
+);
+
+/// Returns the cumulative bit offset of each of `fields`.
+const fn field_offsets(fields: [(&str, u8); 5]) -> [u8; 5] {
+    let mut offsets = [0_u8; 5];
+
+    let mut i = 1;
+    while i < fields.len() {
+        offsets[i] = offsets[i - 1] + fields[i - 1].1;
+        i += 1;
+    }
+
+    offsets
+}
+
+/// Number of bytes in the packed form of a [`DecodedClientRoutingLabel`], see
+/// [`to_bytes`](DecodedClientRoutingLabel::to_bytes).
+pub const DECODED_LABEL_NUM_BYTES: usize = 19;
+
+/// Returns how many chars a label with the `(name, num_bits)` layout of
+/// `fields` is encoded to.
+///
+/// Useful for checking a proposed layout fits in
+/// [`MAX_DNS_LABEL_SIZE`](crate::encode_decode::MAX_DNS_LABEL_SIZE) before
+/// building it.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::client_routing_label::layout_label_len;
+///
+/// let layout = [
+///     ("client_sdk_version", 10),
+///     ("is_ipv6", 1),
+///     ("client_subnet", 64),
+///     ("subnet_mask", 6),
+///     ("cgid", 64),
+/// ];
+/// assert_eq!(29, layout_label_len(&layout));
+/// ```
+pub fn layout_label_len(fields: &[(&str, u8)]) -> usize {
+    num_chars_for_bits(fields.iter().map(|(_, num_bits)| *num_bits as usize).sum())
+}
 
-const CLIENT_ROUTING_LABEL_VERSION: u16 = 1;
+/// Returns the widest subnet mask an Ipv4 or Ipv6 label can have.
+fn max_subnet_mask(is_ipv6: bool) -> u8 {
+    if is_ipv6 {
+        MAX_IPV6_SUBNET_MASK
+    } else {
+        MAX_IPV4_SUBNET_MASK
+    }
+}
 
 /// Struct containing decoded client routing label values.
 ///
@@ -16,6 +98,11 @@ const CLIENT_ROUTING_LABEL_VERSION: u16 = 1;
 /// Only the least significant bits will be kept in the case `value` can't fit in
 /// `num_bits`.
 ///
+/// `client_subnet` is big-endian (network byte order): the network prefix is in
+/// the leading bytes and any unused trailing bytes are 0. Use
+/// [`client_subnet_le`](DecodedClientRoutingLabel::client_subnet_le) for the
+/// little-endian ordering.
+///
 /// # Examples:
 /// ```
 /// use amazon_cloudfront_client_routing_lib::client_routing_label::DecodedClientRoutingLabel;
@@ -34,15 +121,541 @@ const CLIENT_ROUTING_LABEL_VERSION: u16 = 1;
 ///     cgid
 /// };
 /// ```
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
 pub struct DecodedClientRoutingLabel {
     pub client_sdk_version: u16,
     pub is_ipv6: bool,
     pub client_subnet: [u8; 8],
+    /// Number of leading bits of `client_subnet` that are kept, or 0 if the
+    /// label has no subnet. Up to 32 for Ipv4 and 64 for Ipv6. A /64 is
+    /// encoded as 0 in the 6 bit field and restored to 64 from `is_ipv6`.
     pub subnet_mask: u8,
     pub cgid: u64,
 }
 
+impl DecodedClientRoutingLabel {
+    /// Returns whether the label carries a client subnet.
+    ///
+    /// A `subnet_mask` of 0 is the sentinel for "no subnet". It is produced both
+    /// by [`encode_request_data_no_subnet`](crate::encode_request_data_no_subnet)
+    /// and by [`encode_request_data`](crate::encode_request_data) when the
+    /// `client_ip` is invalid; the two cases intentionally can't be told apart.
+    ///
+    /// The encoded subnet mask field is also 0 for an Ipv6 /64, but that label
+    /// has `is_ipv6` set and decodes with a `subnet_mask` of 64, so it still
+    /// has a subnet. Encoding an Ipv6 mask of 0 produces the no subnet
+    /// sentinel, see [`set_data`](ClientRoutingLabel::set_data).
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// assert!(decoded_label.has_subnet());
+    ///
+    /// let decoded_label = decode_request_data("abaaaaaaaaaaaaaanjg3oubcyvrgm").unwrap();
+    /// assert!(!decoded_label.has_subnet());
+    /// ```
+    pub fn has_subnet(&self) -> bool {
+        self.subnet_mask != 0
+    }
+
+    /// Returns whether `client_sdk_version` is a version labels are encoded
+    /// with.
+    ///
+    /// Versions start at 1, so a `client_sdk_version` of 0 is a strong sign the
+    /// label wasn't produced by this library even though it decoded. Combined
+    /// with [`looks_like_routing_label`](crate::looks_like_routing_label) this
+    /// filters out most false positives when searching logs for labels.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abaaaaaaaaaaaaaaaaaaaaaaaaaaa").unwrap();
+    /// assert!(decoded_label.has_valid_version());
+    ///
+    /// let decoded_label = decode_request_data("aaaaaaaaaaaaaaaaaaaaaaaaaaaaa").unwrap();
+    /// assert!(!decoded_label.has_valid_version());
+    /// ```
+    pub fn has_valid_version(&self) -> bool {
+        self.client_sdk_version != 0
+    }
+
+    /// Returns whether the label carries no data: `client_subnet`,
+    /// `subnet_mask`, and `cgid` are all 0.
+    ///
+    /// `client_sdk_version` is ignored, so `abaaaaaaaaaaaaaaaaaaaaaaaaaaa`, the
+    /// label encoded for an invalid client ip and an empty cgid, is empty.
+    /// Callers can skip these labels since they hold no routing information.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abaaaaaaaaaaaaaaaaaaaaaaaaaaa").unwrap();
+    /// assert!(decoded_label.is_empty());
+    ///
+    /// let decoded_label = decode_request_data("abaaaaaaaaaaaaaanjg3oubcyvrgm").unwrap();
+    /// assert!(!decoded_label.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.client_subnet == [0; 8] && self.subnet_mask == 0 && self.cgid == 0
+    }
+
+    /// Returns whether `subnet_mask` is in range for `is_ipv6`.
+    ///
+    /// Encoding never produces a mask over 32 for an Ipv4 label or over 64 for
+    /// an Ipv6 label, so a label outside those ranges was corrupted or not
+    /// produced by this library. Ipv6 labels are /48 by default, but /56 and
+    /// /64 labels from
+    /// [`parse_client_ip_with_ipv6_mask`](crate::ip::parse_client_ip_with_ipv6_mask)
+    /// or [`ParsingConfig`](crate::ip::ParsingConfig) are consistent too.
+    ///
+    /// Decoding restores an Ipv6 mask from its 6 bit field as 1 to 64, so a
+    /// decoded Ipv6 label is always consistent and only Ipv4 labels, whose
+    /// field can hold up to 63, can fail this check. It's part of the
+    /// [`plausibility`](DecodedClientRoutingLabel::plausibility) score.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// assert!(decoded_label.is_consistent());
+    ///
+    /// // an Ipv4 label with a /48 mask
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaawnjg3oubcyvrgm").unwrap();
+    /// assert!(!decoded_label.is_consistent());
+    /// ```
+    pub fn is_consistent(&self) -> bool {
+        self.subnet_mask <= max_subnet_mask(self.is_ipv6)
+    }
+
+    /// Returns a 0-100 score of how likely the label was produced by this
+    /// library.
+    ///
+    /// Any label of the right length decodes, so this scores the decoded
+    /// values. 40 points if `client_sdk_version` is the current version, 30 if
+    /// `subnet_mask` is one [`parse_client_ip`](crate::ip::parse_client_ip)
+    /// produces for `is_ipv6` (15 for another mask that is
+    /// [`is_consistent`](DecodedClientRoutingLabel::is_consistent)), and 30 if
+    /// every bit of `client_subnet` past `subnet_mask` is 0.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// assert_eq!(100, decoded_label.plausibility());
+    ///
+    /// let decoded_label = decode_request_data("zzzzzzzzzzzzzzzzzzzzzzzzzzzzz").unwrap();
+    /// assert!(decoded_label.plausibility() < 50);
+    /// ```
+    pub fn plausibility(&self) -> u8 {
+        let mut plausibility = 0;
+
+        if self.client_sdk_version == CLIENT_ROUTING_LABEL_VERSION {
+            plausibility += 40;
+        }
+
+        plausibility += match (self.is_ipv6, self.subnet_mask) {
+            (false, 0) | (false, 24) | (true, 48) => 30,
+            _ if self.is_consistent() => 15,
+            _ => 0,
+        };
+
+        let host_bits = u64::MAX
+            .checked_shr(self.subnet_mask as u32)
+            .unwrap_or(0);
+        if u64::from_be_bytes(self.client_subnet) & host_bits == 0 {
+            plausibility += 30;
+        }
+
+        plausibility
+    }
+
+    /// Returns `client_subnet` in little-endian byte order.
+    ///
+    /// `client_subnet` is big-endian with the network prefix in the leading
+    /// bytes. This reverses it so the prefix ends up in the trailing bytes, for
+    /// consumers that expect little-endian ordering.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+    /// assert_eq!([0, 0, 0, 0, 0, 3, 2, 1], decoded_label.client_subnet_le());
+    /// ```
+    pub fn client_subnet_le(&self) -> [u8; 8] {
+        u64::from_be_bytes(self.client_subnet).to_le_bytes()
+    }
+
+    /// Returns how many leading bytes of `client_subnet` are covered by
+    /// `subnet_mask`.
+    ///
+    /// Rounds up, so a mask that ends part way through a byte counts that byte.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// assert_eq!(3, decoded_label.significant_subnet_bytes());
+    /// assert_eq!([1, 2, 3], decoded_label.client_subnet[..decoded_label.significant_subnet_bytes()]);
+    /// ```
+    pub fn significant_subnet_bytes(&self) -> usize {
+        (self.subnet_mask as usize + 7) / 8
+    }
+
+    /// Returns `client_subnet` as an [`IpAddr`], or [`None`] if the label has no
+    /// subnet.
+    ///
+    /// An Ipv4 address is built from the first 4 bytes of `client_subnet`, an
+    /// Ipv6 address from all 8 bytes followed by 8 zero bytes. Displaying the
+    /// Ipv6 address uses the canonical compressed form, so the zeroed bits past
+    /// the subnet collapse to `::`.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// assert_eq!("1.2.3.0", decoded_label.to_ip_addr().unwrap().to_string());
+    ///
+    /// let decoded_label = decode_request_data("abqcaqdaqcqmaaaynjg3oubcyvrgm").unwrap();
+    /// assert_eq!("102:304:506::", decoded_label.to_ip_addr().unwrap().to_string());
+    ///
+    /// let decoded_label = decode_request_data("abaaaaaaaaaaaaaanjg3oubcyvrgm").unwrap();
+    /// assert_eq!(None, decoded_label.to_ip_addr());
+    /// ```
+    pub fn to_ip_addr(&self) -> Option<IpAddr> {
+        if !self.has_subnet() {
+            return None;
+        }
+
+        if self.is_ipv6 {
+            let mut octets = [0_u8; 16];
+            octets[..8].copy_from_slice(&self.client_subnet);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        } else {
+            let mut octets = [0_u8; 4];
+            octets.copy_from_slice(&self.client_subnet[..4]);
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+    }
+
+    /// Returns `client_subnet` as a string in the ip's native notation.
+    ///
+    /// Uses [`to_ip_addr`](DecodedClientRoutingLabel::to_ip_addr), so Ipv6
+    /// subnets are in the canonical compressed form. A label with no subnet is
+    /// returned as `"0.0.0.0"`.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abfku6xaaaaaaaamotptyubibrji6").unwrap();
+    /// assert_eq!("85.83.215.0", decoded_label.subnet_string());
+    ///
+    /// let decoded_label = decode_request_data("abqcaqdaqcqmaaaynjg3oubcyvrgm").unwrap();
+    /// assert_eq!("102:304:506::", decoded_label.subnet_string());
+    ///
+    /// let decoded_label = decode_request_data("abaaaaaaaaaaaaaanjg3oubcyvrgm").unwrap();
+    /// assert_eq!("0.0.0.0", decoded_label.subnet_string());
+    /// ```
+    pub fn subnet_string(&self) -> String {
+        self.to_ip_addr()
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+            .to_string()
+    }
+
+    /// Returns `client_subnet` and `subnet_mask` in CIDR notation, or [`None`]
+    /// if the label has no subnet.
+    ///
+    /// Uses [`to_ip_addr`](DecodedClientRoutingLabel::to_ip_addr) for the
+    /// address, so Ipv6 subnets are in the canonical compressed form.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// assert_eq!(Some(String::from("1.2.3.0/24")), decoded_label.to_cidr_string());
+    ///
+    /// let decoded_label = decode_request_data("abqcaqdaqcqmaaaynjg3oubcyvrgm").unwrap();
+    /// assert_eq!(Some(String::from("102:304:506::/48")), decoded_label.to_cidr_string());
+    /// ```
+    pub fn to_cidr_string(&self) -> Option<String> {
+        self.to_ip_addr()
+            .map(|ip_addr| format!("{}/{}", ip_addr, self.subnet_mask))
+    }
+
+    /// Returns `client_subnet` and `subnet_mask` as an [`ipnet::IpNet`].
+    ///
+    /// Uses [`to_ip_addr`](DecodedClientRoutingLabel::to_ip_addr) for the
+    /// address. A label with no subnet is returned as `0.0.0.0/0`. Requires
+    /// the `ipnet` feature.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// assert_eq!("1.2.3.0/24", decoded_label.to_ip_net().to_string());
+    ///
+    /// let decoded_label = decode_request_data("abaaaaaaaaaaaaaanjg3oubcyvrgm").unwrap();
+    /// assert_eq!("0.0.0.0/0", decoded_label.to_ip_net().to_string());
+    /// ```
+    #[cfg(feature = "ipnet")]
+    pub fn to_ip_net(&self) -> ipnet::IpNet {
+        let ip_addr = self
+            .to_ip_addr()
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        // fall back to the full address if subnet_mask is too large for it
+        ipnet::IpNet::new(ip_addr, self.subnet_mask).unwrap_or_else(|_e| ip_addr.into())
+    }
+
+    /// Returns a fixed size, packed binary form of the label.
+    ///
+    /// Intended for host side storage rather than DNS. All values are
+    /// big-endian: `client_sdk_version` in bytes 0-1, `is_ipv6` in the top bit
+    /// of byte 2 with `subnet_mask` in its low 7 bits, `client_subnet` in bytes
+    /// 3-10, and `cgid` in bytes 11-18. A `subnet_mask` over 127 only keeps its
+    /// low 7 bits, which covers every mask a decoded label can have.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::DecodedClientRoutingLabel;
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abqcaqdaqcqmaaaynjg3oubcyvrgm").unwrap();
+    /// let bytes = decoded_label.to_bytes();
+    /// assert_eq!([0, 1, 0b1011_0000], bytes[..3]);
+    ///
+    /// let unpacked_label = DecodedClientRoutingLabel::from_bytes(&bytes);
+    /// assert_eq!(decoded_label.client_subnet, unpacked_label.client_subnet);
+    /// assert_eq!(decoded_label.cgid, unpacked_label.cgid);
+    /// ```
+    pub fn to_bytes(&self) -> [u8; DECODED_LABEL_NUM_BYTES] {
+        let mut bytes = [0_u8; DECODED_LABEL_NUM_BYTES];
+        bytes[..2].copy_from_slice(&self.client_sdk_version.to_be_bytes());
+        bytes[2] = ((self.is_ipv6 as u8) << 7) | (self.subnet_mask & 0x7f);
+        bytes[3..11].copy_from_slice(&self.client_subnet);
+        bytes[11..].copy_from_slice(&self.cgid.to_be_bytes());
+
+        bytes
+    }
+
+    /// Returns the [`DecodedClientRoutingLabel`] packed into `bytes` by
+    /// [`to_bytes`](DecodedClientRoutingLabel::to_bytes).
+    pub fn from_bytes(bytes: &[u8; DECODED_LABEL_NUM_BYTES]) -> Self {
+        let mut client_sdk_version = [0_u8; 2];
+        client_sdk_version.copy_from_slice(&bytes[..2]);
+        let mut client_subnet = [0_u8; 8];
+        client_subnet.copy_from_slice(&bytes[3..11]);
+        let mut cgid = [0_u8; 8];
+        cgid.copy_from_slice(&bytes[11..]);
+
+        Self {
+            client_sdk_version: u16::from_be_bytes(client_sdk_version),
+            is_ipv6: bytes[2] >> 7 != 0,
+            client_subnet,
+            subnet_mask: bytes[2] & 0x7f,
+            cgid: u64::from_be_bytes(cgid),
+        }
+    }
+
+    /// Returns `cgid` as a fixed width, 16 char, lowercase hex string.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// assert_eq!("d49b6ea0458ac4cc", decoded_label.cgid_hex());
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamaaaaaaaaaaaaa").unwrap();
+    /// assert_eq!("0000000000000000", decoded_label.cgid_hex());
+    /// ```
+    pub fn cgid_hex(&self) -> String {
+        format!("{:016x}", self.cgid)
+    }
+
+    /// Returns `is_ipv6`, `client_subnet`, `subnet_mask`, and `cgid` packed into
+    /// a single value usable as a map key.
+    ///
+    /// Those fields take 135 bits, so only the low 56 bits of `cgid` are kept.
+    /// From most to least significant bit:
+    ///
+    /// | bits    | field                        |
+    /// |---------|------------------------------|
+    /// | 127-64  | `client_subnet`              |
+    /// | 63      | `is_ipv6`                    |
+    /// | 62-56   | `subnet_mask`                |
+    /// | 55-0    | low 56 bits of `cgid`        |
+    ///
+    /// `client_sdk_version` isn't included, so labels encoded by different
+    /// versions for the same client and cgid have the same key. Labels whose
+    /// cgids differ only in their top 8 bits also share a key.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// assert_eq!(0x0102_0300_0000_0000_189b_6ea0_458a_c4cc, decoded_label.routing_key());
+    /// ```
+    pub fn routing_key(&self) -> u128 {
+        let client_subnet = u64::from_be_bytes(self.client_subnet) as u128;
+        let is_ipv6 = self.is_ipv6 as u128;
+        let subnet_mask = (self.subnet_mask & 0x7f) as u128;
+        let cgid = (self.cgid & get_mask(56)) as u128;
+
+        client_subnet << 64 | is_ipv6 << 63 | subnet_mask << 56 | cgid
+    }
+
+    /// Returns which of `num_shards` buckets this label belongs in, from 0 to
+    /// `num_shards - 1`.
+    ///
+    /// The two halves of [`routing_key`](DecodedClientRoutingLabel::routing_key)
+    /// are folded together and spread with a Fibonacci hash, then mapped onto
+    /// the buckets by multiplying rather than taking a remainder. Every bit of
+    /// the key affects the result, so labels that differ only in their subnet
+    /// or only in their cgid still spread evenly, and each bucket gets close
+    /// to `1 / num_shards` of the labels for any `num_shards`. The bucket only
+    /// depends on the routing key, so it's stable across processes and
+    /// versions of this library, and labels with the same routing always land
+    /// in the same bucket. Changing `num_shards` moves most labels to a
+    /// different bucket.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_shards` is 0.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// let shard = decoded_label.shard(16);
+    ///
+    /// assert!(shard < 16);
+    /// assert_eq!(shard, decoded_label.shard(16));
+    /// assert_eq!(0, decoded_label.shard(1));
+    /// ```
+    pub fn shard(&self, num_shards: u32) -> u32 {
+        assert!(num_shards > 0, "num_shards must be more than 0");
+
+        let routing_key = self.routing_key();
+        let hash = ((routing_key >> 64) as u64 ^ routing_key as u64).wrapping_mul(FIBONACCI_HASH_MULTIPLIER);
+
+        ((hash as u128 * num_shards as u128) >> 64) as u32
+    }
+
+    /// Returns `(field_name, self_value, other_value)` for each field that
+    /// differs between this label and `other`.
+    ///
+    /// Fields are named and ordered as in [`LABEL_FIELDS`], with values as
+    /// returned by [`field_values`](ClientRoutingLabel::field_values). An empty
+    /// list means the labels are identical. Useful for debugging routing
+    /// mismatches.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// let other_label = decode_request_data("abacaqdaaaaaaaamaaaaaaaaaaaaa").unwrap();
+    ///
+    /// assert_eq!(vec![("cgid", 15319960192071419084, 0)], decoded_label.diff(&other_label));
+    /// assert!(decoded_label.diff(&decoded_label).is_empty());
+    /// ```
+    pub fn diff(&self, other: &Self) -> Vec<(&'static str, u64, u64)> {
+        let field_values = ClientRoutingLabel::from_decoded(self).field_values();
+        let other_field_values = ClientRoutingLabel::from_decoded(other).field_values();
+
+        LABEL_FIELDS
+            .iter()
+            .zip(field_values.iter().zip(other_field_values.iter()))
+            .filter(|(_, (value, other_value))| value != other_value)
+            .map(|((field_name, _), (value, other_value))| (*field_name, *value, *other_value))
+            .collect()
+    }
+
+    /// Returns whether this label routes the same as the label encoded for
+    /// `ip` and `cgid`.
+    ///
+    /// Re-encodes `ip` and `cgid` the same as
+    /// [`encode_request_data`](crate::encode_request_data) and compares
+    /// `client_subnet`, `subnet_mask`, `is_ipv6`, and `cgid`.
+    /// `client_sdk_version` is ignored.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded_label = decode_request_data("abfku6xaaaaaaaamotptyubibrji6").unwrap();
+    ///
+    /// assert!(decoded_label.matches_inputs("85.83.215.126", "B086VX9VMK"));
+    /// // same /24 subnet
+    /// assert!(decoded_label.matches_inputs("85.83.215.1", "B086VX9VMK"));
+    /// assert!(!decoded_label.matches_inputs("85.83.214.126", "B086VX9VMK"));
+    /// assert!(!decoded_label.matches_inputs("85.83.215.126", "B086VX9VML"));
+    /// ```
+    pub fn matches_inputs(&self, ip: &str, cgid: &str) -> bool {
+        self.matches_hashed_inputs(ip, hash_cgid(cgid))
+    }
+
+    /// Returns whether this label routes the same as the label encoded for
+    /// `ip` and the already hashed `cgid`.
+    pub(crate) fn matches_hashed_inputs(&self, ip: &str, cgid: u64) -> bool {
+        let mut expected_label = ClientRoutingLabel::default();
+        expected_label.set_data(parse_client_ip(ip), cgid);
+        let expected_label = ClientRoutingLabel::default()
+            .decode(expected_label.encode().as_bytes())
+            // unwrap is ok here because a freshly encoded label always decodes
+            .unwrap();
+
+        self.has_same_routing(&expected_label)
+    }
+
+    /// Returns whether this label and `other` have the same `client_subnet`,
+    /// `subnet_mask`, `is_ipv6`, and `cgid`.
+    pub(crate) fn has_same_routing(&self, other: &Self) -> bool {
+        self.client_subnet == other.client_subnet
+            && self.subnet_mask == other.subnet_mask
+            && self.is_ipv6 == other.is_ipv6
+            && self.cgid == other.cgid
+    }
+}
+
+/// Serializes every field under its own name, except `client_subnet` which is
+/// serialized as the string from
+/// [`to_ip_addr`](DecodedClientRoutingLabel::to_ip_addr), or null if the label
+/// has no subnet. Requires the `serde` feature.
+#[cfg(feature = "serde")]
+impl serde::Serialize for DecodedClientRoutingLabel {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("DecodedClientRoutingLabel", 5)?;
+        state.serialize_field("client_sdk_version", &self.client_sdk_version)?;
+        state.serialize_field("is_ipv6", &self.is_ipv6)?;
+        state.serialize_field(
+            "client_subnet",
+            &self.to_ip_addr().map(|ip_addr| ip_addr.to_string()),
+        )?;
+        state.serialize_field("subnet_mask", &self.subnet_mask)?;
+        state.serialize_field("cgid", &self.cgid)?;
+        state.end()
+    }
+}
+
 /// Struct containing data to encode in a [`ClientRoutingLabel`].
 ///
 /// Consist of 2 properties: `value`, and `num_bits`. `value` is a u64 and
@@ -57,7 +670,7 @@ pub struct DecodedClientRoutingLabel {
 /// use amazon_cloudfront_client_routing_lib::encode_decode::Base32;
 ///
 /// let mut data: EncodableData;
-/// let encoding_system = Base32 {};
+/// let encoding_system = Base32::default();
 ///
 /// // value is 1 bit and needs to encode as 10 bits: 0b0000000001
 /// data = EncodableData {
@@ -90,6 +703,23 @@ pub struct EncodableData {
 }
 
 impl EncodableData {
+    /// Returns [`EncodableData`] holding `value` in `num_bits` bits.
+    ///
+    /// Usable in a `const` context, e.g. to build a layout table.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::EncodableData;
+    ///
+    /// const LAYOUT: [EncodableData; 2] = [EncodableData::new(1, 10), EncodableData::new(0, 64)];
+    ///
+    /// assert_eq!(1, LAYOUT[0].value);
+    /// assert_eq!(64, LAYOUT[1].num_bits);
+    /// ```
+    pub const fn new(value: u64, num_bits: u8) -> Self {
+        Self { value, num_bits }
+    }
+
     /// Returns `num_bits_needed` from the front of [`EncodableData`].
     /// 
     /// Masks and shifts `value` so the bits in the proper location are returned.
@@ -113,6 +743,7 @@ impl EncodableData {
     /// assert_eq!(2, encodable_data.get_next_bits_to_encode(4)); // 0b0010
     /// assert_eq!(2, encodable_data.get_next_bits_to_encode(2)); // 0b10
     /// ```
+    #[inline]
     pub fn get_next_bits_to_encode(&mut self, num_bits_needed: u8) -> u8 {
         self.num_bits -= num_bits_needed;
         let mask: u128 = (get_mask(num_bits_needed) as u128) << self.num_bits;
@@ -139,6 +770,7 @@ impl EncodableData {
     /// 
     /// assert_eq!(true, encodable_data.has_bits_for_char(5));
     /// ```
+    #[inline]
     pub fn has_bits_for_char(self, num_bits_in_char: u8) -> bool {
         self.num_bits >= num_bits_in_char
     }
@@ -150,6 +782,13 @@ impl EncodableData {
     /// bits can be added in their proper places. `num_bits` gets decremented to
     /// keep track of how many bits are still needed to fill [`EncodableData`].
     /// 
+    /// Assumes `num_bits_to_add` is no more than `num_bits` and that `value`
+    /// has at least `num_bits_to_add` unused high bits. Decoding a label with a
+    /// valid layout always holds to this, but nothing is checked: a larger
+    /// `num_bits_to_add` panics in debug builds and silently shifts bits out of
+    /// `value` in release builds. Use [`try_add_bits`](EncodableData::try_add_bits)
+    /// when the layout isn't trusted.
+    /// 
     /// # Examples:
     /// ```
     /// use amazon_cloudfront_client_routing_lib::client_routing_label::EncodableData;
@@ -165,11 +804,58 @@ impl EncodableData {
     /// encodable_data.add_bits(3, 6);
     /// assert_eq!(174, encodable_data.value);
     /// ```
+    #[inline]
     pub fn add_bits(&mut self, num_bits_to_add: u8, value_to_add: u8) {
         self.num_bits -= num_bits_to_add;
         self.value <<= num_bits_to_add;
         self.value |= value_to_add as u64;
     }
+
+    /// Same as [`add_bits`](EncodableData::add_bits) but returns an
+    /// [`AddBitsOverflowError`] instead of overflowing.
+    ///
+    /// Fails without changing [`EncodableData`] if `num_bits_to_add` is more
+    /// than `num_bits` or if shifting `value` left by `num_bits_to_add` would
+    /// drop set bits.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::EncodableData;
+    ///
+    /// let mut encodable_data = EncodableData {
+    ///     value: 0,
+    ///     num_bits: 10
+    /// };
+    ///
+    /// assert!(encodable_data.try_add_bits(6, 21).is_ok());
+    /// assert_eq!(21, encodable_data.value);
+    ///
+    /// match encodable_data.try_add_bits(5, 6) {
+    ///     Ok(()) => panic!("Didn't throw error when should have."),
+    ///     Err(e) => assert_eq!("Adding 5 bits overflows 4 remaining bits", e.to_string())
+    /// };
+    /// assert_eq!(21, encodable_data.value);
+    /// ```
+    pub fn try_add_bits(
+        &mut self,
+        num_bits_to_add: u8,
+        value_to_add: u8,
+    ) -> Result<(), AddBitsOverflowError> {
+        if num_bits_to_add > self.num_bits
+            || num_bits_to_add as u32 > self.value.leading_zeros()
+        {
+            return Err(AddBitsOverflowError {
+                num_bits_to_add,
+                num_bits: self.num_bits,
+            });
+        }
+
+        self.num_bits -= num_bits_to_add;
+        self.value = self.value.checked_shl(num_bits_to_add as u32).unwrap_or(0);
+        self.value |= value_to_add as u64;
+
+        Ok(())
+    }
 }
 
 /// Struct containing data to encode and what encoding system to use.
@@ -179,6 +865,11 @@ impl EncodableData {
 /// implementation should be used for creating this struct to ensure each item
 /// in the `encodable_data` contains the proper `num_bits` value.
 ///
+/// Prefer [`set_data`](ClientRoutingLabel::set_data) or the per-field setters
+/// like [`set_cgid`](ClientRoutingLabel::set_cgid) over writing
+/// `encodable_data` by index, since the setters check each value fits its
+/// field.
+///
 /// # Examples
 /// ```
 /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
@@ -198,34 +889,129 @@ pub struct ClientRoutingLabel {
 
 impl Default for ClientRoutingLabel {
     fn default() -> Self {
-        let sdk_version = EncodableData {
-            value: CLIENT_ROUTING_LABEL_VERSION as u64,
-            num_bits: 10,
-        };
-        let is_ipv6: EncodableData = EncodableData {
-            value: 0,
-            num_bits: 1,
-        };
-        let client_subnet = EncodableData {
-            value: 0,
-            num_bits: 64,
-        };
-        let subnet_mask = EncodableData {
-            value: 0,
-            num_bits: 6,
-        };
-        let cgid = EncodableData {
-            value: 0,
-            num_bits: 64,
-        };
         Self {
-            encodable_data: [sdk_version, is_ipv6, client_subnet, subnet_mask, cgid],
-            encoding_system: Base32 {},
+            encodable_data: DEFAULT_ENCODABLE_DATA,
+            encoding_system: Base32::default(),
         }
     }
 }
 
+/// `encodable_data` of the default [`ClientRoutingLabel`] layout, one item per
+/// field in [`LABEL_FIELDS`] with every value but the version 0.
+const DEFAULT_ENCODABLE_DATA: [EncodableData; 5] = [
+    EncodableData::new(CLIENT_ROUTING_LABEL_VERSION as u64, LABEL_FIELDS[0].1),
+    EncodableData::new(0, LABEL_FIELDS[1].1),
+    EncodableData::new(0, LABEL_FIELDS[2].1),
+    EncodableData::new(0, LABEL_FIELDS[3].1),
+    EncodableData::new(0, LABEL_FIELDS[4].1),
+];
+
 impl ClientRoutingLabel {
+    /// Returns a [`ClientRoutingLabel`] whose layout has no sdk version bits.
+    ///
+    /// Used when the version is carried out-of-band instead of in the leading
+    /// 10 bits of the label. `version` selects the field layout and is kept as
+    /// the sdk version value, but the sdk version item has a `num_bits` of 0 so
+    /// it is neither encoded nor decoded. Only one layout exists today, so every
+    /// `version` currently selects it.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    ///
+    /// let mut client_routing_label = ClientRoutingLabel::versionless(1);
+    /// assert_eq!(135, client_routing_label.get_total_num_bits());
+    /// assert_eq!("aaaaaaaaaaaaaaaaaaaaaaaaaaa", client_routing_label.encode());
+    /// ```
+    pub fn versionless(version: u16) -> Self {
+        let mut label = Self::default();
+        label.encodable_data[0] = EncodableData {
+            value: version as u64,
+            num_bits: 0,
+        };
+
+        label
+    }
+
+    /// Returns a [`ClientRoutingLabel`] whose cgid field is 32 bits, for
+    /// cgids hashed with [`hash_cgid_crc32`](crate::hash::hash_cgid_crc32).
+    ///
+    /// Every other field is the same as the default layout, so labels are 113
+    /// bits, or 23 chars. Requires the `crc` feature.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    ///
+    /// let mut client_routing_label = ClientRoutingLabel::crc32();
+    /// assert_eq!(113, client_routing_label.get_total_num_bits());
+    /// assert_eq!(23, client_routing_label.encode().len());
+    /// ```
+    #[cfg(feature = "crc")]
+    pub fn crc32() -> Self {
+        let mut label = Self::default();
+        label.encodable_data[4].num_bits = 32;
+
+        label
+    }
+
+    /// Returns a [`ClientRoutingLabel`] whose cgid field is a `token_bits` wide
+    /// token instead of a 64 bit hash.
+    ///
+    /// For low cardinality cgids mapped to small integer tokens outside this
+    /// library, which are encoded as is and take fewer bits than a hash. Every
+    /// other field is the same as the default layout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `token_bits` is more than 64.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    ///
+    /// let mut client_routing_label = ClientRoutingLabel::token(12);
+    /// assert_eq!(93, client_routing_label.get_total_num_bits());
+    /// assert_eq!(19, client_routing_label.encode().len());
+    /// ```
+    pub fn token(token_bits: u8) -> Self {
+        assert!(token_bits <= 64, "token has more than 64 bits");
+
+        let mut label = Self::default();
+        label.encodable_data[4].num_bits = token_bits;
+
+        label
+    }
+
+    /// Returns a [`ClientRoutingLabel`] holding the values of `decoded`.
+    ///
+    /// Maps each field of a [`DecodedClientRoutingLabel`] back into
+    /// `encodable_data`, so a decoded label can be encoded again. Calling
+    /// [`encode`](ClientRoutingLabel::encode) on the result produces the label
+    /// `decoded` was decoded from.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    /// use amazon_cloudfront_client_routing_lib::decode_request_data;
+    ///
+    /// let decoded = decode_request_data("abfku6xaaaaaaaamhmnjxo5hdzrje.example.com").unwrap();
+    ///
+    /// let mut client_routing_label = ClientRoutingLabel::from_decoded(&decoded);
+    /// assert_eq!("abfku6xaaaaaaaamhmnjxo5hdzrje", client_routing_label.encode());
+    /// ```
+    pub fn from_decoded(decoded: &DecodedClientRoutingLabel) -> Self {
+        let mut label = Self::default();
+        label.encodable_data[0].value = decoded.client_sdk_version as u64;
+        label.encodable_data[1].value = decoded.is_ipv6 as u64;
+        label.encodable_data[2].value = u64::from_be_bytes(decoded.client_subnet);
+        // an Ipv6 /64 mask doesn't fit the field and is encoded as 0
+        label.encodable_data[3].value = decoded.subnet_mask as u64 & get_mask(LABEL_FIELDS[3].1);
+        label.encodable_data[4].value = decoded.cgid;
+
+        label
+    }
+
     /// Sets client subnet and cgid data in [`ClientRoutingLabel`].
     ///
     /// Takes in 2 parameters: `client_subnet_encoding_data` and `cgid`.
@@ -233,6 +1019,16 @@ impl ClientRoutingLabel {
     /// struct and has the formatted values for `is_ipv6`, `client_subnet`, and
     /// `subnet_mask`.
     ///
+    /// The subnet mask field is 6 bits, so an Ipv6 /64 mask is encoded as 0 and
+    /// decoded as 64 again. An Ipv6 mask of 0 keeps no bits of the address, so
+    /// it's encoded as the no subnet sentinel, the same as an invalid client ip,
+    /// rather than a 0 that would decode as /64.
+    ///
+    /// A `subnet_mask` over 32 for Ipv4 or 64 for Ipv6 is a programming error
+    /// and panics in debug builds. Release builds stay lenient and keep only
+    /// the field's low 6 bits, the same as any other value that doesn't fit
+    /// its field.
+    ///
     /// # Examples:
     /// ```
     /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
@@ -249,12 +1045,209 @@ impl ClientRoutingLabel {
     /// client_routing_label.set_data(client_subnet_encoding_data, cgid);
     /// ```
     pub fn set_data(&mut self, client_subnet_encoding_data: ClientSubnetEncodingData, cgid: u64) {
-        self.encodable_data[1].value = client_subnet_encoding_data.is_ipv6;
-        self.encodable_data[2].value = client_subnet_encoding_data.client_subnet;
-        self.encodable_data[3].value = client_subnet_encoding_data.subnet_mask;
+        let ClientSubnetEncodingData {
+            client_subnet,
+            subnet_mask,
+            is_ipv6,
+        } = client_subnet_encoding_data;
+        let max_subnet_mask = max_subnet_mask(is_ipv6 != 0);
+        debug_assert!(
+            subnet_mask <= max_subnet_mask as u64,
+            "subnet_mask value {} is over {}",
+            subnet_mask,
+            max_subnet_mask
+        );
+
+        self.set_subnet_fields(is_ipv6, client_subnet, subnet_mask);
         self.encodable_data[4].value = cgid;
     }
 
+    /// Sets the is ipv6, client subnet, and subnet mask fields for
+    /// [`set_data`](ClientRoutingLabel::set_data) and
+    /// [`set_subnet_mask`](ClientRoutingLabel::set_subnet_mask), with an Ipv6
+    /// /0 as the no subnet sentinel and an Ipv6 /64 as 0.
+    fn set_subnet_fields(&mut self, is_ipv6: u64, client_subnet: u64, subnet_mask: u64) {
+        let (is_ipv6, client_subnet) = if is_ipv6 != 0 && subnet_mask == 0 {
+            (0, 0)
+        } else {
+            (is_ipv6, client_subnet)
+        };
+
+        self.encodable_data[1].value = is_ipv6;
+        self.encodable_data[2].value = client_subnet;
+        // an Ipv6 /64 mask doesn't fit the field and is encoded as 0
+        self.encodable_data[3].value = subnet_mask & get_mask(self.encodable_data[3].num_bits);
+    }
+
+    /// Sets the sdk version field.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sdk_version` doesn't fit in the field's `num_bits`, 10 for
+    /// the default layout. A [`versionless`](ClientRoutingLabel::versionless)
+    /// label doesn't encode its version, so any value is kept.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    ///
+    /// let mut client_routing_label = ClientRoutingLabel::default();
+    /// client_routing_label.set_sdk_version(2);
+    /// assert_eq!("acaaaaaaaaaaaaaaaaaaaaaaaaaaa", client_routing_label.encode());
+    /// ```
+    pub fn set_sdk_version(&mut self, sdk_version: u16) {
+        self.set_field(0, sdk_version as u64);
+    }
+
+    /// Sets the is ipv6 field.
+    pub fn set_is_ipv6(&mut self, is_ipv6: bool) {
+        self.set_field(1, is_ipv6 as u64);
+    }
+
+    /// Sets the client subnet field to `client_subnet`, with the network
+    /// prefix in the most significant bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `client_subnet` doesn't fit in the field's `num_bits`, which
+    /// can't happen for the default 64 bit layout.
+    pub fn set_client_subnet(&mut self, client_subnet: u64) {
+        self.set_field(2, client_subnet);
+    }
+
+    /// Sets the subnet mask field.
+    ///
+    /// Encodes the mask the same as [`set_data`](ClientRoutingLabel::set_data)
+    /// for the label's current is ipv6 field, so call it after
+    /// [`set_is_ipv6`](ClientRoutingLabel::set_is_ipv6) and
+    /// [`set_client_subnet`](ClientRoutingLabel::set_client_subnet). An Ipv6
+    /// /64 mask doesn't fit the 6 bit field and is encoded as 0, and an Ipv6
+    /// /0 clears the is ipv6 and client subnet fields to the no subnet
+    /// sentinel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `subnet_mask` is over 32 for an Ipv4 label or over 64 for an
+    /// Ipv6 label.
+    pub fn set_subnet_mask(&mut self, subnet_mask: u8) {
+        let is_ipv6 = self.encodable_data[1].value;
+        let max_subnet_mask = max_subnet_mask(is_ipv6 != 0);
+        assert!(
+            subnet_mask <= max_subnet_mask,
+            "subnet_mask value {} is over {}",
+            subnet_mask,
+            max_subnet_mask
+        );
+
+        self.set_subnet_fields(is_ipv6, self.encodable_data[2].value, subnet_mask as u64);
+    }
+
+    /// Sets the cgid field to an already hashed `cgid`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cgid` doesn't fit in the field's `num_bits`, e.g. a 64 bit
+    /// hash on a [`crc32`](ClientRoutingLabel::crc32) label.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    /// use amazon_cloudfront_client_routing_lib::hash::hash_cgid;
+    /// use amazon_cloudfront_client_routing_lib::ip::parse_client_ip;
+    ///
+    /// let mut client_routing_label = ClientRoutingLabel::default();
+    /// client_routing_label.set_is_ipv6(false);
+    /// client_routing_label.set_client_subnet(u64::from_be_bytes([1, 2, 3, 0, 0, 0, 0, 0]));
+    /// client_routing_label.set_subnet_mask(24);
+    /// client_routing_label.set_cgid(hash_cgid("mv-456"));
+    ///
+    /// let mut expected_label = ClientRoutingLabel::default();
+    /// expected_label.set_data(parse_client_ip("1.2.3.4"), hash_cgid("mv-456"));
+    /// assert_eq!(expected_label.encode(), client_routing_label.encode());
+    /// ```
+    pub fn set_cgid(&mut self, cgid: u64) {
+        self.set_field(4, cgid);
+    }
+
+    /// Sets the value of the item in `encodable_data` at `index`, panicking if
+    /// it doesn't fit in the item's `num_bits`. Items with a `num_bits` of 0
+    /// aren't encoded so aren't checked.
+    fn set_field(&mut self, index: usize, value: u64) {
+        let data = &mut self.encodable_data[index];
+        assert!(
+            data.num_bits == 0 || value <= get_mask(data.num_bits),
+            "{} value {} doesn't fit in {} bits",
+            LABEL_FIELDS[index].0,
+            value,
+            data.num_bits
+        );
+
+        data.value = value;
+    }
+
+    /// Restores `encodable_data` to the values and `num_bits` of
+    /// [`ClientRoutingLabel::default`]. `encoding_system` is left unchanged.
+    ///
+    /// Encoding consumes the `num_bits` of every item in `encodable_data`, so a
+    /// label has to be reset before it can be reused. The intended pattern is
+    /// to keep one label per thread and call `reset` before each
+    /// [`set_data`](ClientRoutingLabel::set_data) and
+    /// [`encode`](ClientRoutingLabel::encode), or before a
+    /// [`decode`](ClientRoutingLabel::decode) that follows an encode.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    /// use amazon_cloudfront_client_routing_lib::ip::parse_client_ip;
+    ///
+    /// let mut client_routing_label = ClientRoutingLabel::default();
+    /// client_routing_label.set_data(parse_client_ip("1.2.3.4"), 0);
+    /// assert_eq!("abacaqdaaaaaaaamaaaaaaaaaaaaa", client_routing_label.encode());
+    ///
+    /// client_routing_label.reset();
+    /// assert_eq!(145, client_routing_label.get_total_num_bits());
+    /// assert_eq!("abaaaaaaaaaaaaaaaaaaaaaaaaaaa", client_routing_label.encode());
+    /// ```
+    pub fn reset(&mut self) {
+        self.encodable_data = DEFAULT_ENCODABLE_DATA;
+    }
+
+    /// Returns the `value` of each item in `encodable_data`, in order.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    ///
+    /// let client_routing_label = ClientRoutingLabel::default();
+    /// assert_eq!([1, 0, 0, 0, 0], client_routing_label.field_values());
+    /// ```
+    pub fn field_values(&self) -> [u64; 5] {
+        let mut field_values = [0_u64; 5];
+        for (field_value, data) in field_values.iter_mut().zip(self.encodable_data.iter()) {
+            *field_value = data.value;
+        }
+
+        field_values
+    }
+
+    /// Sets the `value` of each item in `encodable_data` from `field_values`,
+    /// in order. `num_bits` is left unchanged.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    ///
+    /// let mut client_routing_label = ClientRoutingLabel::default();
+    /// client_routing_label.set_field_values([1, 0, 6148494311290830848, 24, 8517775255794402596]);
+    ///
+    /// assert_eq!("abfku6xaaaaaaaamhmnjxo5hdzrje", client_routing_label.encode());
+    /// ```
+    pub fn set_field_values(&mut self, field_values: [u64; 5]) {
+        for (data, field_value) in self.encodable_data.iter_mut().zip(field_values) {
+            data.value = field_value;
+        }
+    }
+
     /// Encodes `encodable_data` and returns encoded client routing label
     ///
     /// Calls the encode function of `encoding_system`. Each [`EncodableData`]
@@ -279,11 +1272,63 @@ impl ClientRoutingLabel {
     /// assert_eq!("abfku6xaaaaaaaamhmnjxo5hdzrje", client_routing_label.encode());
     /// ```
     pub fn encode(&mut self) -> String {
+        self.debug_assert_values_fit();
         self.encoding_system.encode(&mut self.encodable_data)
     }
 
+    /// Encodes `encodable_data` and appends the encoded client routing label to
+    /// `encoded_label`.
+    ///
+    /// Same as [`encode`](ClientRoutingLabel::encode) but writes into an
+    /// existing [`String`] so callers can reuse its allocation.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    /// use amazon_cloudfront_client_routing_lib::ip::ClientSubnetEncodingData;
+    ///
+    /// let cgid = 8517775255794402596;
+    /// let client_subnet_encoding_data = ClientSubnetEncodingData {
+    ///     is_ipv6: 0,
+    ///     client_subnet: 6148494311290830848,
+    ///     subnet_mask: 24,
+    /// };
+    ///
+    /// let mut client_routing_label = ClientRoutingLabel::default();
+    /// client_routing_label.set_data(client_subnet_encoding_data, cgid);
+    ///
+    /// let mut encoded_label = String::new();
+    /// client_routing_label.encode_to(&mut encoded_label);
+    /// assert_eq!("abfku6xaaaaaaaamhmnjxo5hdzrje", encoded_label);
+    /// ```
+    pub fn encode_to(&mut self, encoded_label: &mut String) {
+        self.debug_assert_values_fit();
+        self.encoding_system.encode_to(&mut self.encodable_data, encoded_label)
+    }
+
+    /// Panics in debug builds if any `value` in `encodable_data` has more bits
+    /// than its `num_bits`.
+    ///
+    /// Encoding only keeps the least significant `num_bits` of each value, so
+    /// an oversized value, e.g. a corrupted version on a reused label, would
+    /// otherwise be silently truncated. Release builds skip the check and
+    /// truncate as documented on [`EncodableData`]. Items with a `num_bits` of
+    /// 0, like the version of a [`versionless`](ClientRoutingLabel::versionless)
+    /// label, aren't encoded so aren't checked.
+    fn debug_assert_values_fit(&self) {
+        for (field, data) in LABEL_FIELDS.iter().zip(self.encodable_data.iter()) {
+            debug_assert!(
+                data.num_bits == 0 || data.value <= get_mask(data.num_bits),
+                "{} value {} doesn't fit in {} bits",
+                field.0,
+                data.value,
+                data.num_bits
+            );
+        }
+    }
+
     /// Decodes `client_routing_label` and returns a result containing either a
-    /// [`DecodedClientRoutingLabel`] or a [`DecodeLengthError`] if the
+    /// [`DecodedClientRoutingLabel`] or a [`DecodeError`] if the
     /// `client_routing_label` is invalid.
     ///
     /// # Examples:
@@ -308,7 +1353,7 @@ impl ClientRoutingLabel {
     pub fn decode(
         &mut self,
         client_routing_label: &[u8],
-    ) -> Result<DecodedClientRoutingLabel, DecodeLengthError> {
+    ) -> Result<DecodedClientRoutingLabel, DecodeError> {
         let total_num_bits = self.get_total_num_bits();
         let decoded_label = self.encoding_system.decode(
             &mut self.encodable_data,
@@ -338,15 +1383,843 @@ impl ClientRoutingLabel {
         self.encodable_data.iter().fold(0, |a, b| a + b.num_bits)
     }
 
+    /// Returns how many bits the encoded label has room for.
+    ///
+    /// Each Base32 char holds 5 bits, so this is the encoded label length
+    /// times 5.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    ///
+    /// let client_routing_label = ClientRoutingLabel::default();
+    /// assert_eq!(145, client_routing_label.capacity_bits());
+    /// ```
+    pub fn capacity_bits(&self) -> u8 {
+        (self.encoding_system.encoded_len(&self.encodable_data) * 5) as u8
+    }
+
+    /// Returns how many padding bits the encoded label carries after the
+    /// fields.
+    ///
+    /// Layouts whose fields don't fill the last Base32 char are padded with
+    /// zero bits when encoding, and those bits are ignored when decoding. The
+    /// default layout uses 145 of 145 bits so has no padding.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    ///
+    /// let mut client_routing_label = ClientRoutingLabel::default();
+    /// assert_eq!(0, client_routing_label.unused_bits());
+    ///
+    /// client_routing_label.encodable_data[4].num_bits = 63;
+    /// assert_eq!(1, client_routing_label.unused_bits());
+    /// ```
+    pub fn unused_bits(&self) -> u8 {
+        let used_bits: u8 = self.encodable_data.iter().map(|data| data.num_bits).sum();
+        self.capacity_bits() - used_bits
+    }
+
     /// Creates and returns [`DecodedClientRoutingLabel`] based on
     /// `encodable_data`.
-    fn get_decoded_client_routing_label(&mut self) -> DecodedClientRoutingLabel {
+    ///
+    /// An Ipv6 `subnet_mask` of 0 is reconstructed as 64, see
+    /// [`parse_client_ip_with_ipv6_mask`](crate::ip::parse_client_ip_with_ipv6_mask).
+    ///
+    /// The version field is 10 bits, so its value always fits the u16
+    /// `client_sdk_version`. A layout with a version field wider than 16 bits
+    /// would be silently truncated by the cast, so that's asserted in debug
+    /// builds.
+    pub(crate) fn get_decoded_client_routing_label(&mut self) -> DecodedClientRoutingLabel {
+        debug_assert!(
+            self.encodable_data[0].value <= u16::MAX as u64,
+            "decoded version {} doesn't fit in client_sdk_version",
+            self.encodable_data[0].value
+        );
+
+        let is_ipv6 = self.encodable_data[1].value != 0;
+        let mut subnet_mask = self.encodable_data[3].value as u8;
+        if is_ipv6 && subnet_mask == 0 {
+            subnet_mask = 64;
+        }
+
         DecodedClientRoutingLabel {
             client_sdk_version: self.encodable_data[0].value as u16,
-            is_ipv6: self.encodable_data[1].value != 0,
+            is_ipv6,
             client_subnet: self.encodable_data[2].value.to_be_bytes(),
-            subnet_mask: self.encodable_data[3].value as u8,
+            subnet_mask,
             cgid: self.encodable_data[4].value,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        layout_label_len, ClientRoutingLabel, DecodedClientRoutingLabel, EncodableData,
+        ENCODED_LABEL_LEN, LABEL_FIELDS, LABEL_FIELD_OFFSETS,
+    };
+    use crate::ip::{parse_client_ip, parse_client_ip_with_ipv6_mask, ClientSubnetEncodingData};
+
+    fn decoded_label_with_mask(subnet_mask: u8) -> DecodedClientRoutingLabel {
+        DecodedClientRoutingLabel {
+            client_sdk_version: 1,
+            is_ipv6: false,
+            client_subnet: [0; 8],
+            subnet_mask,
+            cgid: 0,
+        }
+    }
+
+    #[test]
+    fn validate_encoded_label_len_matches_default_layout() {
+        let mut client_routing_label = ClientRoutingLabel::default();
+
+        assert_eq!(ENCODED_LABEL_LEN, client_routing_label.encode().len());
+    }
+
+    #[test]
+    fn validate_setters_match_set_data() {
+        for client_subnet_encoding_data in [
+            parse_client_ip("85.83.215.126"),
+            parse_client_ip("2001:db8:1:2::1"),
+            parse_client_ip_with_ipv6_mask("2001:db8:1:2::1", 64),
+            parse_client_ip("invalid"),
+        ] {
+            let cgid = 8517775255794402596;
+            let mut expected_label = ClientRoutingLabel::default();
+            expected_label.set_data(client_subnet_encoding_data, cgid);
+
+            let mut client_routing_label = ClientRoutingLabel::default();
+            client_routing_label.set_sdk_version(1);
+            client_routing_label.set_is_ipv6(client_subnet_encoding_data.is_ipv6 == 1);
+            client_routing_label.set_client_subnet(client_subnet_encoding_data.client_subnet);
+            client_routing_label.set_subnet_mask(client_subnet_encoding_data.subnet_mask as u8);
+            client_routing_label.set_cgid(cgid);
+
+            assert_eq!(expected_label.encode(), client_routing_label.encode());
+        }
+    }
+
+    #[test]
+    fn validate_set_sdk_version_versionless() {
+        let mut client_routing_label = ClientRoutingLabel::versionless(1);
+        client_routing_label.set_sdk_version(u16::MAX);
+
+        assert_eq!(u16::MAX as u64, client_routing_label.encodable_data[0].value);
+    }
+
+    #[test]
+    #[should_panic(expected = "client_sdk_version value 1024 doesn't fit in 10 bits")]
+    fn validate_set_sdk_version_too_wide() {
+        ClientRoutingLabel::default().set_sdk_version(1024);
+    }
+
+    #[test]
+    #[should_panic(expected = "subnet_mask value 65 is over 64")]
+    fn validate_set_subnet_mask_too_wide() {
+        let mut client_routing_label = ClientRoutingLabel::default();
+        client_routing_label.set_is_ipv6(true);
+        client_routing_label.set_subnet_mask(65);
+    }
+
+    #[test]
+    fn validate_set_subnet_mask_matches_set_data_at_ipv6_boundaries() {
+        let client_subnet = u64::from_be_bytes([0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0x00, 0x94]);
+
+        for subnet_mask in [0, 64] {
+            let mut expected_label = ClientRoutingLabel::default();
+            expected_label.set_data(
+                ClientSubnetEncodingData {
+                    client_subnet,
+                    subnet_mask,
+                    is_ipv6: 1,
+                },
+                0,
+            );
+
+            let mut client_routing_label = ClientRoutingLabel::default();
+            client_routing_label.set_is_ipv6(true);
+            client_routing_label.set_client_subnet(client_subnet);
+            client_routing_label.set_subnet_mask(subnet_mask as u8);
+
+            assert_eq!(expected_label.field_values(), client_routing_label.field_values());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "subnet_mask value 64 is over 32")]
+    fn validate_set_subnet_mask_ipv4_64_panics() {
+        let mut client_routing_label = ClientRoutingLabel::default();
+        client_routing_label.set_is_ipv6(false);
+        client_routing_label.set_subnet_mask(64);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "subnet_mask value 64 is over 32")]
+    fn validate_set_data_ipv4_64_panics_in_debug() {
+        ClientRoutingLabel::default().set_data(
+            ClientSubnetEncodingData {
+                client_subnet: 0,
+                subnet_mask: 64,
+                is_ipv6: 0,
+            },
+            0,
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "subnet_mask value 65 is over 64")]
+    fn validate_set_data_subnet_mask_too_wide_panics_in_debug() {
+        ClientRoutingLabel::default().set_data(
+            ClientSubnetEncodingData {
+                client_subnet: 0,
+                subnet_mask: 65,
+                is_ipv6: 1,
+            },
+            0,
+        );
+    }
+
+    #[test]
+    fn validate_set_data_ipv6_mask_boundaries() {
+        let mut label = ClientRoutingLabel::default();
+        let client_subnet = u64::from_be_bytes([0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0x00, 0x94]);
+
+        // an Ipv6 mask of 0 is the no subnet sentinel, not a /64
+        label.set_data(
+            ClientSubnetEncodingData {
+                client_subnet,
+                subnet_mask: 0,
+                is_ipv6: 1,
+            },
+            42,
+        );
+        let decoded = match ClientRoutingLabel::default().decode(label.encode().as_bytes()) {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert!(!decoded.has_subnet());
+        assert!(!decoded.is_ipv6);
+        assert_eq!([0; 8], decoded.client_subnet);
+        assert_eq!(0, decoded.subnet_mask);
+        assert_eq!(42, decoded.cgid);
+
+        label.reset();
+        label.set_data(
+            ClientSubnetEncodingData {
+                client_subnet,
+                subnet_mask: 64,
+                is_ipv6: 1,
+            },
+            42,
+        );
+        let decoded = match ClientRoutingLabel::default().decode(label.encode().as_bytes()) {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert!(decoded.has_subnet());
+        assert!(decoded.is_ipv6);
+        assert_eq!(client_subnet.to_be_bytes(), decoded.client_subnet);
+        assert_eq!(64, decoded.subnet_mask);
+    }
+
+    #[test]
+    #[cfg(feature = "crc")]
+    #[should_panic(expected = "cgid value 4294967296 doesn't fit in 32 bits")]
+    fn validate_set_cgid_too_wide_for_crc32() {
+        ClientRoutingLabel::crc32().set_cgid(1 << 32);
+    }
+
+    #[test]
+    fn validate_field_values_round_trip() {
+        let field_values = [1, 1, 9340004030419828736, 48, 12253709671023643154];
+        let mut client_routing_label = ClientRoutingLabel::default();
+
+        client_routing_label.set_field_values(field_values);
+        assert_eq!(field_values, client_routing_label.field_values());
+        for (data, field_value) in client_routing_label.encodable_data.iter().zip(field_values) {
+            assert_eq!(field_value, data.value);
+        }
+
+        let encoded_label = client_routing_label.encode();
+        let mut decoding_label = ClientRoutingLabel::default();
+        match decoding_label.decode(encoded_label.as_bytes()) {
+            Ok(_decoded) => (),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(field_values, decoding_label.field_values());
+    }
+
+    #[test]
+    fn validate_reset_restores_defaults() {
+        let mut client_routing_label = ClientRoutingLabel::default();
+        client_routing_label.set_data(
+            ClientSubnetEncodingData {
+                client_subnet: 6148494311290830848,
+                subnet_mask: 24,
+                is_ipv6: 0,
+            },
+            8517775255794402596,
+        );
+        assert_eq!("abfku6xaaaaaaaamhmnjxo5hdzrje", client_routing_label.encode());
+        // encoding consumed num_bits
+        assert!(client_routing_label.get_total_num_bits() < 145);
+
+        client_routing_label.reset();
+        let default_label = ClientRoutingLabel::default();
+        for (data, default_data) in client_routing_label
+            .encodable_data
+            .iter()
+            .zip(default_label.encodable_data.iter())
+        {
+            assert_eq!(default_data.value, data.value);
+            assert_eq!(default_data.num_bits, data.num_bits);
+        }
+
+        let decoded = match client_routing_label.decode(b"abydhs4fyq6iaaaykudpmaxncecqs") {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(48, decoded.subnet_mask);
+        assert_eq!(12253709671023643154, decoded.cgid);
+    }
+
+    #[test]
+    fn validate_label_field_offsets() {
+        assert_eq!([0, 10, 11, 75, 81], LABEL_FIELD_OFFSETS);
+        // 10 + 1 + 64 + 6
+        assert_eq!(81, LABEL_FIELD_OFFSETS[4]);
+        assert_eq!("cgid", LABEL_FIELDS[4].0);
+
+        let mut offset = 0;
+        for (data, field_offset) in ClientRoutingLabel::default()
+            .encodable_data
+            .iter()
+            .zip(LABEL_FIELD_OFFSETS)
+        {
+            assert_eq!(offset, field_offset);
+            offset += data.num_bits;
+        }
+    }
+
+    #[test]
+    fn validate_decode_ignores_padding_bits() {
+        let mut label = ClientRoutingLabel::default();
+        label.encodable_data[4].num_bits = 63;
+        assert_eq!(145, label.capacity_bits());
+        assert_eq!(1, label.unused_bits());
+
+        label.set_data(
+            ClientSubnetEncodingData {
+                client_subnet: 0x0102_0300_0000_0000,
+                subnet_mask: 24,
+                is_ipv6: 0,
+            },
+            0x7fff_ffff_ffff_ffff,
+        );
+        let encoded = label.encode();
+        assert_eq!(ENCODED_LABEL_LEN, encoded.len());
+
+        // Last char holds the final 4 cgid bits then the padding bit, so flip
+        // the padding bit and decode both.
+        let mut padded = encoded.clone().into_bytes();
+        let last_char = padded.len() - 1;
+        assert_eq!(b'6', padded[last_char]);
+        padded[last_char] = b'7';
+
+        label.reset();
+        label.encodable_data[4].num_bits = 63;
+        let decoded = match label.decode(encoded.as_bytes()) {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        label.reset();
+        label.encodable_data[4].num_bits = 63;
+        let decoded_padded = match label.decode(&padded) {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        assert_eq!(decoded.cgid, decoded_padded.cgid);
+        assert_eq!(0x7fff_ffff_ffff_ffff, decoded_padded.cgid);
+        assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], decoded_padded.client_subnet);
+    }
+
+    #[test]
+    fn validate_const_encodable_data() {
+        const LAYOUT: [EncodableData; 5] = [
+            EncodableData::new(1, LABEL_FIELDS[0].1),
+            EncodableData::new(0, LABEL_FIELDS[1].1),
+            EncodableData::new(0, LABEL_FIELDS[2].1),
+            EncodableData::new(0, LABEL_FIELDS[3].1),
+            EncodableData::new(0, LABEL_FIELDS[4].1),
+        ];
+
+        let widths: Vec<u8> = LAYOUT.iter().map(|data| data.num_bits).collect();
+        assert_eq!(vec![10, 1, 64, 6, 64], widths);
+        assert_eq!(1, LAYOUT[0].value);
+
+        let mut label = ClientRoutingLabel::default();
+        for (data, expected) in label.encodable_data.iter().zip(LAYOUT) {
+            assert_eq!(expected.value, data.value);
+            assert_eq!(expected.num_bits, data.num_bits);
+        }
+        assert_eq!(145, label.get_total_num_bits());
+    }
+
+    #[test]
+    fn validate_is_consistent() {
+        let mut label = ClientRoutingLabel::default();
+
+        for encoded_label in [b"abfku6xaaaaaaaamotptyubibrji6", b"abydhs4fyq6iaaaykudpmaxncecqs"] {
+            let decoded = match label.decode(encoded_label) {
+                Ok(decoded) => decoded,
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+            assert!(decoded.is_consistent());
+        }
+
+        // set_data won't take an Ipv4 /48, so write the fields directly
+        label.set_field_values([1, 0, 0x0102_0300_0000_0000, 48, 0]);
+        let encoded_label = label.encode();
+        let decoded = match ClientRoutingLabel::default().decode(encoded_label.as_bytes()) {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(48, decoded.subnet_mask);
+        assert!(!decoded.is_consistent());
+
+        assert!(decoded_label_with_mask(32).is_consistent());
+        assert!(!decoded_label_with_mask(33).is_consistent());
+        assert!(DecodedClientRoutingLabel { is_ipv6: true, ..decoded_label_with_mask(48) }.is_consistent());
+        assert!(!DecodedClientRoutingLabel { is_ipv6: true, ..decoded_label_with_mask(65) }.is_consistent());
+
+        // Ipv6 masks wider than the default /48 that encoding produces
+        for ipv6_subnet_mask in [56, 64] {
+            let mut label = ClientRoutingLabel::default();
+            label.set_data(
+                parse_client_ip_with_ipv6_mask("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", ipv6_subnet_mask),
+                0,
+            );
+            let decoded = match ClientRoutingLabel::default().decode(label.encode().as_bytes()) {
+                Ok(decoded) => decoded,
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+            assert_eq!(ipv6_subnet_mask, decoded.subnet_mask);
+            assert!(decoded.is_consistent());
+        }
+    }
+
+    #[test]
+    fn validate_is_empty() {
+        let mut label = ClientRoutingLabel::default();
+
+        let decoded = match label.decode(b"abaaaaaaaaaaaaaaaaaaaaaaaaaaa") {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(1, decoded.client_sdk_version);
+        assert!(decoded.is_empty());
+
+        // version doesn't matter
+        let decoded = match label.decode(b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaa") {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert!(decoded.is_empty());
+
+        let decoded = match label.decode(b"abfku6xaaaaaaaamotptyubibrji6") {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert!(!decoded.is_empty());
+
+        // a 0.0.0.0 client ip keeps its mask
+        assert!(!decoded_label_with_mask(24).is_empty());
+    }
+
+    #[test]
+    fn validate_max_version_decodes_intact() {
+        let mut label = ClientRoutingLabel::default();
+        label.set_field_values([1023, 0, 0, 0, 0]);
+        let encoded = label.encode();
+        assert_eq!("77aaaaaaaaaaaaaaaaaaaaaaaaaaa", encoded);
+
+        let decoded = match ClientRoutingLabel::default().decode(encoded.as_bytes()) {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(1023, decoded.client_sdk_version);
+        assert!(decoded.is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "doesn't fit in client_sdk_version")]
+    fn validate_version_wider_than_u16_panics_in_debug() {
+        let mut label = ClientRoutingLabel::default();
+        label.encodable_data[0].num_bits = 20;
+        label.encodable_data[4].num_bits = 54;
+        label.set_field_values([1 << 16, 0, 0, 0, 0]);
+        let encoded = label.encode();
+
+        let mut wide_label = ClientRoutingLabel::default();
+        wide_label.encodable_data[0].num_bits = 20;
+        wide_label.encodable_data[4].num_bits = 54;
+        let _decoded = wide_label.decode(encoded.as_bytes());
+    }
+
+    #[test]
+    fn validate_shard_deterministic() {
+        let mut label = ClientRoutingLabel::default();
+        let decoded = match label.decode(b"abfku6xaaaaaaaamotptyubibrji6") {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        // same routing from a newer sdk version
+        let mut other_version = decoded;
+        other_version.client_sdk_version = 2;
+
+        for num_shards in [1, 2, 7, 16, 1000, u32::MAX] {
+            let shard = decoded.shard(num_shards);
+            assert!(shard < num_shards);
+            assert_eq!(shard, decoded.shard(num_shards));
+            assert_eq!(shard, other_version.shard(num_shards));
+        }
+    }
+
+    #[test]
+    fn validate_shard_roughly_uniform() {
+        const NUM_SHARDS: u32 = 16;
+        const NUM_LABELS: u32 = 16_000;
+
+        // consecutive subnets with one cgid, and one subnet with consecutive
+        // cgids, are the least random inputs a backend is likely to see
+        let mut by_subnet = [0_u32; NUM_SHARDS as usize];
+        let mut by_cgid = [0_u32; NUM_SHARDS as usize];
+        for i in 0..NUM_LABELS {
+            let mut decoded = DecodedClientRoutingLabel {
+                client_subnet: ((i as u64) << 40).to_be_bytes(),
+                subnet_mask: 24,
+                cgid: 42,
+                ..Default::default()
+            };
+            by_subnet[decoded.shard(NUM_SHARDS) as usize] += 1;
+
+            decoded.client_subnet = [1, 2, 3, 0, 0, 0, 0, 0];
+            decoded.cgid = i as u64;
+            by_cgid[decoded.shard(NUM_SHARDS) as usize] += 1;
+        }
+
+        let expected = NUM_LABELS / NUM_SHARDS;
+        for count in by_subnet.iter().chain(by_cgid.iter()) {
+            assert!(
+                expected * 8 / 10 < *count && *count < expected * 12 / 10,
+                "{} labels in a shard, expected about {}",
+                count,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "num_shards must be more than 0")]
+    fn validate_shard_zero_shards_panics() {
+        DecodedClientRoutingLabel::default().shard(0);
+    }
+
+    #[test]
+    fn validate_diff_only_cgid() {
+        let mut label = ClientRoutingLabel::default();
+        let decoded = match label.decode(b"abfku6xaaaaaaaamotptyubibrji6") {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        let mut other = decoded;
+        other.cgid = 42;
+
+        let diff = decoded.diff(&other);
+        assert_eq!(1, diff.len());
+        assert_eq!(("cgid", 16843032286346126622, 42), diff[0]);
+
+        assert_eq!(vec![("cgid", 42, 16843032286346126622)], other.diff(&decoded));
+        assert!(decoded.diff(&decoded).is_empty());
+    }
+
+    #[test]
+    fn validate_diff_multiple_fields_in_layout_order() {
+        let decoded = decoded_label_with_mask(24);
+        let mut other = decoded;
+        other.client_sdk_version = 2;
+        other.subnet_mask = 16;
+
+        assert_eq!(
+            vec![("client_sdk_version", 1, 2), ("subnet_mask", 24, 16)],
+            decoded.diff(&other)
+        );
+    }
+
+    #[test]
+    fn validate_subnet_string() {
+        let mut label = ClientRoutingLabel::default();
+
+        let decoded = match label.decode(b"abfku6xaaaaaaaamotptyubibrji6") {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!("85.83.215.0", decoded.subnet_string());
+
+        let decoded = match label.decode(b"abydhs4fyq6iaaaykudpmaxncecqs") {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!("819e:5c2e:21e4::", decoded.subnet_string());
+
+        let decoded = match label.decode(b"abaaaaaaaaaaaaaaaaaaaaaaaaaaa") {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!("0.0.0.0", decoded.subnet_string());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "client_sdk_version value 5000 doesn't fit in 10 bits")]
+    fn validate_encode_oversized_version_panics_in_debug() {
+        let mut label = ClientRoutingLabel::default();
+        label.encodable_data[0].value = 5000;
+
+        label.encode();
+    }
+
+    #[test]
+    fn validate_encode_ipv6_64_mask_fits() {
+        let mut label = ClientRoutingLabel::default();
+        label.set_data(
+            ClientSubnetEncodingData {
+                client_subnet: 0x0102_0304_0506_0708,
+                subnet_mask: 64,
+                is_ipv6: 1,
+            },
+            0,
+        );
+        assert_eq!(0, label.field_values()[3]);
+
+        let decoded = match ClientRoutingLabel::default().decode(label.encode().as_bytes()) {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(64, decoded.subnet_mask);
+        assert_eq!(0, ClientRoutingLabel::from_decoded(&decoded).field_values()[3]);
+    }
+
+    #[test]
+    fn validate_routing_key() {
+        let mut label = ClientRoutingLabel::default();
+        let decoded = match label.decode(b"abfku6xaaaaaaaamotptyubibrji6") {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        // same routing inputs, different version
+        let mut same_routing = decoded;
+        same_routing.client_sdk_version = 2;
+        assert_eq!(decoded.routing_key(), same_routing.routing_key());
+
+        let mut other_cgid = decoded;
+        other_cgid.cgid += 1;
+        assert_ne!(decoded.routing_key(), other_cgid.routing_key());
+
+        let mut other_subnet = decoded;
+        other_subnet.client_subnet[2] = 216;
+        assert_ne!(decoded.routing_key(), other_subnet.routing_key());
+
+        let mut other_mask = decoded;
+        other_mask.subnet_mask = 16;
+        assert_ne!(decoded.routing_key(), other_mask.routing_key());
+
+        let mut other_family = decoded;
+        other_family.is_ipv6 = true;
+        assert_ne!(decoded.routing_key(), other_family.routing_key());
+
+        // an Ipv6 /64 mask keeps its own bits
+        let mut ipv6_64 = other_family;
+        ipv6_64.subnet_mask = 64;
+        assert_eq!(64, (ipv6_64.routing_key() >> 56) as u8 & 0x7f);
+    }
+
+    #[test]
+    fn validate_layout_label_len() {
+        assert_eq!(ENCODED_LABEL_LEN, layout_label_len(&LABEL_FIELDS));
+
+        let layout = [("a", 64), ("b", 64), ("c", 64), ("d", 58)];
+        assert_eq!(50, layout_label_len(&layout));
+
+        assert_eq!(0, layout_label_len(&[]));
+    }
+
+    #[test]
+    fn validate_significant_subnet_bytes() {
+        assert_eq!(3, decoded_label_with_mask(24).significant_subnet_bytes());
+        assert_eq!(6, decoded_label_with_mask(48).significant_subnet_bytes());
+        assert_eq!(0, decoded_label_with_mask(0).significant_subnet_bytes());
+        assert_eq!(3, decoded_label_with_mask(20).significant_subnet_bytes());
+        assert_eq!(8, decoded_label_with_mask(64).significant_subnet_bytes());
+    }
+
+    #[test]
+    fn validate_try_add_bits_near_overflow() {
+        let mut encodable_data = EncodableData {
+            value: 0,
+            num_bits: 64,
+        };
+
+        for _ in 0..12 {
+            match encodable_data.try_add_bits(5, 31) {
+                Ok(()) => (),
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+        }
+        assert_eq!(4, encodable_data.num_bits);
+        assert_eq!(u64::MAX >> 4, encodable_data.value);
+
+        match encodable_data.try_add_bits(5, 31) {
+            Ok(()) => panic!("Didn't throw error when should have"),
+            Err(e) => assert_eq!("Adding 5 bits overflows 4 remaining bits", e.to_string()),
+        };
+        assert_eq!(4, encodable_data.num_bits);
+        assert_eq!(u64::MAX >> 4, encodable_data.value);
+
+        match encodable_data.try_add_bits(4, 15) {
+            Ok(()) => (),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(0, encodable_data.num_bits);
+        assert_eq!(u64::MAX, encodable_data.value);
+    }
+
+    #[test]
+    fn validate_try_add_bits_value_already_full() {
+        let mut encodable_data = EncodableData {
+            value: u64::MAX,
+            num_bits: 8,
+        };
+
+        match encodable_data.try_add_bits(1, 0) {
+            Ok(()) => panic!("Didn't throw error when should have"),
+            Err(e) => assert_eq!("Adding 1 bits overflows 8 remaining bits", e.to_string()),
+        };
+        assert_eq!(u64::MAX, encodable_data.value);
+    }
+
+    #[test]
+    fn validate_from_decoded_round_trip() {
+        let labels: [&[u8]; 5] = [
+            b"abfku6xaaaaaaaamotptyubibrji6",
+            b"abydhs4fyq6iaaaykudpmaxncecqs",
+            b"abaaaaaaaaaaaaaaoqysz2z3j45da",
+            b"abc4aydaaaaaaaamaaaaaaaaaaaaa",
+            b"abaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        ];
+
+        for encoded_label in labels {
+            let decoded = match ClientRoutingLabel::default().decode(encoded_label) {
+                Ok(decoded) => decoded,
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+
+            assert_eq!(
+                encoded_label,
+                ClientRoutingLabel::from_decoded(&decoded).encode().as_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn validate_cgid_hex() {
+        let mut decoded_label = decoded_label_with_mask(24);
+
+        decoded_label.cgid = 16843032286346126622;
+        assert_eq!("e9be78a05018a51e", decoded_label.cgid_hex());
+
+        decoded_label.cgid = 1;
+        assert_eq!("0000000000000001", decoded_label.cgid_hex());
+    }
+
+    #[test]
+    fn validate_ipv6_reconstruction_is_compressed() {
+        let mut decoded_label = decoded_label_with_mask(48);
+        decoded_label.is_ipv6 = true;
+        decoded_label.client_subnet = [0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0, 0];
+
+        assert_eq!(
+            "819e:5c2e:21e4::",
+            decoded_label.to_ip_addr().unwrap().to_string()
+        );
+        assert_eq!(
+            Some(String::from("819e:5c2e:21e4::/48")),
+            decoded_label.to_cidr_string()
+        );
+
+        decoded_label.subnet_mask = 64;
+        decoded_label.client_subnet = [0x81, 0x9e, 0, 0, 0x21, 0xe4, 0, 0x94];
+
+        assert_eq!(
+            Some(String::from("819e:0:21e4:94::/64")),
+            decoded_label.to_cidr_string()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ipnet")]
+    fn validate_to_ip_net() {
+        let decoded = match ClientRoutingLabel::default().decode(b"abfku6xaaaaaaaamhmnjxo5hdzrje") {
+            Ok(decoded) => decoded,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!("85.83.215.0/24".parse(), Ok(decoded.to_ip_net()));
+
+        assert_eq!("0.0.0.0/0".parse(), Ok(decoded_label_with_mask(0).to_ip_net()));
+    }
+
+    #[test]
+    fn validate_bytes_round_trip() {
+        for encoded_label in [
+            &b"abfku6xaaaaaaaamhmnjxo5hdzrje"[..],
+            b"abydhs4fyq6iaaaykudpmaxncecqs",
+            b"abqcaqdaqcqmaaaaaaaaaaaaaaaaa",
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        ] {
+            let decoded = match ClientRoutingLabel::default().decode(encoded_label) {
+                Ok(decoded) => decoded,
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+
+            let round_trip = DecodedClientRoutingLabel::from_bytes(&decoded.to_bytes());
+            assert_eq!(decoded.client_sdk_version, round_trip.client_sdk_version);
+            assert_eq!(decoded.is_ipv6, round_trip.is_ipv6);
+            assert_eq!(decoded.client_subnet, round_trip.client_subnet);
+            assert_eq!(decoded.subnet_mask, round_trip.subnet_mask);
+            assert_eq!(decoded.cgid, round_trip.cgid);
+        }
+    }
+
+    #[test]
+    fn validate_ipv4_reconstruction() {
+        let mut decoded_label = decoded_label_with_mask(24);
+        decoded_label.client_subnet = [85, 83, 215, 0, 0, 0, 0, 0];
+
+        assert_eq!(
+            Some(String::from("85.83.215.0/24")),
+            decoded_label.to_cidr_string()
+        );
+
+        assert_eq!(None, decoded_label_with_mask(0).to_cidr_string());
+    }
+}
@@ -0,0 +1,612 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use crate::client_routing_label::{ClientRoutingLabel, DecodedClientRoutingLabel};
+use crate::encode_decode::MAX_DNS_LABEL_SIZE;
+use crate::errors::DecodeError;
+use crate::hash::CgidRegistry;
+
+/// Struct for decoding many domains with a single reusable
+/// [`ClientRoutingLabel`].
+///
+/// Consist of 1 property: `label`, the [`ClientRoutingLabel`] that holds the
+/// field layout and is reused for every decode. Each decode behaves the same
+/// as [`decode_request_data`](crate::decode_request_data), the first DNS label
+/// of the domain is lowercased into a stack buffer and decoded, so repeated
+/// decodes don't allocate.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decoder::LabelDecoder;
+///
+/// let mut decoder = LabelDecoder::default();
+///
+/// let decoded_label = decoder.decode("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com").unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+///
+/// assert!(decoder.decode("example.com").is_err());
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LabelDecoder {
+    pub label: ClientRoutingLabel,
+}
+
+impl LabelDecoder {
+    /// Returns a [`LabelDecoder`] that decodes with `label`'s layout.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    /// use amazon_cloudfront_client_routing_lib::decoder::LabelDecoder;
+    ///
+    /// let mut decoder = LabelDecoder::new(ClientRoutingLabel::versionless(1));
+    ///
+    /// let decoded_label = decoder.decode("acaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+    /// assert_eq!(24, decoded_label.subnet_mask);
+    /// ```
+    pub fn new(label: ClientRoutingLabel) -> Self {
+        Self { label }
+    }
+
+    /// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
+    /// [`DecodeError`] for the first DNS label of `domain`.
+    pub fn decode(&mut self, domain: &str) -> Result<DecodedClientRoutingLabel, DecodeError> {
+        self.decode_bytes(domain.as_bytes())
+    }
+
+    /// Same as [`decode`](LabelDecoder::decode) for a `domain` that isn't
+    /// necessarily valid UTF-8.
+    pub fn decode_bytes(
+        &mut self,
+        domain: &[u8],
+    ) -> Result<DecodedClientRoutingLabel, DecodeError> {
+        self.decode_label(first_label(domain))
+    }
+
+    /// Same as [`decode`](LabelDecoder::decode) but also resolves the decoded
+    /// `cgid` against `registry`.
+    ///
+    /// The resolved cgid is [`None`] if `registry` has no cgid hashing to the
+    /// decoded `cgid`.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::decoder::LabelDecoder;
+    /// use amazon_cloudfront_client_routing_lib::hash::CgidRegistry;
+    ///
+    /// let mut registry = CgidRegistry::new();
+    /// registry.register("mv-456");
+    /// let mut decoder = LabelDecoder::default();
+    ///
+    /// let (decoded_label, cgid) = decoder
+    ///     .decode_resolved("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", &registry)
+    ///     .unwrap();
+    /// assert_eq!(24, decoded_label.subnet_mask);
+    /// assert_eq!(Some("mv-456"), cgid);
+    /// ```
+    pub fn decode_resolved<'a>(
+        &mut self,
+        domain: &str,
+        registry: &'a CgidRegistry,
+    ) -> Result<(DecodedClientRoutingLabel, Option<&'a str>), DecodeError> {
+        let decoded_label = self.decode(domain)?;
+
+        Ok((decoded_label, registry.resolve(decoded_label.cgid)))
+    }
+
+    /// Same as [`decode`](LabelDecoder::decode) but only accepts labels whose
+    /// decoded `cgid` is in `allowed`.
+    ///
+    /// Returns [`DecodeError::UnknownCgid`] if the label decodes but its
+    /// `cgid` isn't in `allowed`. `allowed` holds cgids as hashed by
+    /// [`hash_cgid`](crate::hash::hash_cgid), the same as the decoded `cgid`.
+    ///
+    /// # Examples:
+    /// ```
+    /// use std::collections::HashSet;
+    /// use amazon_cloudfront_client_routing_lib::decoder::LabelDecoder;
+    /// use amazon_cloudfront_client_routing_lib::hash::hash_cgid;
+    ///
+    /// let allowed = HashSet::from([hash_cgid("mv-456")]);
+    /// let mut decoder = LabelDecoder::default();
+    ///
+    /// let decoded_label = decoder
+    ///     .decode_allowlisted("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", &allowed)
+    ///     .unwrap();
+    /// assert_eq!(24, decoded_label.subnet_mask);
+    ///
+    /// // empty cgid
+    /// let decoded_label = decoder.decode_allowlisted("abacaqdaaaaaaaamaaaaaaaaaaaaa.example.com", &allowed);
+    /// assert_eq!("Passed a label with unknown cgid 0", decoded_label.unwrap_err().to_string());
+    /// ```
+    pub fn decode_allowlisted(
+        &mut self,
+        domain: &str,
+        allowed: &HashSet<u64>,
+    ) -> Result<DecodedClientRoutingLabel, DecodeError> {
+        let decoded_label = self.decode(domain)?;
+        if !allowed.contains(&decoded_label.cgid) {
+            return Err(DecodeError::UnknownCgid {
+                cgid: decoded_label.cgid,
+            });
+        }
+
+        Ok(decoded_label)
+    }
+
+    /// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
+    /// [`DecodeError`] for `client_routing_label`, which should already be
+    /// split from the rest of the domain.
+    ///
+    /// Returns [`DecodeError::Empty`] if `client_routing_label` is empty,
+    /// [`DecodeError::LabelTooLong`] if it's longer than a DNS label, and
+    /// [`DecodeError::Length`] if it's the wrong length for `label`'s layout.
+    /// The length is checked before the label is copied or lowercased.
+    ///
+    /// With the `tracing` feature each call runs in a `decode` trace span
+    /// recording the decoded `kind`, and a failed decode emits a warn event
+    /// with the [`DecodeError`].
+    pub fn decode_label(
+        &mut self,
+        client_routing_label: &[u8],
+    ) -> Result<DecodedClientRoutingLabel, DecodeError> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!("decode", kind = tracing::field::Empty).entered();
+
+        let decoded_label = self.decode_label_inner(client_routing_label);
+
+        #[cfg(feature = "tracing")]
+        crate::trace::record_decode(&span, decoded_label.as_ref());
+
+        decoded_label
+    }
+
+    /// Decodes `client_routing_label` for [`decode_label`](LabelDecoder::decode_label).
+    fn decode_label_inner(
+        &mut self,
+        client_routing_label: &[u8],
+    ) -> Result<DecodedClientRoutingLabel, DecodeError> {
+        check_label_len(client_routing_label)?;
+
+        // checked again by the decode, but failing here skips copying and
+        // lowercasing labels that can't be the right length
+        let total_num_bits = self.label.get_total_num_bits();
+        self.label
+            .encoding_system
+            .is_valid_client_routing_label(total_num_bits, client_routing_label)?;
+
+        let mut buffer = [0_u8; MAX_DNS_LABEL_SIZE as usize];
+        let buffer = &mut buffer[..client_routing_label.len()];
+        buffer.copy_from_slice(client_routing_label);
+        buffer.make_ascii_lowercase();
+
+        self.label.decode(buffer)
+    }
+}
+
+/// Returns `client_routing_label` lowercased the same way it is before
+/// decoding.
+///
+/// Borrows `client_routing_label` when it has no uppercase ASCII, the common
+/// case, and only allocates when there's something to lowercase.
+///
+/// Decoding doesn't need this and never allocates, since a [`LabelDecoder`]
+/// lowercases into a stack buffer. This is for callers that need the
+/// lowercased label itself, e.g. as a cache key.
+///
+/// # Examples:
+/// ```
+/// use std::borrow::Cow;
+/// use amazon_cloudfront_client_routing_lib::decoder::normalize_label;
+///
+/// assert!(matches!(normalize_label(b"abacaqdaaaaaaaamnjg3oubcyvrgm"), Cow::Borrowed(_)));
+/// assert_eq!(&b"abacaqdaaaaaaaamnjg3oubcyvrgm"[..], &*normalize_label(b"ABACAQDAAAAAAAAMNJG3OUBCYVRGM"));
+/// ```
+pub fn normalize_label(client_routing_label: &[u8]) -> Cow<'_, [u8]> {
+    if client_routing_label.iter().any(u8::is_ascii_uppercase) {
+        Cow::Owned(client_routing_label.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(client_routing_label)
+    }
+}
+
+/// Struct wrapping a [`LabelDecoder`] with a small least recently used cache
+/// of decoded labels.
+///
+/// Consist of 6 properties, all private: `decoder`, `capacity`, `entries`,
+/// `num_decodes`, `hits`, and `misses`. `entries` is keyed by the lowercased
+/// first DNS label and holds at most `capacity` successfully decoded labels,
+/// each with the `num_decodes` count of its last use. A cache hit
+/// returns a copy of the cached [`DecodedClientRoutingLabel`] without running
+/// the Base32 decode. Errors aren't cached. Finding the entry to evict scans
+/// the whole cache, so this is intended for small capacities.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decoder::LruLabelDecoder;
+///
+/// let mut decoder = LruLabelDecoder::new(16);
+///
+/// let first = decoder.decode("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com").unwrap();
+/// let second = decoder.decode("ABACAQDAAAAAAAAMNJG3OUBCYVRGM.vod1.example.com").unwrap();
+///
+/// assert_eq!(first.cgid, second.cgid);
+/// assert_eq!(1, decoder.hits());
+/// assert_eq!(1, decoder.misses());
+/// ```
+#[derive(Clone, Debug)]
+pub struct LruLabelDecoder {
+    decoder: LabelDecoder,
+    capacity: usize,
+    entries: HashMap<Vec<u8>, (DecodedClientRoutingLabel, u64)>,
+    num_decodes: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl LruLabelDecoder {
+    /// Returns a [`LruLabelDecoder`] caching up to `capacity` labels. A
+    /// `capacity` of 0 disables caching.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_decoder(LabelDecoder::default(), capacity)
+    }
+
+    /// Returns a [`LruLabelDecoder`] wrapping `decoder` and caching up to
+    /// `capacity` labels.
+    pub fn with_decoder(decoder: LabelDecoder, capacity: usize) -> Self {
+        Self {
+            decoder,
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            num_decodes: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
+    /// [`DecodeError`] for the first DNS label of `domain`, serving repeated
+    /// labels from the cache.
+    pub fn decode(&mut self, domain: &str) -> Result<DecodedClientRoutingLabel, DecodeError> {
+        self.num_decodes += 1;
+
+        let client_routing_label = first_label(domain.as_bytes());
+        let mut key = [0_u8; MAX_DNS_LABEL_SIZE as usize];
+        let key = if client_routing_label.len() <= key.len() {
+            let key = &mut key[..client_routing_label.len()];
+            key.copy_from_slice(client_routing_label);
+            key.make_ascii_lowercase();
+            &*key
+        } else {
+            client_routing_label
+        };
+
+        if let Some((decoded_label, last_used)) = self.entries.get_mut(key) {
+            *last_used = self.num_decodes;
+            self.hits += 1;
+            return Ok(*decoded_label);
+        }
+
+        self.misses += 1;
+        let decoded_label = self.decoder.decode_label(key)?;
+
+        if self.capacity > 0 {
+            if self.entries.len() >= self.capacity {
+                self.evict_least_recently_used();
+            }
+            self.entries
+                .insert(key.to_vec(), (decoded_label, self.num_decodes));
+        }
+
+        Ok(decoded_label)
+    }
+
+    /// Returns how many decodes were served from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns how many decodes had to run the Base32 decode.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Returns how many labels are currently cached.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes every cached label and resets the hit and miss counters.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.num_decodes = 0;
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    /// Removes the cached label that was used the longest time ago.
+    fn evict_least_recently_used(&mut self) {
+        let least_recently_used = self
+            .entries
+            .iter()
+            .min_by_key(|(_, (_, last_used))| *last_used)
+            .map(|(key, _)| key.clone());
+
+        if let Some(key) = least_recently_used {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+/// Returns [`DecodeError::Empty`] if `client_routing_label` is empty or
+/// [`DecodeError::LabelTooLong`] if it's longer than a DNS label, the checks
+/// every decode path makes before looking at the label's layout.
+pub(crate) fn check_label_len(client_routing_label: &[u8]) -> Result<(), DecodeError> {
+    if client_routing_label.is_empty() {
+        return Err(DecodeError::Empty);
+    }
+
+    if client_routing_label.len() > MAX_DNS_LABEL_SIZE as usize {
+        return Err(DecodeError::LabelTooLong {
+            len: client_routing_label.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns the first DNS label of `domain`.
+///
+/// Every decode path finds the client routing label with this, directly or
+/// through [`normalize_first_label`](crate::normalize_first_label), which
+/// returns [`None`] instead of an empty label.
+pub(crate) fn first_label(domain: &[u8]) -> &[u8] {
+    first_label_sep(domain, '.')
+}
+
+/// Returns the part of `domain` before the first `sep`, or all of `domain` if
+/// it has no `sep`.
+///
+/// `sep` is matched as its UTF-8 bytes. No other char's UTF-8 bytes contain
+/// them, so for a valid UTF-8 `domain` this splits where
+/// [`str::split`] would.
+pub(crate) fn first_label_sep(domain: &[u8], sep: char) -> &[u8] {
+    let mut sep_bytes = [0_u8; 4];
+    let sep = sep.encode_utf8(&mut sep_bytes).as_bytes();
+
+    match domain.windows(sep.len()).position(|window| window == sep) {
+        Some(sep_index) => &domain[..sep_index],
+        None => domain,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_label, LabelDecoder, LruLabelDecoder};
+    use crate::client_routing_label::ClientRoutingLabel;
+    use crate::encode_decode::InvalidCharPolicy;
+    use crate::errors::DecodeError;
+    use crate::hash::{hash_cgid, CgidRegistry};
+    use std::borrow::Cow;
+    use std::collections::HashSet;
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    #[tracing_test::traced_test]
+    fn validate_decode_error_emits_warn_event() {
+        let mut decoder = LabelDecoder::default();
+
+        match decoder.decode("abfku6xaaaaaaaamotptyubibrji6.example.com") {
+            Ok(_decoded_label) => (),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert!(!logs_contain("failed to decode client routing label"));
+
+        match decoder.decode("abfku6.example.com") {
+            Ok(_decoded_label) => panic!("Didn't return an error when it should have"),
+            Err(_e) => (),
+        };
+        assert!(logs_contain("WARN"));
+        assert!(logs_contain("failed to decode client routing label"));
+        assert!(logs_contain("Passed 6 - expected 29 characters"));
+        assert!(logs_contain("decode{kind=\"invalid\"}"));
+    }
+
+    #[test]
+    fn validate_label_decoder_reuse() {
+        let mut decoder = LabelDecoder::default();
+
+        let decoded_label = match decoder.decode("abfku6xaaaaaaaamotptyubibrji6.example.com") {
+            Ok(label) => label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(24, decoded_label.subnet_mask);
+        assert_eq!(16843032286346126622, decoded_label.cgid);
+
+        assert!(decoder.decode("abydhs4fyq6iaaaykudpmaxnce").is_err());
+
+        let decoded_label = match decoder.decode("abydhs4fyq6iaaaykudpmaxncecqs") {
+            Ok(label) => label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(48, decoded_label.subnet_mask);
+        assert_eq!(12253709671023643154, decoded_label.cgid);
+    }
+
+    #[test]
+    fn validate_decode_resolved() {
+        let registry: CgidRegistry = ["mv-456", "SM89P"].into_iter().collect();
+        let mut decoder = LabelDecoder::default();
+
+        let (decoded_label, cgid) = match decoder
+            .decode_resolved("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", &registry)
+        {
+            Ok(resolved) => resolved,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(15319960192071419084, decoded_label.cgid);
+        assert_eq!(Some("mv-456"), cgid);
+
+        let (decoded_label, cgid) = match decoder
+            .decode_resolved("abfku6xaaaaaaaamotptyubibrji6.example.com", &registry)
+        {
+            Ok(resolved) => resolved,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(16843032286346126622, decoded_label.cgid);
+        assert_eq!(None, cgid);
+
+        assert!(decoder.decode_resolved("example.com", &registry).is_err());
+    }
+
+    #[test]
+    fn validate_lru_second_decode_served_from_cache() {
+        let mut decoder = LruLabelDecoder::new(4);
+
+        let first = match decoder.decode("abfku6xaaaaaaaamotptyubibrji6.example.com") {
+            Ok(label) => label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(0, decoder.hits());
+        assert_eq!(1, decoder.misses());
+
+        let second = match decoder.decode("abfku6xaaaaaaaamotptyubibrji6.vod1.example.com") {
+            Ok(label) => label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(1, decoder.hits());
+        assert_eq!(1, decoder.misses());
+
+        assert_eq!(first.client_sdk_version, second.client_sdk_version);
+        assert_eq!(first.is_ipv6, second.is_ipv6);
+        assert_eq!(first.client_subnet, second.client_subnet);
+        assert_eq!(first.subnet_mask, second.subnet_mask);
+        assert_eq!(first.cgid, second.cgid);
+    }
+
+    #[test]
+    fn validate_lru_evicts_least_recently_used() {
+        let mut decoder = LruLabelDecoder::new(2);
+
+        assert!(decoder.decode("abfku6xaaaaaaaamotptyubibrji6").is_ok());
+        assert!(decoder.decode("abydhs4fyq6iaaaykudpmaxncecqs").is_ok());
+        // refresh the first label so the second is evicted next
+        assert!(decoder.decode("abfku6xaaaaaaaamotptyubibrji6").is_ok());
+        assert!(decoder.decode("abc4aydaaaaaaaamaaaaaaaaaaaaa").is_ok());
+        assert_eq!(2, decoder.len());
+        assert_eq!(1, decoder.hits());
+
+        assert!(decoder.decode("abfku6xaaaaaaaamotptyubibrji6").is_ok());
+        assert_eq!(2, decoder.hits());
+
+        assert!(decoder.decode("abydhs4fyq6iaaaykudpmaxncecqs").is_ok());
+        assert_eq!(2, decoder.hits());
+        assert_eq!(4, decoder.misses());
+    }
+
+    #[test]
+    fn validate_lru_does_not_cache_errors() {
+        let mut decoder = LruLabelDecoder::new(2);
+
+        assert!(decoder.decode("example.com").is_err());
+        assert!(decoder.decode("example.com").is_err());
+        assert!(decoder.is_empty());
+        assert_eq!(0, decoder.hits());
+        assert_eq!(2, decoder.misses());
+    }
+
+    #[test]
+    fn validate_decode_allowlisted() {
+        let allowed = HashSet::from([hash_cgid("B086VX9VMK")]);
+        let mut decoder = LabelDecoder::default();
+
+        let decoded_label = match decoder.decode_allowlisted("abfku6xaaaaaaaamotptyubibrji6.example.com", &allowed) {
+            Ok(decoded_label) => decoded_label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(16843032286346126622, decoded_label.cgid);
+
+        match decoder.decode_allowlisted("abydhs4fyq6iaaaykudpmaxncecqs.example.com", &allowed) {
+            Ok(_decoded_label) => panic!("Didn't return an error when it should have"),
+            Err(DecodeError::UnknownCgid { cgid }) => assert_eq!(hash_cgid("Q9OP1I23"), cgid),
+            Err(e) => panic!("Returned the wrong error: {}", e),
+        };
+
+        // decode errors are returned before the allowlist is checked
+        match decoder.decode_allowlisted("example.com", &allowed) {
+            Ok(_decoded_label) => panic!("Didn't return an error when it should have"),
+            Err(e) => assert_eq!("Passed 7 - expected 29 characters", e.to_string()),
+        };
+    }
+
+    #[test]
+    fn validate_decode_label_checks_length_first() {
+        let mut label = ClientRoutingLabel::default();
+        label.encoding_system.invalid_char_policy = InvalidCharPolicy::Error;
+        let mut decoder = LabelDecoder::new(label);
+
+        // the length is checked before any char is looked at, so uppercase and
+        // invalid chars still fail on length
+        for client_routing_label in [
+            &b"abfku"[..],
+            b"ABFKU",
+            b"ab!k\xff",
+            b"abfku6xaaaaaaaamotptyubibrji6a",
+            b"ABFKU6XAAAAAAAAMOTPTYUBIBRJ!6A",
+        ] {
+            match decoder.decode_label(client_routing_label) {
+                Ok(_decoded_label) => panic!("Didn't return an error when it should have"),
+                Err(DecodeError::Length(e)) => {
+                    assert_eq!(client_routing_label.len(), e.num_chars);
+                    assert_eq!(29, e.expected_num_chars);
+                }
+                Err(e) => panic!("Returned the wrong error: {}", e),
+            };
+        }
+
+        match decoder.decode_label(b"ABFKU6XAAAAAAAAMOTPTYUBIBRJ!6") {
+            Ok(_decoded_label) => panic!("Didn't return an error when it should have"),
+            Err(DecodeError::InvalidChar(e)) => assert_eq!(27, e.index),
+            Err(e) => panic!("Returned the wrong error: {}", e),
+        };
+        assert!(decoder.decode_label(b"ABFKU6XAAAAAAAAMOTPTYUBIBRJI6").is_ok());
+
+        let mut decoder = LabelDecoder::new(ClientRoutingLabel::versionless(1));
+        match decoder.decode_label(b"abfku6xaaaaaaaamotptyubibrji6") {
+            Ok(_decoded_label) => panic!("Didn't return an error when it should have"),
+            Err(e) => assert_eq!("Passed 29 - expected 27 characters", e.to_string()),
+        };
+    }
+
+    #[test]
+    fn validate_normalize_label_borrows_lowercase() {
+        match normalize_label(b"abfku6xaaaaaaaamotptyubibrji6") {
+            Cow::Borrowed(label) => assert_eq!(b"abfku6xaaaaaaaamotptyubibrji6", label),
+            Cow::Owned(_label) => panic!("Allocated for an already lowercase label"),
+        };
+
+        // non-letters have no case
+        assert!(matches!(normalize_label(b"234567-"), Cow::Borrowed(_)));
+        assert!(matches!(normalize_label(b""), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn validate_normalize_label_lowercases_uppercase() {
+        match normalize_label(b"ABFKU6XAAAAAAAAMOTPTYUBIBRJI6") {
+            Cow::Borrowed(_label) => panic!("Didn't lowercase an uppercase label"),
+            Cow::Owned(label) => assert_eq!(b"abfku6xaaaaaaaamotptyubibrji6".to_vec(), label),
+        };
+
+        let mut decoder = LabelDecoder::default();
+        let decoded_label = decoder.decode_bytes(&normalize_label(b"ABFKU6XAAAAAAAAMOTPTYUBIBRJI6")).unwrap();
+        assert_eq!(16843032286346126622, decoded_label.cgid);
+    }
+}
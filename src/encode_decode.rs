@@ -1,38 +1,359 @@
 // Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{client_routing_label::EncodableData, errors::DecodeLengthError, bitwise::get_mask};
+use std::borrow::Cow;
+
+use crate::{
+    bitwise::get_mask,
+    client_routing_label::EncodableData,
+    errors::{DecodeError, DecodeLengthError, InvalidCharError},
+};
 
 const BASE32_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+const BASE32_ALPHABET_UPPERCASE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
 const BASE32_NUM_BITS_IN_CHAR: u8 = 5;
-const MAX_DNS_LABEL_SIZE: u8 = 63;
+/// Maximum number of chars in a single DNS label, per RFC 1035.
+///
+/// Encoding doesn't enforce this, so callers that need DNS compliant labels
+/// can check the encoded length against it.
+pub const MAX_DNS_LABEL_SIZE: u8 = 63;
+
+// Fails the build if BASE32_ALPHABET is edited into something that can't be
+// used for encoding.
+const _: () = assert!(is_valid_base32_alphabet(BASE32_ALPHABET));
+const _: () = assert!(is_valid_base32_alphabet(BASE32_ALPHABET_UPPERCASE));
+
+/// Marks a byte in [`BASE32_VALUES`] that isn't in [`BASE32_ALPHABET`].
+const INVALID_CHAR_VALUE: u8 = 0xff;
+/// Reverse lookup of [`BASE32_ALPHABET`] in either case, indexed by byte.
+const BASE32_VALUES: [u8; 256] = base32_values(BASE32_ALPHABET);
+
+/// Calls `emit` with each 5 bit value of `encodable_data`, in order.
+///
+/// The last value is padded with 0 bits if the bits don't divide evenly into
+/// chars. Consumes `num_bits` like [`Base32::encode`].
+fn encode_values(encodable_data: &mut [EncodableData], mut emit: impl FnMut(u8)) {
+    let value_mask: u64 = get_mask(BASE32_NUM_BITS_IN_CHAR);
+    let mut value_to_encode: u8 = 0;
+    let mut num_bits_left_over: u8 = 0;
+    for data in encodable_data.iter_mut() {
+        while data.has_bits_for_char(BASE32_NUM_BITS_IN_CHAR - num_bits_left_over) {
+            value_to_encode += data.get_next_bits_to_encode(BASE32_NUM_BITS_IN_CHAR - num_bits_left_over);
+            emit(value_to_encode);
+
+            num_bits_left_over = 0;
+            value_to_encode = 0;
+        }
+
+        value_to_encode |= ((data.value << (BASE32_NUM_BITS_IN_CHAR - (data.num_bits + num_bits_left_over))) & value_mask) as u8;
+        num_bits_left_over += data.num_bits;
+    }
+
+    if num_bits_left_over > 0 {
+        emit(value_to_encode);
+    }
+}
+
+/// Number of chars encoded by [`encode_to_simd`]: a full client routing label.
+#[cfg(feature = "simd")]
+const SIMD_ENCODED_LEN: usize = crate::client_routing_label::ENCODED_LABEL_LEN;
+
+/// Appends the Base32 encoding of `encodable_data`, which must encode to
+/// [`SIMD_ENCODED_LEN`] chars, to `encoded_data`.
+///
+/// `base` is the first letter of the alphabet, `b'a'` or `b'A'`. Values are
+/// extracted the same way as the scalar path and then mapped to chars 16 at a
+/// time: 0-25 become `base + value` and 26-31 become `'2' + value - 26`.
+#[cfg(feature = "simd")]
+fn encode_to_simd(base: u8, encodable_data: &mut [EncodableData], encoded_data: &mut String) {
+    use wide::{i8x16, CmpGt};
+
+    let mut values = [0i8; 32];
+    let mut num_values = 0;
+    encode_values(encodable_data, |value| {
+        values[num_values] = value as i8;
+        num_values += 1;
+    });
+
+    let last_letter = i8x16::splat(25);
+    let letter_offset = i8x16::splat(base as i8);
+    let digit_offset = i8x16::splat(b'2' as i8 - 26);
+    let mut chars = [0u8; 32];
+    for (values, chars) in values.chunks_exact(16).zip(chars.chunks_exact_mut(16)) {
+        let values = i8x16::new(values.try_into().unwrap());
+        let offsets = values.cmp_gt(last_letter).blend(digit_offset, letter_offset);
+        for (c, v) in chars.iter_mut().zip((values + offsets).to_array()) {
+            *c = v as u8;
+        }
+    }
+
+    // every byte is from the Base32 alphabet, so it's ascii
+    encoded_data.push_str(std::str::from_utf8(&chars[..SIMD_ENCODED_LEN]).unwrap());
+}
+
+/// Returns the char at `index` of `encoded_label`.
+///
+/// Without the `unchecked` feature this is a normal bounds checked index.
+#[cfg(not(feature = "unchecked"))]
+#[inline]
+fn label_char(encoded_label: &[u8], index: usize) -> u8 {
+    encoded_label[index]
+}
+
+/// Returns the char at `index` of `encoded_label` without a bounds check.
+///
+/// Only called from [`Base32::decode`] after it has checked that every field
+/// fits in `encoded_label`, so `index` is always in bounds.
+#[cfg(feature = "unchecked")]
+#[inline]
+fn label_char(encoded_label: &[u8], index: usize) -> u8 {
+    debug_assert!(index < encoded_label.len());
+    // SAFETY: Base32::decode asserts the fields being decoded need no more
+    // than encoded_label.len() chars before indexing.
+    unsafe { *encoded_label.get_unchecked(index) }
+}
+
+/// Returns how many chars a label with `total_num_bits` bits is encoded to.
+///
+/// The same length [`Base32::encoded_len`] returns for a layout with that many
+/// bits. Useful for checking a proposed layout fits in
+/// [`MAX_DNS_LABEL_SIZE`].
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::encode_decode::label_len_for_bits;
+///
+/// assert_eq!(29, label_len_for_bits(145));
+/// assert_eq!(1, label_len_for_bits(1));
+/// ```
+pub fn label_len_for_bits(total_num_bits: u8) -> usize {
+    num_chars_for_bits(total_num_bits as usize)
+}
+
+/// Returns how many Base32 chars are needed to hold `total_num_bits` bits.
+pub(crate) fn num_chars_for_bits(total_num_bits: usize) -> usize {
+    (total_num_bits + BASE32_NUM_BITS_IN_CHAR as usize - 1) / BASE32_NUM_BITS_IN_CHAR as usize
+}
+
+/// Returns whether `c` is in the Base32 alphabet, ignoring case.
+pub(crate) fn is_base32_char(c: u8) -> bool {
+    BASE32_ALPHABET.contains(&c.to_ascii_lowercase())
+}
+
+/// Returns the value of Base32 char `c`, ignoring case, or [`None`] if `c`
+/// isn't in the alphabet.
+pub(crate) fn base32_value(c: u8) -> Option<u8> {
+    match BASE32_VALUES[c as usize] {
+        INVALID_CHAR_VALUE => None,
+        value => Some(value),
+    }
+}
+
+/// Returns the lowercase Base32 char for the low 5 bits of `value`.
+pub(crate) fn base32_char(value: u8) -> u8 {
+    BASE32_ALPHABET[(value & 0x1f) as usize]
+}
+
+/// Returns a table mapping each byte of `alphabet` to its index, with
+/// [`INVALID_CHAR_VALUE`] for every other byte.
+///
+/// The other case of each letter in `alphabet` maps to the same index, unless
+/// `alphabet` already uses it, so decoding is case-insensitive wherever that's
+/// unambiguous.
+const fn base32_values(alphabet: &[u8]) -> [u8; 256] {
+    let mut values = [INVALID_CHAR_VALUE; 256];
+
+    let mut i = 0;
+    while i < alphabet.len() {
+        values[alphabet[i] as usize] = i as u8;
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < alphabet.len() {
+        let other_case = if alphabet[i].is_ascii_lowercase() {
+            alphabet[i].to_ascii_uppercase()
+        } else {
+            alphabet[i].to_ascii_lowercase()
+        };
+        if values[other_case as usize] == INVALID_CHAR_VALUE {
+            values[other_case as usize] = i as u8;
+        }
+        i += 1;
+    }
+
+    values
+}
+
+/// Returns whether `alphabet` is exactly 32 unique ASCII bytes.
+const fn is_valid_base32_alphabet(alphabet: &[u8]) -> bool {
+    if alphabet.len() != 1 << BASE32_NUM_BITS_IN_CHAR {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < alphabet.len() {
+        if !alphabet[i].is_ascii() {
+            return false;
+        }
+
+        let mut j = i + 1;
+        while j < alphabet.len() {
+            if alphabet[i] == alphabet[j] {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+/// Enum describing how [`Base32`] decodes chars outside of its alphabet.
+///
+/// `TreatAsA` decodes them as 'a' (a value of 0) so a label is never rejected
+/// for its contents, which is the default. `Error` rejects the label with a
+/// [`DecodeError::InvalidChar`]. `TreatAsValue` decodes them as the given
+/// value; only its 5 least significant bits are used.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InvalidCharPolicy {
+    #[default]
+    TreatAsA,
+    Error,
+    TreatAsValue(u8),
+}
+
+/// Enum describing which alphabet [`Base32`] encodes and decodes with.
+///
+/// `Lowercase` is the default. `Uppercase` is the canonical RFC 4648 alphabet,
+/// for interop with producers and consumers that expect it. Both decode
+/// either case regardless of this setting. `Custom` is any other mapping of
+/// values to chars, see [`Base32::with_alphabet`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Base32Alphabet {
+    #[default]
+    Lowercase,
+    Uppercase,
+    Custom(CustomBase32Alphabet),
+}
+
+impl Base32Alphabet {
+    /// Returns the 32 chars of this alphabet, in value order.
+    fn chars(&self) -> &[u8] {
+        match self {
+            Base32Alphabet::Lowercase => BASE32_ALPHABET,
+            Base32Alphabet::Uppercase => BASE32_ALPHABET_UPPERCASE,
+            Base32Alphabet::Custom(custom) => &custom.chars,
+        }
+    }
+
+    /// Returns the reverse lookup of this alphabet, indexed by byte.
+    ///
+    /// A custom alphabet's lookup is built on each call rather than stored, to
+    /// keep [`Base32`] small and `Copy`.
+    fn values(&self) -> Cow<'static, [u8; 256]> {
+        match self {
+            Base32Alphabet::Lowercase | Base32Alphabet::Uppercase => Cow::Borrowed(&BASE32_VALUES),
+            Base32Alphabet::Custom(custom) => Cow::Owned(base32_values(&custom.chars)),
+        }
+    }
+}
+
+/// Struct holding a custom Base32 alphabet.
+///
+/// Built by [`Base32::with_alphabet`], which validates the alphabet.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CustomBase32Alphabet {
+    chars: [u8; 32],
+}
 
 /// Struct for encoding, decoding, and validating [`EncodableData`] with Base32.
 /// 
-/// Uses lowercase version of the RFC 4648 Base32 alphabet. Methods treat each
-/// set of 5 bits in [`EncodableData`] as a separate character. Invalid characters
-/// will be treated as 'a' instead of marking the entire label as invalid for
-/// efficiency. Contains no properties, for usage see individual functions or
+/// Uses the RFC 4648 Base32 alphabet. Methods treat each set of 5 bits in
+/// [`EncodableData`] as a separate character. Consist of 2 properties:
+/// `invalid_char_policy`, which decides how chars outside the alphabet are
+/// decoded, and `alphabet`, which decides whether encoding is lowercase,
+/// uppercase, or uses a custom alphabet. The default treats invalid chars as
+/// 'a' instead of marking the entire label as invalid for efficiency, and
+/// encodes lowercase. Decoding accepts either case. For usage see individual functions or
 /// [`ClientRoutingLabel`](crate::client_routing_label::ClientRoutingLabel).
-#[derive(Copy, Clone, Debug)]
-pub struct Base32 {}
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::encode_decode::{Base32, Base32Alphabet, InvalidCharPolicy};
+///
+/// let encoding_system = Base32::default();
+/// assert_eq!(InvalidCharPolicy::TreatAsA, encoding_system.invalid_char_policy);
+/// assert_eq!(Base32Alphabet::Lowercase, encoding_system.alphabet);
+///
+/// let strict_encoding_system = Base32 {
+///     invalid_char_policy: InvalidCharPolicy::Error,
+///     ..Base32::default()
+/// };
+///
+/// let uppercase_encoding_system = Base32 {
+///     alphabet: Base32Alphabet::Uppercase,
+///     ..Base32::default()
+/// };
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Base32 {
+    pub invalid_char_policy: InvalidCharPolicy,
+    pub alphabet: Base32Alphabet,
+}
 
 impl Base32 {
-    /// Returns a lowercase Base32 string encoded from `encodable_data`.
+    /// Returns a [`Base32`] encoding with `alphabet`, where the char at each
+    /// index encodes that 5 bit value.
+    ///
+    /// `alphabet` can be any permutation of 32 unique ASCII bytes, e.g. a
+    /// scrambled version of the default alphabet used while migrating formats.
+    /// Decoding inverts the same mapping, and is case-insensitive for letters
+    /// whose other case isn't also in `alphabet`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alphabet` has a repeated or non ASCII byte.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::encode_decode::Base32;
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::EncodableData;
+    ///
+    /// let encoding_system = Base32::with_alphabet(*b"765432zyxwvutsrqponmlkjihgfedcba");
+    /// let encodable_data = &mut [EncodableData { value: 0, num_bits: 5 }, EncodableData { value: 31, num_bits: 5 }];
+    ///
+    /// assert_eq!("7a", encoding_system.encode(encodable_data));
+    /// ```
+    pub fn with_alphabet(alphabet: [u8; 32]) -> Self {
+        assert!(
+            is_valid_base32_alphabet(&alphabet),
+            "alphabet must be 32 unique ASCII bytes"
+        );
+
+        Self {
+            alphabet: Base32Alphabet::Custom(CustomBase32Alphabet { chars: alphabet }),
+            ..Self::default()
+        }
+    }
+
+    /// Returns a Base32 string encoded from `encodable_data`.
     /// 
     /// Iterates over `encodable_data`, encoding bits from `value` until 
     /// not enough bits remain to make a full char. Remaining bits are
     /// then used in the subsequent iteration. After iterating over
     /// everything, if there are not enough bits to make a char 0 will
-    /// be used to pad the left over bits. Encoding uses a lowercase
-    /// version of the RFC 4648 Base32 alphabet.
+    /// be used to pad the left over bits. Encoding uses the chars of
+    /// `alphabet`: the RFC 4648 Base32 alphabet, lowercase by default, or a
+    /// custom alphabet from [`with_alphabet`](Base32::with_alphabet).
     /// 
     /// # Examples:
     /// ```
     /// use amazon_cloudfront_client_routing_lib::encode_decode::Base32;
     /// use amazon_cloudfront_client_routing_lib::client_routing_label::EncodableData;
     /// 
-    /// let encoding_system = Base32 {};
+    /// let encoding_system = Base32::default();
     /// let encodable_data = &mut [
     ///     EncodableData { // 0b01010 => "k"
     ///         value: 10,
@@ -51,28 +372,75 @@ impl Base32 {
     /// assert_eq!("kd3a", encoding_system.encode(encodable_data));
     /// ```
     pub fn encode(&self, encodable_data: &mut [EncodableData]) -> String {
-        let value_mask: u64 = get_mask(BASE32_NUM_BITS_IN_CHAR);
-        let mut encoded_data: Vec<char> = Vec::with_capacity(MAX_DNS_LABEL_SIZE as usize);
-        let mut value_to_encode: u8 = 0;
-        let mut num_bits_left_over: u8 = 0;
-        for data in encodable_data.iter_mut() {
-            while data.has_bits_for_char(BASE32_NUM_BITS_IN_CHAR - num_bits_left_over) {
-                value_to_encode += data.get_next_bits_to_encode(BASE32_NUM_BITS_IN_CHAR - num_bits_left_over);
-                encoded_data.push(BASE32_ALPHABET[value_to_encode as usize] as char);
+        let mut encoded_data = String::with_capacity(self.encoded_len(encodable_data));
+        self.encode_to(encodable_data, &mut encoded_data);
 
-                num_bits_left_over = 0;
-                value_to_encode = 0;
-            }
+        encoded_data
+    }
 
-            value_to_encode |= ((data.value << (BASE32_NUM_BITS_IN_CHAR - (data.num_bits + num_bits_left_over))) & value_mask) as u8;
-            num_bits_left_over += data.num_bits;
-        }
+    /// Returns how many chars [`encode`](Base32::encode) produces for
+    /// `encodable_data`.
+    ///
+    /// Sums `num_bits` over `encodable_data` and rounds up to a whole number of
+    /// chars. Since encoding consumes `num_bits`, call this before encoding.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::encode_decode::Base32;
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::EncodableData;
+    ///
+    /// let encoding_system = Base32::default();
+    /// let encodable_data = &mut [
+    ///     EncodableData { value: 10, num_bits: 5 },
+    ///     EncodableData { value: 123, num_bits: 10 },
+    ///     EncodableData { value: 0, num_bits: 1 },
+    /// ];
+    ///
+    /// assert_eq!(4, encoding_system.encoded_len(encodable_data));
+    /// assert_eq!("kd3a", encoding_system.encode(encodable_data));
+    /// ```
+    pub fn encoded_len(&self, encodable_data: &[EncodableData]) -> usize {
+        num_chars_for_bits(
+            encodable_data
+                .iter()
+                .map(|data| data.num_bits as usize)
+                .sum(),
+        )
+    }
 
-        if num_bits_left_over > 0 {
-            encoded_data.push(BASE32_ALPHABET[value_to_encode as usize] as char);
+    /// Appends the Base32 encoding of `encodable_data` to `encoded_data`.
+    ///
+    /// Same as [`encode`](Base32::encode) but writes into an existing [`String`]
+    /// so callers can reuse its allocation. The chars follow `alphabet`, so
+    /// they're lowercase by default, uppercase for
+    /// [`Base32Alphabet::Uppercase`], or from a custom alphabet.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::encode_decode::Base32;
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::EncodableData;
+    ///
+    /// let encoding_system = Base32::default();
+    /// let mut encoded_data = String::from("label: ");
+    ///
+    /// encoding_system.encode_to(&mut [EncodableData { value: 10, num_bits: 5 }], &mut encoded_data);
+    /// assert_eq!("label: k", encoded_data);
+    /// ```
+    pub fn encode_to(&self, encodable_data: &mut [EncodableData], encoded_data: &mut String) {
+        #[cfg(feature = "simd")]
+        if self.encoded_len(encodable_data) == SIMD_ENCODED_LEN {
+            let base = match self.alphabet {
+                Base32Alphabet::Lowercase => Some(b'a'),
+                Base32Alphabet::Uppercase => Some(b'A'),
+                Base32Alphabet::Custom(_) => None,
+            };
+            if let Some(base) = base {
+                return encode_to_simd(base, encodable_data, encoded_data);
+            }
         }
 
-        encoded_data.iter().collect()
+        let alphabet = self.alphabet.chars();
+        encode_values(encodable_data, |value| encoded_data.push(alphabet[value as usize] as char));
     }
 
     /// Validates `client_routing_label` is the proper length to fit `total_num_bits`.
@@ -85,7 +453,7 @@ impl Base32 {
     /// ```
     /// use amazon_cloudfront_client_routing_lib::encode_decode::Base32;
     /// 
-    /// let encoding_system = Base32 {};
+    /// let encoding_system = Base32::default();
     /// 
     /// // valid
     /// match encoding_system.is_valid_client_routing_label(145, b"abaaaaaaaaaaaaaaaaaaaackvj5oa") {
@@ -104,13 +472,13 @@ impl Base32 {
         total_num_bits: u8,
         client_routing_label: &[u8],
     ) -> Result<(), DecodeLengthError> {
-        if client_routing_label.len() as u8
-            != (total_num_bits + BASE32_NUM_BITS_IN_CHAR - 1) / BASE32_NUM_BITS_IN_CHAR
-        {
+        // usize so neither a label over 255 chars nor a total_num_bits near
+        // u8::MAX wraps around
+        let expected_num_chars = label_len_for_bits(total_num_bits);
+        if client_routing_label.len() != expected_num_chars {
             let e = DecodeLengthError {
                 num_chars: client_routing_label.len(),
-                expected_num_chars: ((total_num_bits + BASE32_NUM_BITS_IN_CHAR - 1)
-                    / BASE32_NUM_BITS_IN_CHAR) as usize,
+                expected_num_chars,
             };
             return Err(e);
         }
@@ -121,16 +489,17 @@ impl Base32 {
     /// Sets `encodable_data` based on passed `encoded_label`.
     /// 
     /// Validates `encoded_label` is valid based on `total_num_bits`. If not valid,
-    /// returns a [`Result`] containing [`DecodeLengthError`]. If valid, iterates
+    /// returns a [`Result`] containing [`DecodeError::Length`]. If valid, iterates
     /// over `encodable_data` and sets each value based on the label value. Invalid
-    /// characters in a label are treated as if they had a value of 0.
+    /// characters in a label are handled according to `invalid_char_policy`,
+    /// returning [`DecodeError::InvalidChar`] under [`InvalidCharPolicy::Error`].
     /// 
     /// # Examples:
     /// ```
     /// use amazon_cloudfront_client_routing_lib::encode_decode::Base32;
     /// use amazon_cloudfront_client_routing_lib::client_routing_label::EncodableData;
     /// 
-    /// let encoding_system = Base32 {};
+    /// let encoding_system = Base32::default();
     /// 
     /// // valid
     /// let encodable_data = &mut [
@@ -168,52 +537,252 @@ impl Base32 {
         encodable_data: &mut [EncodableData],
         encoded_label: &[u8],
         total_num_bits: u8,
-    ) -> Result<(), DecodeLengthError> {
+    ) -> Result<(), DecodeError> {
         match self.is_valid_client_routing_label(total_num_bits, encoded_label) {
             Ok(()) => (),
-            Err(e) => return Err(e),
+            Err(e) => return Err(e.into()),
         };
 
+        // With the `unchecked` feature encoded_label is indexed without bounds
+        // checks, which is only sound if every field fits in the label. The
+        // label length was validated against total_num_bits, but
+        // encodable_data is passed separately, so check it here once.
+        #[cfg(feature = "unchecked")]
+        assert!(
+            encodable_data
+                .iter()
+                .map(|data| data.num_bits as usize)
+                .sum::<usize>()
+                <= encoded_label.len() * BASE32_NUM_BITS_IN_CHAR as usize,
+            "encodable_data has more bits than encoded_label"
+        );
+
+        // Chars are looked up as they're reached rather than collected up
+        // front, so decoding doesn't allocate. char_value holds the bits of
+        // the current char that haven't been consumed yet.
+        let values = self.alphabet.values();
+        let mut char_value: u8 = 0;
+        let mut num_bits_in_char: u8 = BASE32_NUM_BITS_IN_CHAR;
+        let mut label_index: usize = 0;
+        for data in encodable_data.iter_mut() {
+            let original_num_bits: u8 = data.num_bits;
+            data.value = 0;
+            
+            // add_bits counts num_bits down, so it's restored below even when
+            // an invalid char stops decoding part way through the field
+            let decoded_field = (|| -> Result<(), InvalidCharError> {
+                while data.has_bits_for_char(num_bits_in_char) {
+                    if num_bits_in_char == BASE32_NUM_BITS_IN_CHAR {
+                        char_value = self.get_char_value(&values, label_index, label_char(encoded_label, label_index))?;
+                    }
+                    data.add_bits(num_bits_in_char, char_value);
+                    label_index += 1;
+                    num_bits_in_char = BASE32_NUM_BITS_IN_CHAR;
+                }
+                
+                if data.num_bits > 0 {
+                    if num_bits_in_char == BASE32_NUM_BITS_IN_CHAR {
+                        char_value = self.get_char_value(&values, label_index, label_char(encoded_label, label_index))?;
+                    }
+                    num_bits_in_char -= data.num_bits;
+                    data.add_bits(data.num_bits, char_value >> num_bits_in_char);
+                    char_value &= get_mask(num_bits_in_char) as u8;
+                }
+
+                Ok(())
+            })();
+
+            data.num_bits = original_num_bits;
+            decoded_field?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets `encodable_data` from as much of `encoded_label` as is available and
+    /// returns a [`DecodeProgress`] describing how far decoding got.
+    ///
+    /// Unlike [`decode`](Base32::decode), the length of `encoded_label` is not
+    /// validated up front. Decoding stops when the label runs out of chars, which
+    /// makes it possible to see which field a truncated label stopped in. Under
+    /// [`InvalidCharPolicy::Error`] decoding also stops at the first invalid
+    /// char. Fields
+    /// past `fields_decoded` are left with whatever partial value was read.
+    /// Extra chars at the end of a label that is too long are ignored.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::encode_decode::Base32;
+    /// use amazon_cloudfront_client_routing_lib::client_routing_label::EncodableData;
+    ///
+    /// let encoding_system = Base32::default();
+    /// let encodable_data = &mut [
+    ///     EncodableData {
+    ///         value: 0,
+    ///         num_bits: 5
+    ///     },
+    ///     EncodableData {
+    ///         value: 0,
+    ///         num_bits: 10
+    ///     },
+    ///     EncodableData {
+    ///         value: 0,
+    ///         num_bits: 1
+    ///     },
+    /// ];
+    ///
+    /// // "kd3a" is the full label, "kd" stops part way through the second field
+    /// let progress = encoding_system.decode_with_progress(encodable_data, b"kd");
+    /// assert_eq!(1, progress.fields_decoded);
+    /// assert_eq!(10, progress.bits_decoded);
+    /// assert_eq!(10, encodable_data[0].value);
+    /// ```
+    pub fn decode_with_progress(
+        &self,
+        encodable_data: &mut [EncodableData],
+        encoded_label: &[u8],
+    ) -> DecodeProgress {
+        let values = self.alphabet.values();
         let mut label_values: Vec<u8> = encoded_label
             .iter()
-            .map(|a| BASE32_ALPHABET.iter().position(|b| a == b).unwrap_or(0) as u8)
+            .enumerate()
+            .map_while(|(index, a)| self.get_char_value(&values, index, *a).ok())
             .collect();
 
+        let mut progress = DecodeProgress {
+            fields_decoded: 0,
+            bits_decoded: 0,
+        };
         let mut num_bits_in_char: u8 = BASE32_NUM_BITS_IN_CHAR;
         let mut label_index: usize = 0;
         for data in encodable_data.iter_mut() {
             let original_num_bits: u8 = data.num_bits;
             data.value = 0;
-            
-            while data.has_bits_for_char(num_bits_in_char) {
-                data.add_bits(num_bits_in_char, label_values[label_index]);
-                label_index += 1;
-                num_bits_in_char = BASE32_NUM_BITS_IN_CHAR;
-            }
-            
-            if data.num_bits > 0 {
-                num_bits_in_char -= data.num_bits;
-                data.add_bits(data.num_bits, label_values[label_index] >> num_bits_in_char);
+
+            while data.num_bits > 0 {
+                if label_index >= label_values.len() {
+                    data.num_bits = original_num_bits;
+                    return progress;
+                }
+
+                let num_bits_to_add = num_bits_in_char.min(data.num_bits);
+                num_bits_in_char -= num_bits_to_add;
+                data.add_bits(num_bits_to_add, label_values[label_index] >> num_bits_in_char);
                 label_values[label_index] &= get_mask(num_bits_in_char) as u8;
+                progress.bits_decoded += num_bits_to_add as usize;
+
+                if num_bits_in_char == 0 {
+                    label_index += 1;
+                    num_bits_in_char = BASE32_NUM_BITS_IN_CHAR;
+                }
             }
 
             data.num_bits = original_num_bits;
+            progress.fields_decoded += 1;
         }
 
-        Ok(())
+        progress
+    }
+
+    /// Returns the 5 bit value of `char_to_decode` looked up in `values`,
+    /// applying `invalid_char_policy` if it isn't in the alphabet.
+    fn get_char_value(
+        &self,
+        values: &[u8; 256],
+        index: usize,
+        char_to_decode: u8,
+    ) -> Result<u8, InvalidCharError> {
+        match values[char_to_decode as usize] {
+            INVALID_CHAR_VALUE => match self.invalid_char_policy {
+                InvalidCharPolicy::TreatAsA => Ok(0),
+                InvalidCharPolicy::Error => Err(InvalidCharError {
+                    invalid_char: char_to_decode,
+                    index,
+                }),
+                InvalidCharPolicy::TreatAsValue(value) => {
+                    Ok(value & get_mask(BASE32_NUM_BITS_IN_CHAR) as u8)
+                }
+            },
+            value => Ok(value),
+        }
     }
 }
 
+/// Struct describing how far [`Base32::decode_with_progress`] got through a
+/// label.
+///
+/// Consist of 2 properties: `fields_decoded` and `bits_decoded`.
+/// `fields_decoded` is how many [`EncodableData`] items were completely filled,
+/// so it is also the index of the field decoding stopped in when the label is
+/// too short. `bits_decoded` is the bit offset reached in the label.
+#[derive(Copy, Clone, Debug)]
+pub struct DecodeProgress {
+    pub fields_decoded: usize,
+    pub bits_decoded: usize,
+}
+
 #[cfg(test)]
+// the original decode tests format errors with to_string
+#[allow(clippy::to_string_in_format_args)]
 mod tests {
     use super::*;
-    use crate::client_routing_label::EncodableData;
+    use crate::client_routing_label::{ClientRoutingLabel, EncodableData};
     
+    #[cfg(feature = "simd")]
+    #[test]
+    fn validate_simd_encode_matches_scalar() {
+        // same chars as the built in alphabets, but custom alphabets always take
+        // the scalar path
+        let scalar_lowercase = Base32::with_alphabet(*b"abcdefghijklmnopqrstuvwxyz234567");
+        let scalar_uppercase = Base32::with_alphabet(*b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567");
+        let uppercase = Base32 { alphabet: Base32Alphabet::Uppercase, ..Base32::default() };
+
+        // xorshift, seeded so failures reproduce
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..1000 {
+            let mut label = ClientRoutingLabel::default();
+            for data in label.encodable_data.iter_mut() {
+                data.value = next() & get_mask(data.num_bits);
+            }
+
+            for (simd, scalar) in [(Base32::default(), scalar_lowercase), (uppercase, scalar_uppercase)] {
+                let expected = scalar.encode(&mut label.encodable_data.clone());
+                let actual = simd.encode(&mut label.encodable_data.clone());
+                assert_eq!(SIMD_ENCODED_LEN, actual.len());
+                assert_eq!(expected, actual);
+            }
+        }
+    }
+
+    #[test]
+    fn validate_base32_values_reverse_alphabet() {
+        for (index, c) in BASE32_ALPHABET.iter().enumerate() {
+            assert_eq!(index as u8, BASE32_VALUES[*c as usize]);
+        }
+        for (index, c) in BASE32_ALPHABET_UPPERCASE.iter().enumerate() {
+            assert_eq!(index as u8, BASE32_VALUES[*c as usize]);
+        }
+        // a-z and A-Z plus 2-7 shared by both cases
+        assert_eq!(
+            26 + 26 + 6,
+            BASE32_VALUES.iter().filter(|value| **value != INVALID_CHAR_VALUE).count()
+        );
+        assert_eq!(INVALID_CHAR_VALUE, BASE32_VALUES[b'1' as usize]);
+        assert_eq!(INVALID_CHAR_VALUE, BASE32_VALUES[b'-' as usize]);
+    }
+
     // All the data has values with bit size <= num_bits.
     // Total bits is divisible by 5 and can be encoded with no padding.
     #[test]
     fn validate_encode_value_proper_size_no_padding_needed() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data = &mut [
             EncodableData {
                 value: 0,
@@ -248,7 +817,7 @@ mod tests {
     // Total bits is divisible by 5 and can be encoded with no padding.
     #[test]
     fn validate_encode_value_proper_size_padding_needed() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data = &mut [
             EncodableData {
                 value: 36,
@@ -271,7 +840,7 @@ mod tests {
     // Total bits is not divisible by 5 and will need padding to encode.
     #[test]
     fn validate_encode_value_too_large_no_padding_needed() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data: &mut [EncodableData] = &mut [
             EncodableData {
                 value: 5346,
@@ -302,7 +871,7 @@ mod tests {
     // Total bits is not divisible by 5 and will need padding to encode.
     #[test]
     fn validate_encode_value_too_large_padding_needed() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data: &mut [EncodableData] = &mut [
             EncodableData {
                 value: 2423,
@@ -331,7 +900,7 @@ mod tests {
 
     #[test]
     fn validate_encode_empty_data() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data: &mut [EncodableData] = &mut [];
 
         assert_eq!("", encoding_system.encode(encodable_data));
@@ -339,7 +908,7 @@ mod tests {
 
     #[test]
     fn validate_encode_not_enough_data_for_char() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data: &mut [EncodableData] = &mut [
             EncodableData {
                 value: 1,
@@ -356,7 +925,7 @@ mod tests {
 
     #[test]
     fn validate_decode_label_with_no_padding() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data = &mut [
             EncodableData {
                 value: 0,
@@ -399,7 +968,7 @@ mod tests {
 
     #[test]
     fn validate_decode_label_with_padding() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data = &mut [
             EncodableData {
                 value: 0,
@@ -427,7 +996,7 @@ mod tests {
 
     #[test]
     fn validate_decode_data_already_has_value() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data = &mut [
             EncodableData {
                 value: 2423,
@@ -465,7 +1034,7 @@ mod tests {
 
     #[test]
     fn validate_decode_empty_label() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data = &mut [];
         
         match encoding_system.decode(encodable_data, b"", 0) {
@@ -476,7 +1045,7 @@ mod tests {
 
     #[test]
     fn validate_decode_label_too_large() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data = &mut [
             EncodableData {
                 value: 0,
@@ -500,7 +1069,7 @@ mod tests {
 
     #[test]
     fn validate_decode_label_too_small() {
-        let encoding_system = Base32 {};
+        let encoding_system = Base32::default();
         let encodable_data = &mut [
             EncodableData {
                 value: 0,
@@ -521,4 +1090,422 @@ mod tests {
             Err(e) => assert_eq!("Passed 3 - expected 10 characters", e.to_string())
         };
     }
+
+    #[test]
+    fn validate_decode_with_progress_label_one_char_short() {
+        let encoding_system = Base32::default();
+        let mut client_routing_label = ClientRoutingLabel::default();
+
+        let progress = encoding_system.decode_with_progress(
+            &mut client_routing_label.encodable_data,
+            b"abfku6xaaaaaaaamotptyubibrji",
+        );
+
+        assert_eq!(4, progress.fields_decoded);
+        assert_eq!(140, progress.bits_decoded);
+        assert_eq!(1, client_routing_label.encodable_data[0].value);
+        assert_eq!(6148494311290830848, client_routing_label.encodable_data[2].value);
+        assert_eq!(24, client_routing_label.encodable_data[3].value);
+        assert_eq!(64, client_routing_label.encodable_data[4].num_bits);
+    }
+
+    #[test]
+    fn validate_decode_with_progress_full_label() {
+        let encoding_system = Base32::default();
+        let encodable_data = &mut [
+            EncodableData {
+                value: 0,
+                num_bits: 12,
+            },
+            EncodableData {
+                value: 0,
+                num_bits: 22,
+            },
+            EncodableData {
+                value: 0,
+                num_bits: 14,
+            },
+        ];
+
+        let progress = encoding_system.decode_with_progress(encodable_data, b"ajhd6hgjh4");
+
+        assert_eq!(3, progress.fields_decoded);
+        assert_eq!(48, progress.bits_decoded);
+        assert_eq!(36, encodable_data[0].value);
+        assert_eq!(3734643, encodable_data[1].value);
+        assert_eq!(2367, encodable_data[2].value);
+    }
+
+    #[test]
+    fn validate_base32_alphabet() {
+        assert!(is_valid_base32_alphabet(BASE32_ALPHABET));
+
+        let unique_chars: std::collections::HashSet<&u8> = BASE32_ALPHABET.iter().collect();
+        assert_eq!(32, BASE32_ALPHABET.len());
+        assert_eq!(32, unique_chars.len());
+        assert!(BASE32_ALPHABET.is_ascii());
+    }
+
+    #[test]
+    fn validate_invalid_base32_alphabets() {
+        assert!(!is_valid_base32_alphabet(b"abcdefghijklmnopqrstuvwxyz23456"));
+        assert!(!is_valid_base32_alphabet(b"abcdefghijklmnopqrstuvwxyz234567a"));
+        assert!(!is_valid_base32_alphabet(b"aacdefghijklmnopqrstuvwxyz234567"));
+        assert!(!is_valid_base32_alphabet("abcdefghijklmnopqrstuvwxyz2345\u{e9}".as_bytes()));
+    }
+
+    // Exhaustively checks the ceiling division in is_valid_client_routing_label
+    // against what encode produces and decode consumes.
+    #[test]
+    fn validate_label_length_matches_decodability() {
+        let encoding_system = Base32::default();
+
+        for total_num_bits in 1..=160_u8 {
+            let mut encodable_data: Vec<EncodableData> = Vec::new();
+            let mut num_bits_left = total_num_bits;
+            while num_bits_left > 0 {
+                let num_bits = num_bits_left.min(64);
+                encodable_data.push(EncodableData {
+                    value: u64::MAX,
+                    num_bits,
+                });
+                num_bits_left -= num_bits;
+            }
+
+            let encoded_label = encoding_system.encode(&mut encodable_data.clone());
+            let expected_len = (total_num_bits as usize + 4) / 5;
+            assert_eq!(expected_len, encoded_label.len());
+
+            let valid_label = "7".repeat(expected_len);
+            let short_label = "7".repeat(expected_len - 1);
+            let long_label = "7".repeat(expected_len + 1);
+
+            assert!(encoding_system
+                .is_valid_client_routing_label(total_num_bits, valid_label.as_bytes())
+                .is_ok());
+            assert!(encoding_system
+                .is_valid_client_routing_label(total_num_bits, short_label.as_bytes())
+                .is_err());
+            assert!(encoding_system
+                .is_valid_client_routing_label(total_num_bits, long_label.as_bytes())
+                .is_err());
+
+            match encoding_system.decode(&mut encodable_data, encoded_label.as_bytes(), total_num_bits) {
+                Ok(()) => (),
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+            for data in encodable_data.iter() {
+                assert_eq!(get_mask(data.num_bits), data.value);
+            }
+
+            match encoding_system.decode(&mut encodable_data, valid_label.as_bytes(), total_num_bits) {
+                Ok(()) => (),
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+        }
+    }
+
+    fn three_field_encodable_data() -> [EncodableData; 3] {
+        [
+            EncodableData {
+                value: 0,
+                num_bits: 5,
+            },
+            EncodableData {
+                value: 0,
+                num_bits: 10,
+            },
+            EncodableData {
+                value: 0,
+                num_bits: 1,
+            },
+        ]
+    }
+
+    #[test]
+    fn validate_decode_invalid_char_treat_as_a() {
+        let encoding_system = Base32 {
+            invalid_char_policy: InvalidCharPolicy::TreatAsA,
+            ..Base32::default()
+        };
+        let encodable_data = &mut three_field_encodable_data();
+
+        match encoding_system.decode(encodable_data, b"k03a", 16) {
+            Ok(()) => {
+                assert_eq!(10, encodable_data[0].value);
+                assert_eq!(27, encodable_data[1].value);
+                assert_eq!(0, encodable_data[2].value);
+            },
+            Err(e) => panic!("Threw error when shouldn't have: {}", e)
+        };
+    }
+
+    #[test]
+    fn validate_decode_invalid_char_error() {
+        let encoding_system = Base32 {
+            invalid_char_policy: InvalidCharPolicy::Error,
+            ..Base32::default()
+        };
+        let encodable_data = &mut three_field_encodable_data();
+
+        match encoding_system.decode(encodable_data, b"k03a", 16) {
+            Ok(()) => panic!("Didn't throw error when should have"),
+            Err(DecodeError::InvalidChar(e)) => {
+                assert_eq!(b'0', e.invalid_char);
+                assert_eq!(1, e.index);
+                assert_eq!("Invalid character '0' at index 1", e.to_string());
+            },
+            Err(e) => panic!("Threw the wrong error: {}", e)
+        };
+
+        // stopping part way through a field leaves its num_bits as it was
+        match encoding_system.decode(encodable_data, b"kd0a", 16) {
+            Ok(()) => panic!("Didn't throw error when should have"),
+            Err(DecodeError::InvalidChar(e)) => assert_eq!(2, e.index),
+            Err(e) => panic!("Threw the wrong error: {}", e)
+        };
+        assert_eq!([5, 10, 1], encodable_data.map(|data| data.num_bits));
+
+        match encoding_system.decode(encodable_data, b"kd3a", 16) {
+            Ok(()) => assert_eq!(123, encodable_data[1].value),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e)
+        };
+
+        let progress = encoding_system.decode_with_progress(encodable_data, b"k03a");
+        assert_eq!(1, progress.fields_decoded);
+        assert_eq!(5, progress.bits_decoded);
+    }
+
+    #[test]
+    fn validate_decode_invalid_char_treat_as_value() {
+        let encoding_system = Base32 {
+            invalid_char_policy: InvalidCharPolicy::TreatAsValue(3),
+            ..Base32::default()
+        };
+        let encodable_data = &mut three_field_encodable_data();
+
+        match encoding_system.decode(encodable_data, b"k03a", 16) {
+            Ok(()) => {
+                assert_eq!(10, encodable_data[0].value);
+                assert_eq!(123, encodable_data[1].value);
+                assert_eq!(0, encodable_data[2].value);
+            },
+            Err(e) => panic!("Threw error when shouldn't have: {}", e)
+        };
+
+        // only the 5 least significant bits are used
+        let encoding_system = Base32 {
+            invalid_char_policy: InvalidCharPolicy::TreatAsValue(35),
+            ..Base32::default()
+        };
+
+        match encoding_system.decode(encodable_data, b"k03a", 16) {
+            Ok(()) => assert_eq!(123, encodable_data[1].value),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e)
+        };
+    }
+
+    #[test]
+    fn validate_uppercase_round_trip() {
+        let encoding_system = Base32 {
+            alphabet: Base32Alphabet::Uppercase,
+            ..Base32::default()
+        };
+        let encodable_data = &mut three_field_encodable_data();
+        encodable_data[0].value = 10;
+        encodable_data[1].value = 123;
+
+        let encoded = encoding_system.encode(&mut encodable_data.clone());
+        assert_eq!("KD3A", encoded);
+
+        let lowercase_encoded = Base32::default().encode(encodable_data);
+        assert_eq!("kd3a", lowercase_encoded);
+
+        for (decoding_system, label) in [
+            (encoding_system, "KD3A"),
+            (encoding_system, "kd3a"),
+            (Base32::default(), "KD3A"),
+            (Base32::default(), "Kd3A"),
+        ] {
+            let encodable_data = &mut three_field_encodable_data();
+            match decoding_system.decode(encodable_data, label.as_bytes(), 16) {
+                Ok(()) => {
+                    assert_eq!(10, encodable_data[0].value);
+                    assert_eq!(123, encodable_data[1].value);
+                    assert_eq!(0, encodable_data[2].value);
+                },
+                Err(e) => panic!("Threw error when shouldn't have: {}", e)
+            };
+        }
+    }
+
+    #[test]
+    fn validate_scrambled_alphabet_round_trip() {
+        let alphabet = *b"q7zm2xk4wa6jrdv5ctgbn3yhfpu8lsei";
+        let encoding_system = Base32::with_alphabet(alphabet);
+
+        // every value encodes to its char in the scrambled alphabet
+        for (value, c) in alphabet.iter().enumerate() {
+            let encodable_data = &mut [EncodableData { value: value as u64, num_bits: 5 }];
+            assert_eq!((*c as char).to_string(), encoding_system.encode(encodable_data));
+        }
+
+        let mut label = ClientRoutingLabel {
+            encoding_system,
+            ..ClientRoutingLabel::default()
+        };
+        label.set_field_values([1, 0, 6148494311290830848, 24, 8517775255794402596]);
+        let encoded = label.encode();
+        assert_ne!("abfku6xaaaaaaaamhmnjxo5hdzrje", encoded);
+        assert!(encoded.bytes().all(|c| alphabet.contains(&c)));
+
+        let mut label = ClientRoutingLabel {
+            encoding_system,
+            ..ClientRoutingLabel::default()
+        };
+        match label.decode(encoded.as_bytes()) {
+            Ok(decoded) => {
+                assert_eq!(1, decoded.client_sdk_version);
+                assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded.client_subnet);
+                assert_eq!(24, decoded.subnet_mask);
+                assert_eq!(8517775255794402596, decoded.cgid);
+            },
+            Err(e) => panic!("Threw error when shouldn't have: {}", e)
+        };
+
+        // the default alphabet reads the same label differently
+        match ClientRoutingLabel::default().decode(encoded.as_bytes()) {
+            Ok(decoded) => assert_ne!(8517775255794402596, decoded.cgid),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e)
+        };
+    }
+
+    #[test]
+    fn validate_scrambled_alphabet_rejects_chars_outside_it() {
+        let encoding_system = Base32 {
+            invalid_char_policy: InvalidCharPolicy::Error,
+            ..Base32::with_alphabet(*b"q7zm2xk4wa6jrdv5ctgbn3yhfpu8lsei")
+        };
+        let encodable_data = &mut three_field_encodable_data();
+
+        // 'o' is in the default alphabet but not this one
+        match encoding_system.decode(encodable_data, b"qqqo", 16) {
+            Ok(()) => panic!("Didn't throw error when should have"),
+            Err(DecodeError::InvalidChar(e)) => assert_eq!(3, e.index),
+            Err(e) => panic!("Threw the wrong error: {}", e)
+        };
+
+        // uppercase decodes the same as lowercase
+        match encoding_system.decode(encodable_data, b"Q7ZM", 16) {
+            Ok(()) => {
+                assert_eq!(0, encodable_data[0].value);
+                assert_eq!(0b00001_00010, encodable_data[1].value);
+            },
+            Err(e) => panic!("Threw error when shouldn't have: {}", e)
+        };
+    }
+
+    #[test]
+    #[should_panic]
+    fn validate_with_alphabet_repeated_char_panics() {
+        Base32::with_alphabet(*b"aacdefghijklmnopqrstuvwxyz234567");
+    }
+
+    #[test]
+    fn validate_client_routing_label_default_treats_invalid_char_as_a() {
+        let client_routing_label = ClientRoutingLabel::default();
+
+        assert_eq!(
+            InvalidCharPolicy::TreatAsA,
+            client_routing_label.encoding_system.invalid_char_policy
+        );
+    }
+
+    #[test]
+    fn validate_encode_longer_than_dns_label_presized() {
+        let encoding_system = Base32::default();
+        let encodable_data = &mut [EncodableData {
+            value: u64::MAX,
+            num_bits: 64,
+        }; 6];
+
+        // 384 bits round up to 77 chars, well past a DNS label
+        assert_eq!(77, encoding_system.encoded_len(encodable_data));
+
+        let encoded_data = encoding_system.encode(encodable_data);
+        assert_eq!(77, encoded_data.len());
+        assert!(encoded_data.len() > MAX_DNS_LABEL_SIZE as usize);
+        assert_eq!(encoded_data.len(), encoded_data.capacity());
+    }
+
+    #[test]
+    fn validate_label_len_for_bits() {
+        assert_eq!(29, label_len_for_bits(145));
+        assert_eq!(50, label_len_for_bits(250));
+        assert_eq!(51, label_len_for_bits(255));
+        assert_eq!(0, label_len_for_bits(0));
+    }
+
+    #[test]
+    fn validate_label_length_does_not_wrap() {
+        let encoding_system = Base32::default();
+
+        // 285 chars would be 29 if truncated to a u8
+        let client_routing_label = [b'a'; 285];
+        match encoding_system.is_valid_client_routing_label(145, &client_routing_label) {
+            Ok(()) => panic!("Didn't throw error when should have."),
+            Err(e) => assert_eq!("Passed 285 - expected 29 characters", e.to_string()),
+        };
+
+        match encoding_system.is_valid_client_routing_label(255, &[b'a'; 51]) {
+            Ok(()) => (),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+    }
+
+    #[test]
+    fn validate_decode_matches_decode_with_progress() {
+        let encoding_system = Base32::default();
+        let mut value: u64 = 0x9e37_79b9_7f4a_7c15;
+
+        for _ in 0..1000 {
+            value = value.rotate_left(13) ^ value.wrapping_mul(0x2545_f491_4f6c_dd1d);
+
+            let mut client_routing_label = ClientRoutingLabel::default();
+            client_routing_label.encodable_data[2].value = value;
+            client_routing_label.encodable_data[3].value = value >> 58;
+            client_routing_label.encodable_data[4].value = !value;
+            let encoded_label = client_routing_label.encode();
+
+            let decoded_data = &mut ClientRoutingLabel::default().encodable_data;
+            match encoding_system.decode(decoded_data, encoded_label.as_bytes(), 145) {
+                Ok(()) => (),
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+
+            let progress_data = &mut ClientRoutingLabel::default().encodable_data;
+            let progress =
+                encoding_system.decode_with_progress(progress_data, encoded_label.as_bytes());
+            assert_eq!(5, progress.fields_decoded);
+
+            for (decoded, expected) in decoded_data.iter().zip(progress_data.iter()) {
+                assert_eq!(expected.value, decoded.value);
+            }
+            assert_eq!(value, decoded_data[2].value);
+            assert_eq!(!value, decoded_data[4].value);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "unchecked")]
+    #[should_panic(expected = "encodable_data has more bits than encoded_label")]
+    fn validate_unchecked_decode_rejects_oversized_layout() {
+        let encoding_system = Base32::default();
+        let encodable_data = &mut [EncodableData {
+            value: 0,
+            num_bits: 64,
+        }];
+
+        let _ = encoding_system.decode(encodable_data, b"aa", 10);
+    }
 }
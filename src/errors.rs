@@ -2,6 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::fmt;
+use std::io;
+
+use crate::encode_decode::MAX_DNS_LABEL_SIZE;
 
 /// Error struct used when decoding a client routing label key of an improper
 /// length.
@@ -35,9 +38,178 @@ impl fmt::Display for DecodeLengthError {
     }
 }
 
+/// Error struct used when decoding a client routing label containing a char
+/// outside the Base32 alphabet with
+/// [`InvalidCharPolicy::Error`](crate::encode_decode::InvalidCharPolicy::Error).
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::errors::InvalidCharError;
+///
+/// let error = InvalidCharError {
+///     invalid_char: b'0',
+///     index: 3,
+/// };
+///
+/// assert_eq!("Invalid character '0' at index 3", error.to_string());
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct InvalidCharError {
+    pub invalid_char: u8,
+    pub index: usize,
+}
+
+impl std::error::Error for InvalidCharError {}
+
+impl fmt::Display for InvalidCharError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid character '{}' at index {}",
+            self.invalid_char.escape_ascii(), self.index,
+        )
+    }
+}
+
+/// Error struct used when adding bits to an
+/// [`EncodableData`](crate::client_routing_label::EncodableData) would overflow
+/// its value.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::errors::AddBitsOverflowError;
+///
+/// let error = AddBitsOverflowError {
+///     num_bits_to_add: 5,
+///     num_bits: 4,
+/// };
+///
+/// assert_eq!("Adding 5 bits overflows 4 remaining bits", error.to_string());
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct AddBitsOverflowError {
+    pub num_bits_to_add: u8,
+    pub num_bits: u8,
+}
+
+impl std::error::Error for AddBitsOverflowError {}
+
+impl fmt::Display for AddBitsOverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Adding {} bits overflows {} remaining bits",
+            self.num_bits_to_add, self.num_bits,
+        )
+    }
+}
+
+/// Error enum covering every way decoding a client routing label can fail.
+///
+/// `Length` wraps a [`DecodeLengthError`] and `InvalidChar` wraps an
+/// [`InvalidCharError`], exposing them through
+/// [`source`](std::error::Error::source) so wrapping error types keep the full
+/// chain. `Empty` is used when there is no label to decode at all.
+/// `LabelTooLong` is used when the label is longer than
+/// [`MAX_DNS_LABEL_SIZE`](crate::encode_decode::MAX_DNS_LABEL_SIZE), so it
+/// can't be a client routing label regardless of layout. `NonUtf8` is used
+/// when a domain that isn't a `&str`, e.g. an [`OsStr`](std::ffi::OsStr),
+/// isn't valid UTF-8. `UnknownCgid` is used when a label decodes but its
+/// `cgid` isn't one the caller accepts.
+///
+/// # Examples:
+/// ```
+/// use std::error::Error;
+/// use amazon_cloudfront_client_routing_lib::errors::{DecodeError, DecodeLengthError};
+///
+/// let error = DecodeError::from(DecodeLengthError {
+///     num_chars: 10,
+///     expected_num_chars: 29,
+/// });
+///
+/// assert_eq!("Passed 10 - expected 29 characters", error.to_string());
+/// assert!(error.source().is_some());
+///
+/// let error = DecodeError::Empty;
+/// assert_eq!("Passed an empty label", error.to_string());
+/// assert!(error.source().is_none());
+///
+/// let error = DecodeError::LabelTooLong { len: 100 };
+/// assert_eq!("Passed 100 - longer than the 63 character max DNS label", error.to_string());
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub enum DecodeError {
+    Length(DecodeLengthError),
+    Empty,
+    InvalidChar(InvalidCharError),
+    LabelTooLong { len: usize },
+    NonUtf8,
+    UnknownCgid { cgid: u64 },
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Length(e) => Some(e),
+            DecodeError::Empty => None,
+            DecodeError::InvalidChar(e) => Some(e),
+            DecodeError::LabelTooLong { .. } => None,
+            DecodeError::NonUtf8 => None,
+            DecodeError::UnknownCgid { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Length(e) => e.fmt(f),
+            DecodeError::Empty => write!(f, "Passed an empty label"),
+            DecodeError::InvalidChar(e) => e.fmt(f),
+            DecodeError::LabelTooLong { len } => write!(
+                f,
+                "Passed {} - longer than the {} character max DNS label",
+                len, MAX_DNS_LABEL_SIZE,
+            ),
+            DecodeError::NonUtf8 => write!(f, "Passed a domain that isn't valid UTF-8"),
+            DecodeError::UnknownCgid { cgid } => write!(f, "Passed a label with unknown cgid {}", cgid),
+        }
+    }
+}
+
+impl From<DecodeLengthError> for DecodeError {
+    fn from(e: DecodeLengthError) -> Self {
+        DecodeError::Length(e)
+    }
+}
+
+impl From<InvalidCharError> for DecodeError {
+    fn from(e: InvalidCharError) -> Self {
+        DecodeError::InvalidChar(e)
+    }
+}
+
+/// Converts to an [`io::ErrorKind::InvalidData`] error wrapping `e`, so `?`
+/// works in [`io::Result`] contexts.
+impl From<DecodeLengthError> for io::Error {
+    fn from(e: DecodeLengthError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// Converts to an [`io::ErrorKind::InvalidData`] error wrapping `e`, so `?`
+/// works in [`io::Result`] contexts.
+impl From<DecodeError> for io::Error {
+    fn from(e: DecodeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::DecodeLengthError;
+    use super::{DecodeError, DecodeLengthError};
+    use std::error::Error;
+    use std::io;
 
     #[test]
     fn validate_decode_length_error_text() {
@@ -48,4 +220,44 @@ mod tests {
 
         assert_eq!(error.to_string(), "Passed 10 - expected 29 characters");
     }
+
+    #[test]
+    fn validate_decode_error_source() {
+        let error = DecodeError::from(DecodeLengthError {
+            num_chars: 10,
+            expected_num_chars: 29,
+        });
+
+        assert!(error.source().is_some());
+        assert_eq!(
+            "Passed 10 - expected 29 characters",
+            error.source().unwrap().to_string()
+        );
+        assert!(DecodeError::Empty.source().is_none());
+    }
+
+    #[test]
+    fn validate_io_error_from_decode_errors() {
+        let length_error = DecodeLengthError {
+            num_chars: 10,
+            expected_num_chars: 29,
+        };
+
+        let error = io::Error::from(length_error);
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!("Passed 10 - expected 29 characters", error.to_string());
+
+        let error = io::Error::from(DecodeError::from(length_error));
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!("Passed 10 - expected 29 characters", error.to_string());
+
+        let error = io::Error::from(DecodeError::Empty);
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!("Passed an empty label", error.to_string());
+
+        fn decode_io(domain: &str) -> io::Result<u64> {
+            Ok(crate::decode_request_data(domain)?.cgid)
+        }
+        assert_eq!(io::ErrorKind::InvalidData, decode_io("example.com").unwrap_err().kind());
+    }
 }
@@ -0,0 +1,177 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! C entry points for building the crate as a C library.
+//!
+//! The crate builds as a plain Rust library by default. Build the shared
+//! library for C callers with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (cargo 1.64 or
+//! newer).
+//!
+//! Decoding reports a [`CfStatus`] code rather than a bare success flag so C
+//! callers can tell failures apart and produce useful diagnostics.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+use crate::client_routing_label::{DecodedClientRoutingLabel, ENCODED_LABEL_LEN};
+use crate::decode_request_data;
+use crate::errors::DecodeError;
+
+/// Status code returned by [`cf_decode_request_data`].
+///
+/// `CF_OK` is always 0 so C callers can keep treating non-zero as failure.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CfStatus {
+    /// Decoded successfully; the out label was written.
+    CF_OK = 0,
+    /// The label has the wrong number of chars; the counts were written.
+    CF_ERR_LENGTH = 1,
+    /// There was no label to decode.
+    CF_ERR_EMPTY = 2,
+    /// The domain contains a byte outside ASCII.
+    CF_ERR_NONASCII = 3,
+    /// The label contains a char outside the Base32 alphabet.
+    CF_ERR_INVALID_CHAR = 4,
+    /// `domain` or `out` was null.
+    CF_ERR_NULL = 5,
+    /// The label decoded but its cgid isn't accepted.
+    CF_ERR_UNKNOWN_CGID = 6,
+}
+
+impl From<&DecodeError> for CfStatus {
+    fn from(e: &DecodeError) -> Self {
+        match e {
+            DecodeError::Length(_) | DecodeError::LabelTooLong { .. } => CfStatus::CF_ERR_LENGTH,
+            DecodeError::Empty => CfStatus::CF_ERR_EMPTY,
+            DecodeError::InvalidChar(_) => CfStatus::CF_ERR_INVALID_CHAR,
+            DecodeError::NonUtf8 => CfStatus::CF_ERR_NONASCII,
+            DecodeError::UnknownCgid { .. } => CfStatus::CF_ERR_UNKNOWN_CGID,
+        }
+    }
+}
+
+/// Decodes the client routing label at the start of the nul-terminated
+/// `domain` into `out`, the same as
+/// [`decode_request_data`](crate::decode_request_data).
+///
+/// On `CF_ERR_LENGTH` the label's char count is written to `num_chars` and the
+/// count a label should have to `expected_num_chars`. Either may be null if the
+/// caller doesn't want it. Nothing is written to `out` unless `CF_OK` is
+/// returned.
+///
+/// # Safety
+///
+/// `domain` must be null or point to a nul-terminated string, and `out`,
+/// `num_chars` and `expected_num_chars` must each be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cf_decode_request_data(
+    domain: *const c_char,
+    out: *mut DecodedClientRoutingLabel,
+    num_chars: *mut usize,
+    expected_num_chars: *mut usize,
+) -> CfStatus {
+    if domain.is_null() || out.is_null() {
+        return CfStatus::CF_ERR_NULL;
+    }
+
+    let bytes = CStr::from_ptr(domain).to_bytes();
+    if !bytes.is_ascii() {
+        return CfStatus::CF_ERR_NONASCII;
+    }
+    // ascii is always valid utf8
+    let domain = std::str::from_utf8_unchecked(bytes);
+
+    match decode_request_data(domain) {
+        Ok(decoded) => {
+            *out = decoded;
+            CfStatus::CF_OK
+        }
+        Err(e) => {
+            let counts = match e {
+                DecodeError::Length(e) => Some((e.num_chars, e.expected_num_chars)),
+                DecodeError::LabelTooLong { len } => Some((len, ENCODED_LABEL_LEN)),
+                _ => None,
+            };
+            if let Some((actual, expected)) = counts {
+                if !num_chars.is_null() {
+                    *num_chars = actual;
+                }
+                if !expected_num_chars.is_null() {
+                    *expected_num_chars = expected;
+                }
+            }
+            CfStatus::from(&e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::ptr;
+
+    fn decode(
+        domain: &[u8],
+    ) -> (CfStatus, DecodedClientRoutingLabel, usize, usize) {
+        let domain = CString::new(domain).unwrap();
+        let mut out = DecodedClientRoutingLabel::default();
+        let mut num_chars = 0;
+        let mut expected_num_chars = 0;
+        let status = unsafe {
+            cf_decode_request_data(
+                domain.as_ptr(),
+                &mut out,
+                &mut num_chars,
+                &mut expected_num_chars,
+            )
+        };
+
+        (status, out, num_chars, expected_num_chars)
+    }
+
+    #[test]
+    fn validate_cf_decode_request_data() {
+        let (status, out, num_chars, expected_num_chars) =
+            decode(b"abacaqdaaaaaaaamnjg3oubcyvrgm.example.com");
+        assert_eq!(CfStatus::CF_OK, status);
+        assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], out.client_subnet);
+        assert_eq!(24, out.subnet_mask);
+        assert_eq!(0, num_chars);
+        assert_eq!(0, expected_num_chars);
+    }
+
+    #[test]
+    fn validate_cf_decode_request_data_too_short() {
+        let (status, out, num_chars, expected_num_chars) = decode(b"abacaqdaaaaaaaam.example.com");
+        assert_eq!(CfStatus::CF_ERR_LENGTH, status);
+        assert_eq!(16, num_chars);
+        assert_eq!(29, expected_num_chars);
+        assert_eq!(0, out.cgid);
+    }
+
+    #[test]
+    fn validate_cf_decode_request_data_errors() {
+        assert_eq!(CfStatus::CF_ERR_EMPTY, decode(b"").0);
+        assert_eq!(CfStatus::CF_ERR_NONASCII, decode("abacaqdaaaaaaaamnjg3oubcyvrgé.com".as_bytes()).0);
+
+        let mut out = DecodedClientRoutingLabel::default();
+        let status = unsafe {
+            cf_decode_request_data(ptr::null(), &mut out, ptr::null_mut(), ptr::null_mut())
+        };
+        assert_eq!(CfStatus::CF_ERR_NULL, status);
+    }
+
+    #[test]
+    fn validate_cf_decode_request_data_null_counts() {
+        let domain = CString::new("abacaq.example.com").unwrap();
+        let mut out = DecodedClientRoutingLabel::default();
+        let status = unsafe {
+            cf_decode_request_data(domain.as_ptr(), &mut out, ptr::null_mut(), ptr::null_mut())
+        };
+        assert_eq!(CfStatus::CF_ERR_LENGTH, status);
+    }
+}
@@ -1,9 +1,12 @@
 // Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::collections::HashMap;
 use std::hash::Hasher;
 use twox_hash::XxHash64;
 
+use crate::bitwise::get_mask;
+
 /// Utilizes xxHash to hash a `cgid` into a 64 bit number and returns that
 /// number.
 ///
@@ -33,9 +36,228 @@ pub fn hash_cgid(cgid: &str) -> u64 {
     hasher.finish()
 }
 
+/// Utilizes xxHash to hash a `cgid` mixed with `salt` into a 64 bit number and
+/// returns that number.
+///
+/// `salt` is hashed first and used as the seed for hashing `cgid`, so the
+/// result can't be precomputed for a list of cgids without knowing `salt`. An
+/// empty `salt` is the same as [`hash_cgid`], and an empty `cgid` still results
+/// in 0.
+///
+/// # Examples
+/// ```
+/// use amazon_cloudfront_client_routing_lib::hash::{hash_cgid, hash_cgid_salted};
+///
+/// assert_ne!(hash_cgid("mv-456"), hash_cgid_salted("mv-456", b"salt"));
+/// assert_eq!(hash_cgid("mv-456"), hash_cgid_salted("mv-456", b""));
+/// assert_eq!(0, hash_cgid_salted("", b"salt"));
+/// ```
+pub fn hash_cgid_salted(cgid: &str, salt: &[u8]) -> u64 {
+    if cgid.is_empty() || salt.is_empty() {
+        return hash_cgid(cgid);
+    }
+
+    let mut salt_hasher = XxHash64::default();
+    salt_hasher.write(salt);
+
+    let mut hasher = XxHash64::with_seed(salt_hasher.finish());
+    hasher.write(cgid.as_bytes());
+
+    hasher.finish()
+}
+
+/// Utilizes xxHash to hash the length of a `cgid` followed by its bytes into a
+/// 64 bit number and returns that number.
+///
+/// Prefixing the length, as 8 little-endian bytes, gives cgids of different
+/// lengths separate hash domains. The result is **not** compatible with
+/// [`hash_cgid`]: every non-empty cgid hashes to a different value, so labels
+/// encoded with one can't be verified with the other. Passing an empty string
+/// as the `cgid` will result in 0 being returned.
+///
+/// # Examples
+/// ```
+/// use amazon_cloudfront_client_routing_lib::hash::{hash_cgid, hash_cgid_lenprefixed};
+///
+/// assert_ne!(hash_cgid("mv-456"), hash_cgid_lenprefixed("mv-456"));
+/// assert_eq!(0, hash_cgid_lenprefixed(""));
+/// ```
+pub fn hash_cgid_lenprefixed(cgid: &str) -> u64 {
+    if cgid.is_empty() {
+        return 0;
+    }
+
+    let mut hasher = XxHash64::default();
+    hasher.write(&(cgid.len() as u64).to_le_bytes());
+    hasher.write(cgid.as_bytes());
+
+    hasher.finish()
+}
+
+/// Hashes a `cgid` with [`hash_cgid`] and keeps only its `num_bits` least
+/// significant bits.
+///
+/// Matches the value a cgid field `num_bits` wide holds after encoding, for
+/// layouts that trade cgid precision for a shorter label. `num_bits` is capped
+/// at 64. Passing an empty string as the `cgid` will result in 0 being
+/// returned.
+///
+/// # Examples
+/// ```
+/// use amazon_cloudfront_client_routing_lib::hash::{hash_cgid, hash_cgid_truncated};
+///
+/// let hashed_cgid = hash_cgid_truncated("f3663718-7699-4e6e-b482-daa2f690cf64", 40);
+/// assert_eq!(hash_cgid("f3663718-7699-4e6e-b482-daa2f690cf64") & 0xff_ffff_ffff, hashed_cgid);
+///
+/// assert_eq!(hash_cgid("mv-456"), hash_cgid_truncated("mv-456", 64));
+/// assert_eq!(0, hash_cgid_truncated("", 40));
+/// ```
+pub fn hash_cgid_truncated(cgid: &str, num_bits: u8) -> u64 {
+    hash_cgid(cgid) & get_mask(num_bits.min(64))
+}
+
+/// Hashes a `cgid` into a 32 bit number with CRC-32 and returns that number.
+///
+/// Cheaper than [`hash_cgid`] but only suitable for small cgid spaces where
+/// 32 bit collision resistance is enough. Uses the common CRC-32/ISO-HDLC
+/// parameters, the same as zlib. Passing an empty string as the `cgid` will
+/// result in 0 being returned. Requires the `crc` feature.
+///
+/// # Examples
+/// ```
+/// use amazon_cloudfront_client_routing_lib::hash::hash_cgid_crc32;
+///
+/// assert_eq!(1871431112, hash_cgid_crc32("mv-456"));
+/// assert_eq!(0, hash_cgid_crc32(""));
+/// ```
+#[cfg(feature = "crc")]
+pub fn hash_cgid_crc32(cgid: &str) -> u32 {
+    const CRC32: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+    if cgid.is_empty() {
+        return 0;
+    }
+
+    CRC32.checksum(cgid.as_bytes())
+}
+
+/// Returns whether two different `cgid`s hash to the same 64 bit number.
+///
+/// Identical `cgid`s are not considered a collision.
+///
+/// # Examples
+/// ```
+/// use amazon_cloudfront_client_routing_lib::hash::cgids_collide;
+///
+/// assert_eq!(false, cgids_collide("SM89P", "sm89p"));
+/// assert_eq!(false, cgids_collide("SM89P", "SM89P"));
+/// ```
+pub fn cgids_collide(a: &str, b: &str) -> bool {
+    a != b && hash_cgid(a) == hash_cgid(b)
+}
+
+/// Returns every pair of different `ids` that hash to the same 64 bit number.
+///
+/// Each `id` is hashed with [`hash_cgid`] and compared against the ids seen
+/// before it, so each colliding pair is returned once, in the order the second
+/// id appears. Repeated copies of the same id are ignored.
+///
+/// # Examples
+/// ```
+/// use amazon_cloudfront_client_routing_lib::hash::find_collisions;
+///
+/// let collisions = find_collisions(&["SM89P", "DP0124QHYT", "SM89P"]);
+/// assert!(collisions.is_empty());
+/// ```
+pub fn find_collisions(ids: &[&str]) -> Vec<(String, String)> {
+    let mut seen: HashMap<u64, Vec<&str>> = HashMap::with_capacity(ids.len());
+    let mut collisions = Vec::new();
+
+    for id in ids {
+        let ids_with_hash = seen.entry(hash_cgid(id)).or_default();
+        if ids_with_hash.contains(id) {
+            continue;
+        }
+
+        for other in ids_with_hash.iter() {
+            collisions.push((other.to_string(), id.to_string()));
+        }
+        ids_with_hash.push(id);
+    }
+
+    collisions
+}
+
+/// Struct mapping hashed cgids back to the cgids they were hashed from.
+///
+/// Consist of 1 private property: `cgids`, keyed by [`hash_cgid`] of each
+/// registered cgid. Since a client routing label only carries the hash, a
+/// registry of known cgids is needed to recover the human readable cgid.
+///
+/// # Examples
+/// ```
+/// use amazon_cloudfront_client_routing_lib::hash::{hash_cgid, CgidRegistry};
+///
+/// let mut registry = CgidRegistry::new();
+/// registry.register("mv-456");
+///
+/// assert_eq!(Some("mv-456"), registry.resolve(hash_cgid("mv-456")));
+/// assert_eq!(None, registry.resolve(hash_cgid("mv-457")));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CgidRegistry {
+    cgids: HashMap<u64, String>,
+}
+
+impl CgidRegistry {
+    /// Returns an empty [`CgidRegistry`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `cgid` and returns its hash. Registering a cgid whose hash is
+    /// already registered replaces the earlier cgid.
+    pub fn register(&mut self, cgid: &str) -> u64 {
+        let hashed_cgid = hash_cgid(cgid);
+        self.cgids.insert(hashed_cgid, cgid.to_string());
+
+        hashed_cgid
+    }
+
+    /// Returns the registered cgid that hashes to `hashed_cgid`, or [`None`]
+    /// if there isn't one.
+    pub fn resolve(&self, hashed_cgid: u64) -> Option<&str> {
+        self.cgids.get(&hashed_cgid).map(String::as_str)
+    }
+
+    /// Returns how many cgids are registered.
+    pub fn len(&self) -> usize {
+        self.cgids.len()
+    }
+
+    /// Returns whether no cgids are registered.
+    pub fn is_empty(&self) -> bool {
+        self.cgids.is_empty()
+    }
+}
+
+impl<'a> FromIterator<&'a str> for CgidRegistry {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(cgids: I) -> Self {
+        let mut registry = CgidRegistry::new();
+        for cgid in cgids {
+            registry.register(cgid);
+        }
+
+        registry
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::hash_cgid;
+    use super::{
+        cgids_collide, find_collisions, hash_cgid, hash_cgid_lenprefixed, hash_cgid_salted,
+        CgidRegistry,
+    };
 
     #[test]
     fn validate_hash_cgid() {
@@ -64,4 +286,81 @@ mod tests {
     fn validate_hash_empty_cgid_zero() {
         assert_eq!(0, hash_cgid(""));
     }
+
+    #[test]
+    fn validate_hash_cgid_lenprefixed() {
+        assert_eq!(0, hash_cgid_lenprefixed(""));
+
+        for cgid in ["SM89P", "DP0124QHYT", "b086vx9VmK", "abcdefghijhjuio"] {
+            assert_ne!(hash_cgid(cgid), hash_cgid_lenprefixed(cgid));
+            assert_eq!(hash_cgid_lenprefixed(cgid), hash_cgid_lenprefixed(cgid));
+        }
+
+        assert_ne!(hash_cgid_lenprefixed("ab"), hash_cgid_lenprefixed("abc"));
+        assert_ne!(hash_cgid_lenprefixed("ab"), hash_cgid_lenprefixed("ab\u{00}"));
+    }
+
+    #[test]
+    #[cfg(feature = "crc")]
+    fn validate_hash_cgid_crc32() {
+        use super::hash_cgid_crc32;
+
+        assert_eq!(2675485312, hash_cgid_crc32("SM89P"));
+        assert_eq!(2583956718, hash_cgid_crc32("DP0124QHYT"));
+        assert_eq!(1168410944, hash_cgid_crc32("b086vx9VmK"));
+        assert_eq!(1848518364, hash_cgid_crc32("abcdefghijhjuio"));
+        assert_eq!(
+            2623326265,
+            hash_cgid_crc32("VZ9C5G6H12PC5GH7Y0ABCDEFGHIJHJUIOZZAA1")
+        );
+        assert_eq!(0, hash_cgid_crc32(""));
+    }
+
+    #[test]
+    fn validate_hash_salted_cgids() {
+        assert_ne!(hash_cgid("SM89P"), hash_cgid_salted("SM89P", b"salt"));
+        assert_ne!(hash_cgid_salted("SM89P", b"salt"), hash_cgid_salted("SM89P", b"pepper"));
+        assert_eq!(hash_cgid_salted("SM89P", b"salt"), hash_cgid_salted("SM89P", b"salt"));
+        assert_eq!(hash_cgid("SM89P"), hash_cgid_salted("SM89P", b""));
+        assert_eq!(0, hash_cgid_salted("", b"salt"));
+    }
+
+    #[test]
+    fn validate_no_collisions_among_fixture_cgids() {
+        let ids = [
+            "SM89P",
+            "sm89p",
+            "DP0124QHYT",
+            "b086vx9VmK",
+            "B086VX9VMK",
+            "abcdefghijhjuio",
+            "abcdefghijhjuio0",
+            "VZ9C5G6H12PC5GH7Y0ABCDEFGHIJHJUIOZZAA1",
+            "f3663718-7699-4e6e-b482-daa2f690cf64",
+            "mv-456",
+            "",
+        ];
+
+        assert!(find_collisions(&ids).is_empty());
+        for a in ids {
+            for b in ids {
+                assert!(!cgids_collide(a, b));
+            }
+        }
+    }
+
+    #[test]
+    fn validate_find_collisions_ignores_duplicate_ids() {
+        assert!(find_collisions(&["SM89P", "SM89P", "SM89P"]).is_empty());
+    }
+
+    #[test]
+    fn validate_registry_resolves_registered_cgids() {
+        let registry: CgidRegistry = ["SM89P", "DP0124QHYT"].into_iter().collect();
+
+        assert_eq!(2, registry.len());
+        assert_eq!(Some("SM89P"), registry.resolve(9402033733208250942));
+        assert_eq!(Some("DP0124QHYT"), registry.resolve(16745045142164894816));
+        assert_eq!(None, registry.resolve(hash_cgid("sm89p")));
+    }
 }
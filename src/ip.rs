@@ -3,11 +3,53 @@
 
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
-enum SubnetMask {
-    Ipv4 = 24,
-    Ipv6 = 48,
+/// Enum describing how many leading bits of a client ip are kept as its
+/// subnet.
+///
+/// `Ipv4` is the /24 used for every [`Ipv4Addr`] and `Ipv6` is the /48 used by
+/// default for an [`Ipv6Addr`]. `Custom` holds any other number of bits, e.g.
+/// for a [`ParsingConfig`] or [`parse_client_ip_with_defaults`].
+///
+/// # Examples
+/// ```
+/// use amazon_cloudfront_client_routing_lib::ip::SubnetMask;
+///
+/// assert_eq!(24, SubnetMask::Ipv4.bits());
+/// assert_eq!(48, SubnetMask::Ipv6.bits());
+/// assert_eq!(56, SubnetMask::Custom(56).bits());
+///
+/// // masks are equal when they keep the same number of bits
+/// assert_eq!(SubnetMask::Ipv4, SubnetMask::Custom(24));
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub enum SubnetMask {
+    Ipv4,
+    Ipv6,
+    Custom(u8),
+}
+
+impl SubnetMask {
+    /// Returns the number of leading bits kept.
+    pub fn bits(&self) -> u8 {
+        match self {
+            SubnetMask::Ipv4 => 24,
+            SubnetMask::Ipv6 => 48,
+            SubnetMask::Custom(bits) => *bits,
+        }
+    }
 }
 
+impl PartialEq for SubnetMask {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for SubnetMask {}
+
+pub(crate) const MAX_IPV4_SUBNET_MASK: u8 = 32;
+pub(crate) const MAX_IPV6_SUBNET_MASK: u8 = 64;
+
 /// Struct containing 3 values needed for encoding: `client_subnet`,
 /// `subnet_mask`, and `is_ipv6`.
 ///
@@ -39,12 +81,34 @@ enum SubnetMask {
 ///     is_ipv6,
 /// };
 /// ```
+#[derive(Copy, Clone, Debug)]
 pub struct ClientSubnetEncodingData {
     pub client_subnet: u64,
     pub subnet_mask: u64,
     pub is_ipv6: u64,
 }
 
+impl ClientSubnetEncodingData {
+    /// Returns the prefix length that was applied to the client ip.
+    ///
+    /// This is `subnet_mask` as a u8, so callers using a configurable mask like
+    /// [`ParsingConfig`] can verify the mask they expected was applied. Masks
+    /// are clamped to the widths a label supports, so this can be less than the
+    /// mask requested. Returns 0 for an invalid client ip.
+    ///
+    /// # Examples
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::ip::{parse_client_ip, parse_client_ip_with_ipv6_mask};
+    ///
+    /// assert_eq!(24, parse_client_ip("1.2.3.4").applied_prefix());
+    /// assert_eq!(64, parse_client_ip_with_ipv6_mask("0102:0304:0506:0708:090a:0b0c:0d0e:0f10", 96).applied_prefix());
+    /// assert_eq!(0, parse_client_ip("1.2.a").applied_prefix());
+    /// ```
+    pub fn applied_prefix(&self) -> u8 {
+        self.subnet_mask as u8
+    }
+}
+
 /// Parses passed `client_ip` into various data, returns
 /// [`ClientSubnetEncodingData`].
 ///
@@ -54,6 +118,11 @@ pub struct ClientSubnetEncodingData {
 /// information. If unsuccessful, returns [`ClientSubnetEncodingData`] with all
 /// properties set to 0.
 ///
+/// A valid `0.0.0.0` has a `client_subnet` of 0, the same as an invalid
+/// `client_ip`, but keeps its `subnet_mask` of 24. Use
+/// [`has_subnet`](crate::client_routing_label::DecodedClientRoutingLabel::has_subnet)
+/// on the decoded label to tell them apart.
+///
 /// # Examples:
 /// ```
 /// use amazon_cloudfront_client_routing_lib::ip::parse_client_ip;
@@ -77,38 +146,398 @@ pub struct ClientSubnetEncodingData {
 /// assert_eq!(0, client_subnet_encoding_data.is_ipv6);
 /// ```
 pub fn parse_client_ip(client_ip: &str) -> ClientSubnetEncodingData {
-    if let Ok(addr) = client_ip.parse::<IpAddr>() {
-        if addr.is_ipv4() {
-            // unwrap is ok here because we verify it is parsable before
-            let ipv4_address: Ipv4Addr = client_ip.parse().unwrap();
-            ClientSubnetEncodingData {
-                client_subnet: (u32::from_be_bytes(ipv4_address.octets()) as u64 & 0xffffff00)
-                    << 32,
-                subnet_mask: SubnetMask::Ipv4 as u64,
-                is_ipv6: 0,
-            }
-        } else {
-            // unwrap is ok here because we verify it is parsable before
-            let ipv6_address: Ipv6Addr = client_ip.parse().unwrap();
-            ClientSubnetEncodingData {
-                client_subnet: ((u128::from_be_bytes(ipv6_address.octets()) >> 64)
-                    & 0xffffffffffff0000) as u64,
-                subnet_mask: SubnetMask::Ipv6 as u64,
-                is_ipv6: 1,
-            }
+    parse_client_ip_with_ipv6_mask(client_ip, SubnetMask::Ipv6.bits())
+}
+
+/// Parses passed `client_ip` into various data, resolving it as a hostname if
+/// it isn't an ip, returns [`ClientSubnetEncodingData`].
+///
+/// Behaves the same as [`parse_client_ip`] for an ip. Anything else, like a
+/// `"localhost"` sent by a misconfigured upstream, is resolved with
+/// [`ToSocketAddrs`](std::net::ToSocketAddrs) and the first resolved address
+/// is used. If resolution fails or finds nothing, returns
+/// [`ClientSubnetEncodingData`] with all properties set to 0. Resolution may
+/// block on DNS, so only use this where that's acceptable. Requires the
+/// `resolve` feature.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::ip::parse_client_ip_resolve;
+///
+/// let client_subnet_encoding_data = parse_client_ip_resolve("1.2.3.4");
+/// assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], client_subnet_encoding_data.client_subnet.to_be_bytes());
+/// ```
+#[cfg(feature = "resolve")]
+pub fn parse_client_ip_resolve(client_ip: &str) -> ClientSubnetEncodingData {
+    use std::net::ToSocketAddrs;
+
+    let addr = match client_ip.parse() {
+        Ok(addr) => Some(addr),
+        Err(_e) => (client_ip, 0)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut socket_addrs| socket_addrs.next())
+            .map(|socket_addr| socket_addr.ip()),
+    };
+
+    addr_encoding_data(addr, SubnetMask::Ipv4.bits(), SubnetMask::Ipv6.bits())
+}
+
+/// Converts an already parsed `client_ip` into [`ClientSubnetEncodingData`].
+///
+/// Behaves the same as [`parse_client_ip`] for a valid ip, for callers that
+/// already have an [`IpAddr`].
+///
+/// # Examples:
+/// ```
+/// use std::net::{IpAddr, Ipv4Addr};
+/// use amazon_cloudfront_client_routing_lib::ip::{parse_client_ip, parse_client_ip_addr};
+///
+/// let client_subnet_encoding_data = parse_client_ip_addr(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)));
+/// assert_eq!(parse_client_ip("1.2.3.4").client_subnet, client_subnet_encoding_data.client_subnet);
+/// assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+/// ```
+pub fn parse_client_ip_addr(client_ip: IpAddr) -> ClientSubnetEncodingData {
+    addr_encoding_data(Some(client_ip), SubnetMask::Ipv4.bits(), SubnetMask::Ipv6.bits())
+}
+
+/// Parses passed `client_ip` into various data using `ipv6_subnet_mask` for
+/// Ipv6 addresses, returns [`ClientSubnetEncodingData`].
+///
+/// Behaves the same as [`parse_client_ip`] except an [`Ipv6Addr`] keeps its top
+/// `ipv6_subnet_mask` bits instead of 48. The client subnet field of a label is
+/// 64 bits, so `ipv6_subnet_mask` is capped at 64. A `ipv6_subnet_mask` of 0
+/// keeps nothing and is treated like an invalid `client_ip`.
+///
+/// The subnet mask field of a label is only 6 bits, so a /64 mask is encoded
+/// as 0. Because an Ipv6 label never otherwise has a mask of 0, `is_ipv6` is
+/// used together with the mask to tell them apart and
+/// [`DecodedClientRoutingLabel`](crate::client_routing_label::DecodedClientRoutingLabel)
+/// reports it as 64 again.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::ip::parse_client_ip_with_ipv6_mask;
+///
+/// // Ipv6 /56
+/// let mut client_subnet_encoding_data = parse_client_ip_with_ipv6_mask("0102:0304:0506:0708:090a:0b0c:0d0e:0f10", 56);
+/// assert_eq!([1, 2, 3, 4, 5, 6, 7, 0], client_subnet_encoding_data.client_subnet.to_be_bytes());
+/// assert_eq!(56, client_subnet_encoding_data.subnet_mask);
+/// assert_eq!(1, client_subnet_encoding_data.is_ipv6);
+///
+/// // Ipv6 /64
+/// client_subnet_encoding_data = parse_client_ip_with_ipv6_mask("0102:0304:0506:0708:090a:0b0c:0d0e:0f10", 64);
+/// assert_eq!([1, 2, 3, 4, 5, 6, 7, 8], client_subnet_encoding_data.client_subnet.to_be_bytes());
+/// assert_eq!(64, client_subnet_encoding_data.subnet_mask);
+///
+/// // Ipv4 is unaffected
+/// client_subnet_encoding_data = parse_client_ip_with_ipv6_mask("1.2.3.4", 64);
+/// assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], client_subnet_encoding_data.client_subnet.to_be_bytes());
+/// assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+/// ```
+pub fn parse_client_ip_with_ipv6_mask(
+    client_ip: &str,
+    ipv6_subnet_mask: u8,
+) -> ClientSubnetEncodingData {
+    parse_client_ip_with_defaults(client_ip, SubnetMask::Ipv4, SubnetMask::Custom(ipv6_subnet_mask))
+}
+
+/// Parses passed `client_ip` into various data using `ipv4_subnet_mask` for
+/// Ipv4 addresses and `ipv6_subnet_mask` for Ipv6 addresses, returns
+/// [`ClientSubnetEncodingData`].
+///
+/// Behaves the same as [`parse_client_ip_with_ipv6_mask`] except the Ipv4 mask
+/// is configurable too. `ipv4_subnet_mask` is capped at 32 and, like
+/// `ipv6_subnet_mask`, a mask of 0 keeps nothing and is treated like an
+/// invalid `client_ip`. See [`ParsingConfig`] to set both masks once.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::ip::{parse_client_ip_with_defaults, SubnetMask};
+///
+/// let mut client_subnet_encoding_data = parse_client_ip_with_defaults("0102:0304:0506:0708:090a:0b0c:0d0e:0f10", SubnetMask::Ipv4, SubnetMask::Custom(56));
+/// assert_eq!([1, 2, 3, 4, 5, 6, 7, 0], client_subnet_encoding_data.client_subnet.to_be_bytes());
+/// assert_eq!(56, client_subnet_encoding_data.subnet_mask);
+///
+/// client_subnet_encoding_data = parse_client_ip_with_defaults("1.2.3.4", SubnetMask::Custom(16), SubnetMask::Custom(56));
+/// assert_eq!([1, 2, 0, 0, 0, 0, 0, 0], client_subnet_encoding_data.client_subnet.to_be_bytes());
+/// assert_eq!(16, client_subnet_encoding_data.subnet_mask);
+/// ```
+pub fn parse_client_ip_with_defaults(
+    client_ip: &str,
+    ipv4_subnet_mask: SubnetMask,
+    ipv6_subnet_mask: SubnetMask,
+) -> ClientSubnetEncodingData {
+    addr_encoding_data(client_ip.parse().ok(), ipv4_subnet_mask.bits(), ipv6_subnet_mask.bits())
+}
+
+/// ECS address family code for Ipv4, per RFC 7871 and the IANA address family
+/// numbers.
+pub const ECS_FAMILY_IPV4: u16 = 1;
+/// ECS address family code for Ipv6.
+pub const ECS_FAMILY_IPV6: u16 = 2;
+
+/// Converts an EDNS Client Subnet (ECS) option into
+/// [`ClientSubnetEncodingData`].
+///
+/// `family` is the ECS family code, [`ECS_FAMILY_IPV4`] or [`ECS_FAMILY_IPV6`],
+/// `source_prefix` is the ECS source prefix length, and `address_bytes` is the
+/// ECS address. ECS truncates the address to the bytes the prefix covers, so
+/// missing trailing bytes are treated as 0 and extra bytes are ignored.
+/// `source_prefix` is clamped the same way as [`parse_client_ip_with_defaults`]:
+/// to 32 for Ipv4 and 64 for Ipv6. An unknown `family` or a `source_prefix` of
+/// 0 returns [`ClientSubnetEncodingData`] with all properties set to 0.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::ip::{from_ecs, ECS_FAMILY_IPV4, ECS_FAMILY_IPV6};
+///
+/// let mut client_subnet_encoding_data = from_ecs(ECS_FAMILY_IPV4, 24, &[1, 2, 3]);
+/// assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], client_subnet_encoding_data.client_subnet.to_be_bytes());
+/// assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+/// assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+///
+/// client_subnet_encoding_data = from_ecs(ECS_FAMILY_IPV6, 56, &[1, 2, 3, 4, 5, 6, 7]);
+/// assert_eq!([1, 2, 3, 4, 5, 6, 7, 0], client_subnet_encoding_data.client_subnet.to_be_bytes());
+/// assert_eq!(56, client_subnet_encoding_data.subnet_mask);
+/// assert_eq!(1, client_subnet_encoding_data.is_ipv6);
+/// ```
+pub fn from_ecs(family: u16, source_prefix: u8, address_bytes: &[u8]) -> ClientSubnetEncodingData {
+    let addr = match family {
+        ECS_FAMILY_IPV4 => {
+            let mut octets = [0_u8; 4];
+            let num_bytes = address_bytes.len().min(octets.len());
+            octets[..num_bytes].copy_from_slice(&address_bytes[..num_bytes]);
+            Some(IpAddr::from(octets))
         }
-    } else {
-        ClientSubnetEncodingData {
+        ECS_FAMILY_IPV6 => {
+            let mut octets = [0_u8; 16];
+            let num_bytes = address_bytes.len().min(octets.len());
+            octets[..num_bytes].copy_from_slice(&address_bytes[..num_bytes]);
+            Some(IpAddr::from(octets))
+        }
+        _ => None,
+    };
+
+    addr_encoding_data(addr, source_prefix, source_prefix)
+}
+
+/// Returns [`ClientSubnetEncodingData`] for `addr` keeping `ipv4_subnet_mask`
+/// or `ipv6_subnet_mask` bits, or all 0 if there's no `addr` or the mask is 0.
+fn addr_encoding_data(
+    addr: Option<IpAddr>,
+    ipv4_subnet_mask: u8,
+    ipv6_subnet_mask: u8,
+) -> ClientSubnetEncodingData {
+    let ipv4_subnet_mask = ipv4_subnet_mask.min(MAX_IPV4_SUBNET_MASK);
+    let ipv6_subnet_mask = ipv6_subnet_mask.min(MAX_IPV6_SUBNET_MASK);
+    match addr {
+        Some(IpAddr::V4(ipv4_address)) if ipv4_subnet_mask > 0 => ClientSubnetEncodingData {
+            client_subnet: mask_ipv4(ipv4_address, ipv4_subnet_mask),
+            subnet_mask: ipv4_subnet_mask as u64,
+            is_ipv6: 0,
+        },
+        Some(IpAddr::V6(ipv6_address)) if ipv6_subnet_mask > 0 => ClientSubnetEncodingData {
+            client_subnet: mask_ipv6(ipv6_address, ipv6_subnet_mask),
+            subnet_mask: ipv6_subnet_mask as u64,
+            is_ipv6: 1,
+        },
+        _ => ClientSubnetEncodingData {
             client_subnet: 0,
             subnet_mask: 0,
             is_ipv6: 0,
+        },
+    }
+}
+
+/// Struct holding the Ipv4 and Ipv6 subnet masks used when parsing client ips.
+///
+/// Lets an application pick its subnet masks once, e.g. a /56 for Ipv6, and
+/// reuse them for every request. [`Default`] uses the same /24 and /48 as
+/// [`parse_client_ip`].
+///
+/// # Examples
+/// ```
+/// use amazon_cloudfront_client_routing_lib::ip::{ParsingConfig, SubnetMask};
+///
+/// let parsing_config = ParsingConfig::new(SubnetMask::Ipv4, SubnetMask::Custom(56));
+///
+/// let client_subnet_encoding_data = parsing_config.parse_client_ip("0102:0304:0506:0708:090a:0b0c:0d0e:0f10");
+/// assert_eq!([1, 2, 3, 4, 5, 6, 7, 0], client_subnet_encoding_data.client_subnet.to_be_bytes());
+/// assert_eq!(56, client_subnet_encoding_data.subnet_mask);
+///
+/// assert_eq!(ParsingConfig::new(SubnetMask::Ipv4, SubnetMask::Ipv6), ParsingConfig::default());
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ParsingConfig {
+    pub ipv4_subnet_mask: SubnetMask,
+    pub ipv6_subnet_mask: SubnetMask,
+}
+
+impl Default for ParsingConfig {
+    fn default() -> Self {
+        Self::new(SubnetMask::Ipv4, SubnetMask::Ipv6)
+    }
+}
+
+impl ParsingConfig {
+    /// Returns a [`ParsingConfig`] using `ipv4_subnet_mask` and
+    /// `ipv6_subnet_mask`.
+    pub fn new(ipv4_subnet_mask: SubnetMask, ipv6_subnet_mask: SubnetMask) -> Self {
+        Self {
+            ipv4_subnet_mask,
+            ipv6_subnet_mask,
         }
     }
+
+    /// Parses passed `client_ip` with this config's subnet masks, see
+    /// [`parse_client_ip_with_defaults`].
+    pub fn parse_client_ip(&self, client_ip: &str) -> ClientSubnetEncodingData {
+        parse_client_ip_with_defaults(client_ip, self.ipv4_subnet_mask, self.ipv6_subnet_mask)
+    }
+}
+
+/// Parses the client ip out of an `X-Forwarded-For` style `header`, returns
+/// [`ClientSubnetEncodingData`].
+///
+/// `header` is a comma separated list of ips, with the closest proxy last.
+/// The last `trust_depth` entries are skipped as trusted proxies and the entry
+/// before them is parsed with [`parse_client_ip`]. Whitespace around each
+/// entry is ignored. If `header` doesn't have that many entries or the entry
+/// is invalid, returns [`ClientSubnetEncodingData`] with all properties set
+/// to 0.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::ip::parse_client_ip_xff;
+///
+/// let mut client_subnet_encoding_data = parse_client_ip_xff("85.83.215.126, 10.0.0.1", 1);
+/// assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], client_subnet_encoding_data.client_subnet.to_be_bytes());
+/// assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+///
+/// client_subnet_encoding_data = parse_client_ip_xff("85.83.215.126, 10.0.0.1", 0);
+/// assert_eq!([10, 0, 0, 0, 0, 0, 0, 0], client_subnet_encoding_data.client_subnet.to_be_bytes());
+///
+/// // not enough entries
+/// client_subnet_encoding_data = parse_client_ip_xff("85.83.215.126, 10.0.0.1", 2);
+/// assert_eq!(0, client_subnet_encoding_data.subnet_mask);
+/// ```
+pub fn parse_client_ip_xff(header: &str, trust_depth: usize) -> ClientSubnetEncodingData {
+    match header.rsplit(',').nth(trust_depth) {
+        Some(client_ip) => parse_client_ip(client_ip.trim()),
+        None => ClientSubnetEncodingData {
+            client_subnet: 0,
+            subnet_mask: 0,
+            is_ipv6: 0,
+        },
+    }
+}
+
+/// Returns the top `prefix` bits of `addr` formatted as a client subnet.
+///
+/// Keeps the top `prefix` bits of the address, zeroes the rest, then shifts it
+/// into the top 32 bits of a u64, the same way [`parse_client_ip`] does.
+/// `prefix` is capped at 32.
+///
+/// # Examples:
+/// ```
+/// use std::net::Ipv4Addr;
+/// use amazon_cloudfront_client_routing_lib::ip::mask_ipv4;
+///
+/// assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], mask_ipv4(Ipv4Addr::new(1, 2, 3, 4), 24).to_be_bytes());
+/// assert_eq!([1, 2, 0, 0, 0, 0, 0, 0], mask_ipv4(Ipv4Addr::new(1, 2, 3, 4), 16).to_be_bytes());
+/// ```
+pub fn mask_ipv4(addr: Ipv4Addr, prefix: u8) -> u64 {
+    let mask = u32::MAX.checked_shr(prefix as u32).unwrap_or(0);
+
+    ((u32::from_be_bytes(addr.octets()) & !mask) as u64) << 32
+}
+
+/// Returns the top `prefix` bits of `addr` formatted as a client subnet.
+///
+/// Keeps the top 64 bits of the address, then zeroes everything below the top
+/// `prefix` bits, the same way [`parse_client_ip`] does. `prefix` is capped at
+/// 64.
+///
+/// # Examples:
+/// ```
+/// use std::net::Ipv6Addr;
+/// use amazon_cloudfront_client_routing_lib::ip::mask_ipv6;
+///
+/// let addr: Ipv6Addr = "0102:0304:0506:0708:090a:0b0c:0d0e:0f10".parse().unwrap();
+/// assert_eq!([1, 2, 3, 4, 5, 6, 0, 0], mask_ipv6(addr, 48).to_be_bytes());
+/// assert_eq!([1, 2, 3, 4, 5, 6, 7, 8], mask_ipv6(addr, 64).to_be_bytes());
+/// ```
+pub fn mask_ipv6(addr: Ipv6Addr, prefix: u8) -> u64 {
+    let mask = u64::MAX.checked_shr(prefix as u32).unwrap_or(0);
+
+    (u128::from_be_bytes(addr.octets()) >> 64) as u64 & !mask
+}
+
+/// Returns the longest prefix for `family` whose block holds at least `k`
+/// addresses, for picking a subnet mask that meets a k-anonymity target.
+///
+/// `family` is [`ECS_FAMILY_IPV4`] or [`ECS_FAMILY_IPV6`]. A /p block holds
+/// 2^(32 - p) Ipv4 or 2^(128 - p) Ipv6 addresses, so the result is the address
+/// width minus `k` rounded up to a power of 2. Ipv6 results are capped at 64,
+/// the widest mask a label holds, which every `k` fits. A `k` of 0 or 1 needs
+/// no grouping and returns the widest mask. Returns 0, no subnet, for an
+/// unknown `family` or a `k` more than the whole address space.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::ip::{mask_for_k_anonymity, ECS_FAMILY_IPV4, ECS_FAMILY_IPV6};
+///
+/// assert_eq!(24, mask_for_k_anonymity(ECS_FAMILY_IPV4, 256));
+/// assert_eq!(22, mask_for_k_anonymity(ECS_FAMILY_IPV4, 1000));
+/// assert_eq!(64, mask_for_k_anonymity(ECS_FAMILY_IPV6, 1 << 40));
+/// ```
+pub fn mask_for_k_anonymity(family: u16, k: u64) -> u8 {
+    let (address_bits, max_subnet_mask): (u8, u8) = match family {
+        ECS_FAMILY_IPV4 => (32, MAX_IPV4_SUBNET_MASK),
+        ECS_FAMILY_IPV6 => (128, MAX_IPV6_SUBNET_MASK),
+        _ => return 0,
+    };
+
+    // bits needed to count k addresses, i.e. ceil(log2(k))
+    let host_bits = match k {
+        0 | 1 => 0,
+        _ => 64 - (k - 1).leading_zeros() as u8,
+    };
+
+    address_bits.saturating_sub(host_bits).min(max_subnet_mask)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parse_client_ip;
+    use super::{
+        from_ecs, mask_for_k_anonymity, mask_ipv4, ECS_FAMILY_IPV4, ECS_FAMILY_IPV6, mask_ipv6, parse_client_ip, parse_client_ip_with_defaults,
+        parse_client_ip_with_ipv6_mask, parse_client_ip_xff, ParsingConfig, SubnetMask,
+    };
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    #[cfg(feature = "resolve")]
+    fn validate_parse_client_ip_resolve_localhost() {
+        use super::parse_client_ip_resolve;
+
+        // localhost may resolve to either loopback address
+        let client_subnet_encoding_data = parse_client_ip_resolve("localhost");
+        if client_subnet_encoding_data.is_ipv6 == 0 {
+            assert_eq!([127, 0, 0, 0, 0, 0, 0, 0], client_subnet_encoding_data.client_subnet.to_be_bytes());
+            assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+        } else {
+            assert_eq!(0, client_subnet_encoding_data.client_subnet);
+            assert_eq!(48, client_subnet_encoding_data.subnet_mask);
+        }
+
+        let client_subnet_encoding_data = parse_client_ip_resolve("1.2.3.4");
+        assert_eq!(parse_client_ip("1.2.3.4").client_subnet, client_subnet_encoding_data.client_subnet);
+
+        let client_subnet_encoding_data = parse_client_ip_resolve("not a host");
+        assert_eq!(0, client_subnet_encoding_data.client_subnet);
+        assert_eq!(0, client_subnet_encoding_data.subnet_mask);
+    }
 
     #[test]
     fn validate_parse_ipv4() {
@@ -122,6 +551,24 @@ mod tests {
         assert_eq!(0, client_subnet_encoding_data.is_ipv6);
     }
 
+    #[test]
+    fn validate_parse_ipv4_extremes() {
+        let client_subnet_encoding_data = parse_client_ip("255.255.255.255");
+
+        assert_eq!(
+            [255, 255, 255, 0, 0, 0, 0, 0],
+            client_subnet_encoding_data.client_subnet.to_be_bytes()
+        );
+        assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+
+        let client_subnet_encoding_data = parse_client_ip("0.0.0.0");
+
+        assert_eq!(0, client_subnet_encoding_data.client_subnet);
+        assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+    }
+
     #[test]
     fn validate_parse_ipv6() {
         let client_subnet_encoding_data =
@@ -155,4 +602,331 @@ mod tests {
         assert_eq!(0, client_subnet_encoding_data.subnet_mask);
         assert_eq!(0, client_subnet_encoding_data.is_ipv6);
     }
+
+    #[test]
+    fn validate_parse_ipv6_with_finer_masks() {
+        let client_subnet_encoding_data =
+            parse_client_ip_with_ipv6_mask("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", 56);
+
+        assert_eq!(
+            [0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0x00, 0x00],
+            client_subnet_encoding_data.client_subnet.to_be_bytes()
+        );
+        assert_eq!(56, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(1, client_subnet_encoding_data.is_ipv6);
+
+        let client_subnet_encoding_data =
+            parse_client_ip_with_ipv6_mask("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", 64);
+
+        assert_eq!(
+            [0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0x00, 0x94],
+            client_subnet_encoding_data.client_subnet.to_be_bytes()
+        );
+        assert_eq!(64, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(1, client_subnet_encoding_data.is_ipv6);
+    }
+
+    #[test]
+    fn validate_parse_ipv6_with_out_of_range_masks() {
+        let client_subnet_encoding_data =
+            parse_client_ip_with_ipv6_mask("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", 128);
+
+        assert_eq!(9340004030419828884, client_subnet_encoding_data.client_subnet);
+        assert_eq!(64, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(1, client_subnet_encoding_data.is_ipv6);
+
+        let client_subnet_encoding_data =
+            parse_client_ip_with_ipv6_mask("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", 0);
+
+        assert_eq!(0, client_subnet_encoding_data.client_subnet);
+        assert_eq!(0, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+    }
+
+    #[test]
+    fn validate_parse_with_defaults_24_56() {
+        let client_subnet_encoding_data =
+            parse_client_ip_with_defaults("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", SubnetMask::Ipv4, SubnetMask::Custom(56));
+
+        assert_eq!(
+            [0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0x00, 0x00],
+            client_subnet_encoding_data.client_subnet.to_be_bytes()
+        );
+        assert_eq!(56, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(1, client_subnet_encoding_data.is_ipv6);
+
+        let client_subnet_encoding_data = parse_client_ip_with_defaults("85.83.215.126", SubnetMask::Ipv4, SubnetMask::Custom(56));
+
+        assert_eq!(
+            6148494311290830848,
+            client_subnet_encoding_data.client_subnet
+        );
+        assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+
+        let client_subnet_encoding_data = parse_client_ip_with_defaults("1.2.a", SubnetMask::Ipv4, SubnetMask::Custom(56));
+
+        assert_eq!(0, client_subnet_encoding_data.client_subnet);
+        assert_eq!(0, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+    }
+
+    #[test]
+    fn validate_parse_with_defaults_out_of_range_ipv4_masks() {
+        let client_subnet_encoding_data = parse_client_ip_with_defaults("85.83.215.126", SubnetMask::Custom(40), SubnetMask::Custom(56));
+
+        assert_eq!(
+            [85, 83, 215, 126, 0, 0, 0, 0],
+            client_subnet_encoding_data.client_subnet.to_be_bytes()
+        );
+        assert_eq!(32, client_subnet_encoding_data.subnet_mask);
+
+        let client_subnet_encoding_data = parse_client_ip_with_defaults("85.83.215.126", SubnetMask::Custom(0), SubnetMask::Custom(56));
+
+        assert_eq!(0, client_subnet_encoding_data.client_subnet);
+        assert_eq!(0, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+    }
+
+    #[test]
+    fn validate_parsing_config() {
+        let parsing_config = ParsingConfig::new(SubnetMask::Ipv4, SubnetMask::Custom(56));
+
+        for client_ip in [
+            "85.83.215.126",
+            "819e:5c2e:21e4:0094:4805:1635:f8e4:049b",
+            "1.2.a",
+        ] {
+            let from_config = parsing_config.parse_client_ip(client_ip);
+            let from_fn = parse_client_ip_with_defaults(client_ip, SubnetMask::Ipv4, SubnetMask::Custom(56));
+
+            assert_eq!(from_fn.client_subnet, from_config.client_subnet);
+            assert_eq!(from_fn.subnet_mask, from_config.subnet_mask);
+            assert_eq!(from_fn.is_ipv6, from_config.is_ipv6);
+        }
+
+        // default config keeps parse_client_ip's 24/48
+        let client_ip = "819e:5c2e:21e4:0094:4805:1635:f8e4:049b";
+        assert_eq!(
+            parse_client_ip(client_ip).client_subnet,
+            ParsingConfig::default()
+                .parse_client_ip(client_ip)
+                .client_subnet
+        );
+        assert_eq!(
+            48,
+            ParsingConfig::default()
+                .parse_client_ip(client_ip)
+                .subnet_mask
+        );
+    }
+
+    #[test]
+    fn validate_mask_for_k_anonymity() {
+        assert_eq!(24, mask_for_k_anonymity(ECS_FAMILY_IPV4, 256));
+        assert_eq!(22, mask_for_k_anonymity(ECS_FAMILY_IPV4, 1024));
+        assert_eq!(23, mask_for_k_anonymity(ECS_FAMILY_IPV4, 257));
+        assert_eq!(32, mask_for_k_anonymity(ECS_FAMILY_IPV4, 0));
+        assert_eq!(32, mask_for_k_anonymity(ECS_FAMILY_IPV4, 1));
+        assert_eq!(31, mask_for_k_anonymity(ECS_FAMILY_IPV4, 2));
+        assert_eq!(0, mask_for_k_anonymity(ECS_FAMILY_IPV4, 1 << 32));
+        assert_eq!(0, mask_for_k_anonymity(ECS_FAMILY_IPV4, u64::MAX));
+
+        assert_eq!(64, mask_for_k_anonymity(ECS_FAMILY_IPV6, 1));
+        assert_eq!(64, mask_for_k_anonymity(ECS_FAMILY_IPV6, u64::MAX));
+
+        assert_eq!(0, mask_for_k_anonymity(3, 256));
+    }
+
+    #[test]
+    fn validate_from_ecs_ipv4_24() {
+        let client_subnet_encoding_data = from_ecs(ECS_FAMILY_IPV4, 24, &[85, 83, 215]);
+
+        assert_eq!(
+            parse_client_ip("85.83.215.126").client_subnet,
+            client_subnet_encoding_data.client_subnet
+        );
+        assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+
+        // bits past the prefix are dropped even if the option carries them
+        let client_subnet_encoding_data = from_ecs(ECS_FAMILY_IPV4, 24, &[85, 83, 215, 126]);
+
+        assert_eq!(6148494311290830848, client_subnet_encoding_data.client_subnet);
+
+        // prefix clamped to 32
+        let client_subnet_encoding_data = from_ecs(ECS_FAMILY_IPV4, 40, &[85, 83, 215, 126]);
+
+        assert_eq!(
+            [85, 83, 215, 126, 0, 0, 0, 0],
+            client_subnet_encoding_data.client_subnet.to_be_bytes()
+        );
+        assert_eq!(32, client_subnet_encoding_data.subnet_mask);
+    }
+
+    #[test]
+    fn validate_from_ecs_ipv6_48() {
+        let client_subnet_encoding_data =
+            from_ecs(ECS_FAMILY_IPV6, 48, &[0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4]);
+
+        assert_eq!(
+            parse_client_ip("819e:5c2e:21e4:0094:4805:1635:f8e4:049b").client_subnet,
+            client_subnet_encoding_data.client_subnet
+        );
+        assert_eq!(48, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(1, client_subnet_encoding_data.is_ipv6);
+
+        // prefix clamped to 64
+        let client_subnet_encoding_data = from_ecs(
+            ECS_FAMILY_IPV6,
+            128,
+            &[0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0x00, 0x94, 0x48, 0x05],
+        );
+
+        assert_eq!(9340004030419828884, client_subnet_encoding_data.client_subnet);
+        assert_eq!(64, client_subnet_encoding_data.subnet_mask);
+    }
+
+    #[test]
+    fn validate_from_ecs_unknown_family_or_zero_prefix() {
+        for client_subnet_encoding_data in [
+            from_ecs(3, 24, &[85, 83, 215]),
+            from_ecs(ECS_FAMILY_IPV4, 0, &[]),
+            from_ecs(ECS_FAMILY_IPV6, 0, &[]),
+        ] {
+            assert_eq!(0, client_subnet_encoding_data.client_subnet);
+            assert_eq!(0, client_subnet_encoding_data.subnet_mask);
+            assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+        }
+    }
+
+    #[test]
+    fn validate_applied_prefix() {
+        assert_eq!(24, parse_client_ip("85.83.215.126").applied_prefix());
+        assert_eq!(
+            48,
+            parse_client_ip("819e:5c2e:21e4:0094:4805:1635:f8e4:049b").applied_prefix()
+        );
+        assert_eq!(0, parse_client_ip("1.2.a").applied_prefix());
+        assert_eq!(0, parse_client_ip("").applied_prefix());
+
+        let parsing_config = ParsingConfig::new(SubnetMask::Custom(16), SubnetMask::Custom(56));
+        assert_eq!(16, parsing_config.parse_client_ip("85.83.215.126").applied_prefix());
+        assert_eq!(
+            56,
+            parsing_config
+                .parse_client_ip("819e:5c2e:21e4:0094:4805:1635:f8e4:049b")
+                .applied_prefix()
+        );
+        assert_eq!(0, parsing_config.parse_client_ip("1.2.a").applied_prefix());
+
+        // clamped to what the label supports
+        assert_eq!(
+            64,
+            parse_client_ip_with_ipv6_mask("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", 128)
+                .applied_prefix()
+        );
+    }
+
+    #[test]
+    fn validate_parse_xff_single_entry() {
+        let client_subnet_encoding_data = parse_client_ip_xff("85.83.215.126", 0);
+
+        assert_eq!(
+            6148494311290830848,
+            client_subnet_encoding_data.client_subnet
+        );
+        assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+
+        let client_subnet_encoding_data = parse_client_ip_xff("85.83.215.126", 1);
+
+        assert_eq!(0, client_subnet_encoding_data.client_subnet);
+        assert_eq!(0, client_subnet_encoding_data.subnet_mask);
+    }
+
+    #[test]
+    fn validate_parse_xff_multiple_entries() {
+        let header = "819e:5c2e:21e4:0094:4805:1635:f8e4:049b, 85.83.215.126,10.0.0.1";
+
+        let client_subnet_encoding_data = parse_client_ip_xff(header, 0);
+        assert_eq!(
+            [10, 0, 0, 0, 0, 0, 0, 0],
+            client_subnet_encoding_data.client_subnet.to_be_bytes()
+        );
+        assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+
+        let client_subnet_encoding_data = parse_client_ip_xff(header, 1);
+        assert_eq!(
+            6148494311290830848,
+            client_subnet_encoding_data.client_subnet
+        );
+        assert_eq!(24, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+
+        let client_subnet_encoding_data = parse_client_ip_xff(header, 2);
+        assert_eq!(
+            9340004030419828736,
+            client_subnet_encoding_data.client_subnet
+        );
+        assert_eq!(48, client_subnet_encoding_data.subnet_mask);
+        assert_eq!(1, client_subnet_encoding_data.is_ipv6);
+    }
+
+    #[test]
+    fn validate_parse_xff_malformed_entries() {
+        for (header, trust_depth) in [
+            ("", 0),
+            (" , ", 0),
+            ("85.83.215.126, unknown", 0),
+            ("85.83.215.126,,10.0.0.1", 1),
+            ("85.83.215", 0),
+        ] {
+            let client_subnet_encoding_data = parse_client_ip_xff(header, trust_depth);
+
+            assert_eq!(0, client_subnet_encoding_data.client_subnet);
+            assert_eq!(0, client_subnet_encoding_data.subnet_mask);
+            assert_eq!(0, client_subnet_encoding_data.is_ipv6);
+        }
+    }
+
+    #[test]
+    fn validate_subnet_mask_bits() {
+        assert_eq!(24, SubnetMask::Ipv4.bits());
+        assert_eq!(48, SubnetMask::Ipv6.bits());
+        assert_eq!(56, SubnetMask::Custom(56).bits());
+        assert_eq!(
+            SubnetMask::Ipv4.bits() as u64,
+            parse_client_ip("85.83.215.126").subnet_mask
+        );
+        assert_eq!(
+            SubnetMask::Ipv6.bits() as u64,
+            parse_client_ip("819e:5c2e:21e4:0094:4805:1635:f8e4:049b").subnet_mask
+        );
+    }
+
+    #[test]
+    fn validate_mask_ipv4() {
+        let addr: Ipv4Addr = "85.83.215.126".parse().unwrap();
+
+        assert_eq!(6148494311290830848, mask_ipv4(addr, 24));
+        assert_eq!(parse_client_ip("85.83.215.126").client_subnet, mask_ipv4(addr, 24));
+        assert_eq!(0, mask_ipv4(addr, 0));
+        assert_eq!(6148494852456710144, mask_ipv4(addr, 32));
+        assert_eq!(mask_ipv4(addr, 32), mask_ipv4(addr, 40));
+    }
+
+    #[test]
+    fn validate_mask_ipv6() {
+        let addr: Ipv6Addr = "819e:5c2e:21e4:0094:4805:1635:f8e4:049b".parse().unwrap();
+
+        assert_eq!(9340004030419828736, mask_ipv6(addr, 48));
+        assert_eq!(0, mask_ipv6(addr, 0));
+        assert_eq!(9340004030419828884, mask_ipv6(addr, 64));
+        assert_eq!(mask_ipv6(addr, 64), mask_ipv6(addr, 128));
+
+        let addr: Ipv6Addr = "0319:7db1:f4d6::".parse().unwrap();
+
+        assert_eq!(223347859801899008, mask_ipv6(addr, 48));
+    }
 }
@@ -0,0 +1,305 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::client_routing_label::{EncodableData, LABEL_FIELDS};
+use crate::encode_decode::Base32;
+use crate::errors::DecodeError;
+
+/// Number of bits in the `timestamp` field of [`LabelCodec::timestamped`].
+pub const TIMESTAMP_NUM_BITS: u8 = 20;
+
+/// Struct for encoding and decoding labels with a custom field layout.
+///
+/// Consist of 2 properties: `layout` and `encoding_system`. `layout` is a list
+/// of `(name, num_bits)` pairs, in the order the fields appear in a label. It
+/// exposes the same [`Base32`] and [`EncodableData`] machinery that
+/// [`ClientRoutingLabel`](crate::client_routing_label::ClientRoutingLabel) uses
+/// for its fixed 5 field layout.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::label_codec::LabelCodec;
+///
+/// let codec = LabelCodec::new(vec![("version", 10), ("flag", 1), ("id", 64)]);
+///
+/// let encoded_label = codec.encode(&[1, 1, 8517775255794402596]);
+/// assert_eq!("abxmnjxo5hdzrje", encoded_label);
+///
+/// let values = codec.decode(encoded_label.as_bytes()).unwrap();
+/// assert_eq!(vec![1, 1, 8517775255794402596], values);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LabelCodec {
+    layout: Vec<(String, u8)>,
+    encoding_system: Base32,
+}
+
+impl LabelCodec {
+    /// Returns a [`LabelCodec`] for `layout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a field has more than 64 bits or the whole layout has more
+    /// than 255 bits, since neither can be represented by [`EncodableData`] and
+    /// [`Base32`].
+    pub fn new(layout: Vec<(&str, u8)>) -> Self {
+        let mut total_num_bits: usize = 0;
+        for (name, num_bits) in layout.iter() {
+            assert!(*num_bits <= 64, "field {} has more than 64 bits", name);
+            total_num_bits += *num_bits as usize;
+        }
+        assert!(
+            total_num_bits <= u8::MAX as usize,
+            "layout has more than {} bits",
+            u8::MAX
+        );
+
+        Self {
+            layout: layout
+                .into_iter()
+                .map(|(name, num_bits)| (name.to_string(), num_bits))
+                .collect(),
+            encoding_system: Base32::default(),
+        }
+    }
+
+    /// Returns a [`LabelCodec`] for the default
+    /// [`ClientRoutingLabel`](crate::client_routing_label::ClientRoutingLabel)
+    /// layout in [`LABEL_FIELDS`].
+    ///
+    /// Combine with [`with_cgid_bits`](LabelCodec::with_cgid_bits) to
+    /// experiment with shorter labels.
+    pub fn client_routing_label() -> Self {
+        Self::new(LABEL_FIELDS.to_vec())
+    }
+
+    /// Returns a [`LabelCodec`] for the default layout followed by a
+    /// [`TIMESTAMP_NUM_BITS`] wide `timestamp` field, making 165 bit, 33 char
+    /// labels.
+    ///
+    /// Used by
+    /// [`encode_request_data_timestamped`](crate::encode_request_data_timestamped)
+    /// to carry a coarse timestamp for anti-replay checks.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::label_codec::LabelCodec;
+    ///
+    /// let codec = LabelCodec::timestamped();
+    /// assert_eq!(165, codec.total_num_bits());
+    /// assert_eq!(Some(5), codec.field_index("timestamp"));
+    /// ```
+    pub fn timestamped() -> Self {
+        let mut layout = LABEL_FIELDS.to_vec();
+        layout.push(("timestamp", TIMESTAMP_NUM_BITS));
+
+        Self::new(layout)
+    }
+
+    /// Returns this [`LabelCodec`] with its `cgid` field `num_bits` wide.
+    ///
+    /// Narrower cgids make shorter labels at the cost of more collisions. Hash
+    /// cgids for it with
+    /// [`hash_cgid_truncated`](crate::hash::hash_cgid_truncated).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the layout has no `cgid` field or `num_bits` is more than 64.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::hash::hash_cgid_truncated;
+    /// use amazon_cloudfront_client_routing_lib::label_codec::LabelCodec;
+    ///
+    /// let codec = LabelCodec::client_routing_label().with_cgid_bits(40);
+    /// assert_eq!(121, codec.total_num_bits());
+    ///
+    /// let encoded_label = codec.encode(&[1, 0, 6148494311290830848, 24, hash_cgid_truncated("mv-456", 40)]);
+    /// assert_eq!(25, encoded_label.len());
+    /// ```
+    pub fn with_cgid_bits(mut self, num_bits: u8) -> Self {
+        assert!(num_bits <= 64, "field cgid has more than 64 bits");
+        let cgid_index = match self.field_index("cgid") {
+            Some(cgid_index) => cgid_index,
+            None => panic!("layout has no cgid field"),
+        };
+
+        self.layout[cgid_index].1 = num_bits;
+        self
+    }
+
+    /// Returns the `(name, num_bits)` layout of each field.
+    pub fn layout(&self) -> &[(String, u8)] {
+        &self.layout
+    }
+
+    /// Returns the index of the field called `name`, if there is one.
+    ///
+    /// # Examples:
+    /// ```
+    /// use amazon_cloudfront_client_routing_lib::label_codec::LabelCodec;
+    ///
+    /// let codec = LabelCodec::new(vec![("version", 10), ("flag", 1), ("id", 64)]);
+    /// assert_eq!(Some(2), codec.field_index("id"));
+    /// assert_eq!(None, codec.field_index("cgid"));
+    /// ```
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.layout.iter().position(|(field_name, _)| field_name == name)
+    }
+
+    /// Returns the total number of bits in the layout.
+    pub fn total_num_bits(&self) -> u8 {
+        self.layout.iter().fold(0, |a, (_, num_bits)| a + num_bits)
+    }
+
+    /// Returns `values` encoded as a label with this layout.
+    ///
+    /// `values` are matched to fields in order. Fields without a value are
+    /// encoded as 0 and extra values are ignored. Only the least significant
+    /// `num_bits` of each value are kept.
+    pub fn encode(&self, values: &[u64]) -> String {
+        let mut encodable_data = self.encodable_data();
+        for (data, value) in encodable_data.iter_mut().zip(values) {
+            data.value = *value;
+        }
+
+        self.encoding_system.encode(&mut encodable_data)
+    }
+
+    /// Decodes `label` with this layout and returns the value of each field.
+    ///
+    /// Returns a [`DecodeError::Length`] if `label` isn't the right length for
+    /// the layout.
+    pub fn decode(&self, label: &[u8]) -> Result<Vec<u64>, DecodeError> {
+        let mut encodable_data = self.encodable_data();
+        self.encoding_system
+            .decode(&mut encodable_data, label, self.total_num_bits())?;
+
+        Ok(encodable_data.iter().map(|data| data.value).collect())
+    }
+
+    /// Returns an empty [`EncodableData`] for each field in the layout.
+    fn encodable_data(&self) -> Vec<EncodableData> {
+        self.layout
+            .iter()
+            .map(|(_, num_bits)| EncodableData {
+                value: 0,
+                num_bits: *num_bits,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LabelCodec;
+    use crate::client_routing_label::ClientRoutingLabel;
+    use crate::hash::{hash_cgid, hash_cgid_truncated};
+
+    #[test]
+    fn validate_three_field_round_trip() {
+        let codec = LabelCodec::new(vec![("region", 7), ("bucket", 20), ("shard", 3)]);
+
+        let encoded_label = codec.encode(&[101, 957415, 5]);
+        assert_eq!(6, encoded_label.len());
+
+        match codec.decode(encoded_label.as_bytes()) {
+            Ok(values) => assert_eq!(vec![101, 957415, 5], values),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+    }
+
+    #[test]
+    fn validate_missing_and_oversized_values() {
+        let codec = LabelCodec::new(vec![("region", 7), ("bucket", 20), ("shard", 3)]);
+
+        let encoded_label = codec.encode(&[u64::MAX]);
+
+        match codec.decode(encoded_label.as_bytes()) {
+            Ok(values) => assert_eq!(vec![127, 0, 0], values),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+    }
+
+    #[test]
+    fn validate_decode_wrong_length() {
+        let codec = LabelCodec::new(vec![("region", 7), ("bucket", 20), ("shard", 3)]);
+
+        match codec.decode(b"abcdefg") {
+            Ok(_values) => panic!("Didn't throw error when should have"),
+            Err(e) => assert_eq!("Passed 7 - expected 6 characters", e.to_string()),
+        };
+    }
+
+    #[test]
+    fn validate_matches_client_routing_label_layout() {
+        let codec = LabelCodec::new(vec![
+            ("sdk_version", 10),
+            ("is_ipv6", 1),
+            ("client_subnet", 64),
+            ("subnet_mask", 6),
+            ("cgid", 64),
+        ]);
+
+        let mut client_routing_label = ClientRoutingLabel::default();
+        assert_eq!(client_routing_label.get_total_num_bits(), codec.total_num_bits());
+
+        assert_eq!(
+            "abfku6xaaaaaaaamhmnjxo5hdzrje",
+            codec.encode(&[1, 0, 6148494311290830848, 24, 8517775255794402596])
+        );
+    }
+
+    #[test]
+    fn validate_40_bit_cgid_round_trip() {
+        let codec = LabelCodec::client_routing_label().with_cgid_bits(40);
+        assert_eq!(40, codec.layout()[4].1);
+
+        let cgid = hash_cgid_truncated("f3663718-7699-4e6e-b482-daa2f690cf64", 40);
+        assert_eq!(hash_cgid("f3663718-7699-4e6e-b482-daa2f690cf64") & 0xff_ffff_ffff, cgid);
+
+        let values = [1, 0, 6148494311290830848, 24, cgid];
+        let encoded_label = codec.encode(&values);
+        // 121 bits instead of 145, so 5 chars shorter
+        assert_eq!(25, encoded_label.len());
+
+        match codec.decode(encoded_label.as_bytes()) {
+            Ok(decoded_values) => assert_eq!(values.to_vec(), decoded_values),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        // the untruncated hash encodes to the same label
+        assert_eq!(
+            encoded_label,
+            codec.encode(&[
+                1,
+                0,
+                6148494311290830848,
+                24,
+                hash_cgid("f3663718-7699-4e6e-b482-daa2f690cf64")
+            ])
+        );
+    }
+
+    #[test]
+    fn validate_client_routing_label_codec_matches_default_layout() {
+        let codec = LabelCodec::client_routing_label();
+
+        assert_eq!(
+            "abfku6xaaaaaaaamhmnjxo5hdzrje",
+            codec.encode(&[1, 0, 6148494311290830848, 24, 8517775255794402596])
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn validate_cgid_bits_too_large_panics() {
+        LabelCodec::client_routing_label().with_cgid_bits(65);
+    }
+
+    #[test]
+    #[should_panic]
+    fn validate_field_too_large_panics() {
+        LabelCodec::new(vec![("too_big", 65)]);
+    }
+}
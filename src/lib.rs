@@ -6,16 +6,52 @@
 //! label for verification purposes.
 
 mod bitwise;
+pub mod checksum;
 pub mod client_routing_label;
+pub mod decoder;
 pub mod encode_decode;
 pub mod errors;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod hash;
 pub mod ip;
+pub mod label_codec;
+#[cfg(feature = "tracing")]
+mod trace;
 
-use client_routing_label::{ClientRoutingLabel, DecodedClientRoutingLabel};
-use errors::DecodeLengthError;
-use hash::hash_cgid;
-use ip::parse_client_ip;
+use std::cell::RefCell;
+use std::ffi::OsStr;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use bitwise::get_mask;
+use client_routing_label::{
+    ClientRoutingLabel, DecodedClientRoutingLabel, CLIENT_ROUTING_LABEL_VERSION, ENCODED_LABEL_LEN,
+};
+use decoder::{check_label_len, first_label, first_label_sep, LabelDecoder};
+use encode_decode::{is_base32_char, Base32Alphabet};
+use errors::DecodeError;
+use hash::{hash_cgid, hash_cgid_lenprefixed, hash_cgid_salted};
+use ip::{parse_client_ip, ClientSubnetEncodingData};
+use label_codec::{LabelCodec, TIMESTAMP_NUM_BITS};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Generation markers that can prefix a client routing label, each with the
+/// layout used to decode the rest of the label. Markers are chars outside the
+/// Base32 alphabet so they can't be mistaken for the start of a label.
+const LABEL_GENERATIONS: [(u8, fn() -> ClientRoutingLabel); 1] =
+    [(b'1', ClientRoutingLabel::default)];
+
+thread_local! {
+    /// Scratch label and buffer reused by [`encode_request_data`] on each thread.
+    static ENCODE_SCRATCH: RefCell<(ClientRoutingLabel, String)> = {
+        let label = ClientRoutingLabel::default();
+        let encoded_len = label.encoding_system.encoded_len(&label.encodable_data);
+
+        RefCell::new((label, String::with_capacity(encoded_len)))
+    };
+}
 
 /// Returns domain with client routing key prepended as a subdomain.
 ///
@@ -47,29 +83,707 @@ use ip::parse_client_ip;
 /// assert_eq!("abacaqdaaaaaaaamaaaaaaaaaaaaa.example.com", encoded_label);
 /// ```
 pub fn encode_request_data(client_ip: &str, content_group_id: &str, fqdn: &str) -> String {
-    let client_subnet_encoding_data = parse_client_ip(client_ip);
+    with_client_routing_label(parse_client_ip(client_ip), hash_cgid(content_group_id), |client_routing_label| {
+        prepend_label(client_routing_label, fqdn)
+    })
+}
+
+/// Returns `client_routing_label` prepended as a subdomain to `fqdn`.
+fn prepend_label(client_routing_label: &str, fqdn: &str) -> String {
+    let mut encoded_label = String::with_capacity(client_routing_label.len() + 1 + fqdn.len());
+    encoded_label.push_str(client_routing_label);
+    encoded_label.push('.');
+    encoded_label.push_str(fqdn);
 
+    encoded_label
+}
+
+/// Returns domain with an uppercase client routing key prepended as a
+/// subdomain.
+///
+/// Behaves the same as [`encode_request_data`] except the label is encoded
+/// with the uppercase RFC 4648 Base32 alphabet, for interop with systems that
+/// expect canonical uppercase Base32. DNS names are case-insensitive and
+/// decoding accepts either case, so the label decodes to the same values.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{decode_request_data, encode_request_data_uppercase};
+///
+/// let encoded_label = encode_request_data_uppercase("1.2.3.4", "mv-456", "example.com");
+/// assert_eq!("ABACAQDAAAAAAAAMNJG3OUBCYVRGM.example.com", encoded_label);
+///
+/// let decoded_label = decode_request_data(&encoded_label).unwrap();
+/// assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+/// ```
+pub fn encode_request_data_uppercase(client_ip: &str, content_group_id: &str, fqdn: &str) -> String {
     let mut label = ClientRoutingLabel::default();
+    label.encoding_system.alphabet = Base32Alphabet::Uppercase;
+
+    label.set_data(parse_client_ip(client_ip), hash_cgid(content_group_id));
+
+    prepend_label(&label.encode(), fqdn)
+}
+
+/// Returns an iterator lazily encoding each `(client_ip, content_group_id,
+/// fqdn)` of `inputs`.
+///
+/// Each item is the same as calling [`encode_request_data`] on that input, and
+/// is only encoded when the iterator is advanced, so it can be combined with
+/// adapters like `filter` and `take` without collecting every label first.
+/// The iterator owns a [`ClientRoutingLabel`] and label buffer that it reuses
+/// for every input, so only the returned domains are allocated.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::encode_iter;
+///
+/// let inputs = [
+///     ("1.2.3.4", "mv-456", "example.com"),
+///     ("1.2.3.4", "", "example.com"),
+/// ];
+///
+/// let encoded_labels: Vec<String> = encode_iter(inputs.into_iter()).take(1).collect();
+/// assert_eq!(vec!["abacaqdaaaaaaaamnjg3oubcyvrgm.example.com"], encoded_labels);
+/// ```
+pub fn encode_iter<'a, I: Iterator<Item = (&'a str, &'a str, &'a str)> + 'a>(
+    inputs: I,
+) -> impl Iterator<Item = String> + 'a {
+    let label = ClientRoutingLabel::default();
+    let encoded_len = label.encoding_system.encoded_len(&label.encodable_data);
+
+    EncodeIter {
+        inputs,
+        label,
+        client_routing_label: String::with_capacity(encoded_len),
+    }
+}
+
+/// Iterator returned by [`encode_iter`].
+///
+/// Consist of 3 properties: `inputs`, the iterator being encoded, and `label`
+/// and `client_routing_label`, the layout and buffer reused for every input.
+struct EncodeIter<I> {
+    inputs: I,
+    label: ClientRoutingLabel,
+    client_routing_label: String,
+}
+
+impl<'a, I: Iterator<Item = (&'a str, &'a str, &'a str)>> Iterator for EncodeIter<I> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let (client_ip, content_group_id, fqdn) = self.inputs.next()?;
+        encode_into(
+            &mut self.label,
+            &mut self.client_routing_label,
+            parse_client_ip(client_ip),
+            hash_cgid(content_group_id),
+        );
+
+        Some(prepend_label(&self.client_routing_label, fqdn))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inputs.size_hint()
+    }
+}
+
+/// Returns `fqdn` with a client routing key prepended for every combination of
+/// `subnets` and `cgids`.
+///
+/// Each label is the same as calling [`encode_request_data`] with that client
+/// ip and cgid. The output is subnet-major: every cgid for `subnets[0]`, then
+/// every cgid for `subnets[1]`, and so on, so the label for `subnets[i]` and
+/// `cgids[j]` is at index `i * cgids.len() + j`. Each cgid is hashed and each
+/// subnet parsed only once, and encoding reuses the same scratch label and
+/// buffer for every label.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::encode_matrix;
+///
+/// let encoded_labels = encode_matrix(&["1.2.3.4", "1.2.a"], &["mv-456", ""], "example.com");
+/// assert_eq!(
+///     vec![
+///         "abacaqdaaaaaaaamnjg3oubcyvrgm.example.com",
+///         "abacaqdaaaaaaaamaaaaaaaaaaaaa.example.com",
+///         "abaaaaaaaaaaaaaanjg3oubcyvrgm.example.com",
+///         "abaaaaaaaaaaaaaaaaaaaaaaaaaaa.example.com",
+///     ],
+///     encoded_labels
+/// );
+/// ```
+pub fn encode_matrix(subnets: &[&str], cgids: &[&str], fqdn: &str) -> Vec<String> {
+    encode_matrix_with_hasher(subnets, cgids, fqdn, hash_cgid)
+}
+
+/// Same as [`encode_matrix`] but hashes cgids with `hasher`.
+fn encode_matrix_with_hasher(
+    subnets: &[&str],
+    cgids: &[&str],
+    fqdn: &str,
+    hasher: impl FnMut(&str) -> u64,
+) -> Vec<String> {
+    let hashed_cgids: Vec<u64> = cgids.iter().copied().map(hasher).collect();
+
+    let mut encoded_labels = Vec::with_capacity(subnets.len() * cgids.len());
+    for client_ip in subnets {
+        let client_subnet_encoding_data = parse_client_ip(client_ip);
+        for hashed_cgid in hashed_cgids.iter() {
+            encoded_labels.push(with_client_routing_label(
+                client_subnet_encoding_data,
+                *hashed_cgid,
+                |client_routing_label| prepend_label(client_routing_label, fqdn),
+            ));
+        }
+    }
 
+    encoded_labels
+}
+
+/// Writes domain with client routing key prepended as a subdomain to `writer`.
+///
+/// Produces the same bytes as [`encode_request_data`] but writes them straight
+/// to `writer` instead of allocating a [`String`] per label. Returns any error
+/// from `writer`. See [`encode_request_data_write_bytes`] for an `fqdn` that
+/// isn't a `&str`.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::encode_request_data_write;
+///
+/// let mut encoded_labels: Vec<u8> = Vec::new();
+/// encode_request_data_write(&mut encoded_labels, "1.2.3.4", "mv-456", "example.com").unwrap();
+/// encode_request_data_write(&mut encoded_labels, "1.2.3.4", "", "example.com").unwrap();
+///
+/// assert_eq!(
+///     b"abacaqdaaaaaaaamnjg3oubcyvrgm.example.comabacaqdaaaaaaaamaaaaaaaaaaaaa.example.com".to_vec(),
+///     encoded_labels
+/// );
+/// ```
+pub fn encode_request_data_write<W: io::Write>(
+    writer: &mut W,
+    client_ip: &str,
+    content_group_id: &str,
+    fqdn: &str,
+) -> io::Result<()> {
+    encode_request_data_write_bytes(writer, client_ip, content_group_id, fqdn.as_bytes())
+}
+
+/// Writes domain with client routing key prepended as a subdomain to `writer`,
+/// for an `fqdn` given as bytes.
+///
+/// Behaves the same as [`encode_request_data_write`], but `fqdn` doesn't have
+/// to be valid UTF-8. Its bytes are written verbatim after the label and dot,
+/// so non-ASCII or invalid UTF-8 bytes are passed through rather than rejected
+/// or replaced. Validating a domain is left to the caller.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::encode_request_data_write_bytes;
+///
+/// let mut encoded_label: Vec<u8> = Vec::new();
+/// encode_request_data_write_bytes(&mut encoded_label, "1.2.3.4", "mv-456", b"ex\xffmple.com").unwrap();
+///
+/// assert_eq!(b"abacaqdaaaaaaaamnjg3oubcyvrgm.ex\xffmple.com".to_vec(), encoded_label);
+/// ```
+pub fn encode_request_data_write_bytes<W: io::Write>(
+    writer: &mut W,
+    client_ip: &str,
+    content_group_id: &str,
+    fqdn: &[u8],
+) -> io::Result<()> {
+    with_client_routing_label(parse_client_ip(client_ip), hash_cgid(content_group_id), |client_routing_label| {
+        writer.write_all(client_routing_label.as_bytes())?;
+        writer.write_all(b".")?;
+        writer.write_all(fqdn)
+    })
+}
+
+/// Returns the client routing label in DNS wire format.
+///
+/// Encodes the same label as [`encode_request_data`], but instead of joining
+/// it to a domain with a dot returns it as a single DNS wire format label: one
+/// length octet followed by the ASCII label.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::encode_request_data_wire;
+///
+/// let encoded_label = encode_request_data_wire("1.2.3.4", "mv-456");
+/// assert_eq!(29, encoded_label[0]);
+/// assert_eq!(b"abacaqdaaaaaaaamnjg3oubcyvrgm", &encoded_label[1..]);
+/// ```
+pub fn encode_request_data_wire(client_ip: &str, content_group_id: &str) -> Vec<u8> {
+    with_client_routing_label(parse_client_ip(client_ip), hash_cgid(content_group_id), |client_routing_label| {
+        let mut encoded_label = Vec::with_capacity(1 + client_routing_label.len());
+        // a client routing label is always shorter than a DNS label, so its
+        // length fits in the length octet
+        encoded_label.push(client_routing_label.len() as u8);
+        encoded_label.extend_from_slice(client_routing_label.as_bytes());
+
+        encoded_label
+    })
+}
+
+/// Returns domain with client routing key prepended as a subdomain, using an
+/// already computed cgid instead of hashing one.
+///
+/// Behaves the same as [`encode_request_data`] except `cgid_decimal` is parsed
+/// as the decimal string of a 64 bit number and encoded as is, without
+/// [`hash_cgid`]. A `cgid_decimal` that isn't a valid `u64` is encoded as 0,
+/// the same as an empty `content_group_id`.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{decode_request_data, encode_request_data, encode_request_data_raw_cgid};
+///
+/// let encoded_label = encode_request_data_raw_cgid("1.2.3.4", "8517775255794402596", "example.com");
+/// assert_eq!("abacaqdaaaaaaaamhmnjxo5hdzrje.example.com", encoded_label);
+///
+/// let decoded_label = decode_request_data(&encoded_label).unwrap();
+/// assert_eq!(8517775255794402596, decoded_label.cgid);
+///
+/// // invalid cgid_decimal
+/// let encoded_label = encode_request_data_raw_cgid("1.2.3.4", "mv-456", "example.com");
+/// assert_eq!(encode_request_data("1.2.3.4", "", "example.com"), encoded_label);
+/// ```
+pub fn encode_request_data_raw_cgid(client_ip: &str, cgid_decimal: &str, fqdn: &str) -> String {
+    let cgid = cgid_decimal.parse::<u64>().unwrap_or(0);
+
+    with_client_routing_label(parse_client_ip(client_ip), cgid, |client_routing_label| {
+        prepend_label(client_routing_label, fqdn)
+    })
+}
+
+/// Returns domain with client routing key prepended as a subdomain, with the
+/// cgid hashed with its length prefixed.
+///
+/// Behaves the same as [`encode_request_data`] except the cgid is hashed with
+/// [`hash_cgid_lenprefixed`]. The cgid field holds a different value than
+/// [`encode_request_data`] would encode, so verify the label with
+/// [`verify_label_lenprefixed`] rather than [`verify_label`].
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{encode_request_data, encode_request_data_lenprefixed, verify_label, verify_label_lenprefixed};
+///
+/// let encoded_label = encode_request_data_lenprefixed("1.2.3.4", "mv-456", "example.com");
+/// assert_ne!(encode_request_data("1.2.3.4", "mv-456", "example.com"), encoded_label);
+/// assert!(encoded_label.starts_with("abacaqdaaaaaaaam"));
+///
+/// assert!(verify_label_lenprefixed(&encoded_label, "1.2.3.4", "mv-456"));
+/// assert!(!verify_label(&encoded_label, "1.2.3.4", "mv-456"));
+/// ```
+pub fn encode_request_data_lenprefixed(client_ip: &str, content_group_id: &str, fqdn: &str) -> String {
+    let cgid = hash_cgid_lenprefixed(content_group_id);
+
+    with_client_routing_label(parse_client_ip(client_ip), cgid, |client_routing_label| {
+        prepend_label(client_routing_label, fqdn)
+    })
+}
+
+/// Returns domain with a client routing key that omits the sdk version
+/// prepended as a subdomain.
+///
+/// For single version deployments where the version is implicit. Drops the
+/// leading 10 version bits using the layout of
+/// [`ClientRoutingLabel::versionless`], so the label is 135 bits, or 27 chars
+/// instead of 29. Otherwise behaves the same as [`encode_request_data`].
+/// Decode it with [`decode_request_data_versionless`].
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{encode_request_data, encode_request_data_versionless};
+///
+/// let encoded_label = encode_request_data_versionless("1.2.3.4", "mv-456", "example.com");
+/// assert_eq!("acaqdaaaaaaaamnjg3oubcyvrgm.example.com", encoded_label);
+/// ```
+pub fn encode_request_data_versionless(client_ip: &str, content_group_id: &str, fqdn: &str) -> String {
+    let mut label = ClientRoutingLabel::versionless(CLIENT_ROUTING_LABEL_VERSION);
+    label.set_data(parse_client_ip(client_ip), hash_cgid(content_group_id));
+
+    prepend_label(&label.encode(), fqdn)
+}
+
+/// Returns domain with a client routing key carrying a raw cgid token
+/// prepended as a subdomain.
+///
+/// Behaves the same as [`encode_request_data`] except `token` is encoded as is
+/// in a `token_bits` wide cgid field, the layout of
+/// [`ClientRoutingLabel::token`], instead of hashing a cgid into 64 bits. Decode
+/// it with [`decode_request_data_token`] and the same `token_bits`.
+///
+/// # Panics
+///
+/// Panics if `token_bits` is more than 64 or `token` doesn't fit in
+/// `token_bits`.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{decode_request_data_token, encode_request_data_token};
+///
+/// let encoded_label = encode_request_data_token("1.2.3.4", 1000, 12, "example.com");
+/// assert_eq!("abacaqdaaaaaaaamd5a.example.com", encoded_label);
+///
+/// let decoded_label = decode_request_data_token(&encoded_label, 12).unwrap();
+/// assert_eq!(1000, decoded_label.cgid);
+/// ```
+pub fn encode_request_data_token(client_ip: &str, token: u64, token_bits: u8, fqdn: &str) -> String {
+    let mut label = ClientRoutingLabel::token(token_bits);
+    label.set_data(parse_client_ip(client_ip), 0);
+    label.set_cgid(token);
+
+    prepend_label(&label.encode(), fqdn)
+}
+
+/// Returns domain with a timestamped client routing key prepended as a
+/// subdomain.
+///
+/// Encodes the same fields as [`encode_request_data`] followed by a
+/// [`TIMESTAMP_NUM_BITS`] wide coarse timestamp in the layout of
+/// [`LabelCodec::timestamped`], so the label is 33 chars. Decode it with [`decode_request_data_timestamped`].
+///
+/// Only the low 20 bits of `epoch_minutes` are kept, so the timestamp wraps
+/// every 2^20 minutes, about 728 days. Compute a label's age with wrapping
+/// subtraction masked to 20 bits, which is correct across a wrap as long as
+/// the label is less than 728 days old.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{decode_request_data_timestamped, encode_request_data_timestamped};
+///
+/// let encoded_label = encode_request_data_timestamped("1.2.3.4", "mv-456", "example.com", 28_000_000);
+/// let (decoded_label, timestamp) = decode_request_data_timestamped(&encoded_label).unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+/// assert_eq!(28_000_000 & 0xfffff, timestamp);
+///
+/// // age in minutes, even if the counter wrapped since encoding
+/// let now: u32 = 28_000_015;
+/// assert_eq!(15, now.wrapping_sub(timestamp) & 0xfffff);
+/// ```
+pub fn encode_request_data_timestamped(
+    client_ip: &str,
+    content_group_id: &str,
+    fqdn: &str,
+    epoch_minutes: u32,
+) -> String {
+    let client_subnet_encoding_data = parse_client_ip(client_ip);
+    let mut label = ClientRoutingLabel::default();
     label.set_data(client_subnet_encoding_data, hash_cgid(content_group_id));
 
-    let client_routing_label = label.encode();
-    format!("{}.{}", client_routing_label, fqdn)
+    let [version, is_ipv6, client_subnet, subnet_mask, cgid] = label.field_values();
+    let timestamp = epoch_minutes as u64 & get_mask(TIMESTAMP_NUM_BITS);
+    let client_routing_label = LabelCodec::timestamped()
+        .encode(&[version, is_ipv6, client_subnet, subnet_mask, cgid, timestamp]);
+
+    prepend_label(&client_routing_label, fqdn)
+}
+
+/// Returns domain with client routing key prepended as a subdomain, with the
+/// cgid hashed together with `salt`.
+///
+/// Behaves the same as [`encode_request_data`] except the cgid is hashed with
+/// [`hash_cgid_salted`], so the cgid of a label can't be found by hashing a
+/// list of guessed cgids without knowing `salt`. Use [`verify_label_salted`]
+/// with the same `salt` to verify the label. Only the cgid is salted, the
+/// client subnet is still encoded as is and can be read by anyone.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{encode_request_data, encode_request_data_salted, verify_label_salted};
+///
+/// let encoded_label = encode_request_data_salted("1.2.3.4", "mv-456", "example.com", b"salt");
+/// assert_ne!(encode_request_data("1.2.3.4", "mv-456", "example.com"), encoded_label);
+///
+/// // the client subnet isn't salted
+/// assert!(encoded_label.starts_with("abacaqdaaaaaaaam"));
+///
+/// assert!(verify_label_salted(&encoded_label, "1.2.3.4", "mv-456", b"salt"));
+/// assert!(!verify_label_salted(&encoded_label, "1.2.3.4", "mv-456", b"pepper"));
+/// ```
+pub fn encode_request_data_salted(
+    client_ip: &str,
+    content_group_id: &str,
+    fqdn: &str,
+    salt: &[u8],
+) -> String {
+    let cgid = hash_cgid_salted(content_group_id, salt);
+
+    with_client_routing_label(parse_client_ip(client_ip), cgid, |client_routing_label| {
+        prepend_label(client_routing_label, fqdn)
+    })
+}
+
+/// Returns domain with client routing key prepended as a subdomain for an
+/// already parsed `client_ip`.
+///
+/// Behaves the same as [`encode_request_data`] for a valid ip, using
+/// [`parse_client_ip_addr`](ip::parse_client_ip_addr) instead of parsing a
+/// string.
+///
+/// # Examples:
+/// ```
+/// use std::net::{IpAddr, Ipv4Addr};
+/// use amazon_cloudfront_client_routing_lib::encode_request_data_from_ip;
+///
+/// let encoded_label = encode_request_data_from_ip(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), "mv-456", "example.com");
+/// assert_eq!("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", encoded_label);
+/// ```
+pub fn encode_request_data_from_ip(client_ip: IpAddr, content_group_id: &str, fqdn: &str) -> String {
+    encode_request_data_from_subnet_data(ip::parse_client_ip_addr(client_ip), content_group_id, fqdn)
+}
+
+/// Returns domain with client routing key prepended as a subdomain for the
+/// Ipv4 address with `octets`.
+///
+/// Skips parsing a client ip string entirely, for callers that already have
+/// the address as octets. Otherwise behaves the same as
+/// [`encode_request_data`].
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{encode_request_data, encode_request_data_v4_octets};
+///
+/// let encoded_label = encode_request_data_v4_octets([1, 2, 3, 4], "mv-456", "example.com");
+/// assert_eq!("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", encoded_label);
+/// assert_eq!(encode_request_data("1.2.3.4", "mv-456", "example.com"), encoded_label);
+/// ```
+pub fn encode_request_data_v4_octets(octets: [u8; 4], content_group_id: &str, fqdn: &str) -> String {
+    encode_request_data_from_ip(IpAddr::V4(Ipv4Addr::from(octets)), content_group_id, fqdn)
+}
+
+/// Returns domain with client routing key prepended as a subdomain for the ip
+/// of `client_addr`.
+///
+/// The port is ignored, so web frameworks can pass the peer [`SocketAddr`]
+/// straight through. Otherwise behaves the same as
+/// [`encode_request_data_from_ip`].
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::encode_request_data_from_socket;
+///
+/// let client_addr = "1.2.3.4:443".parse().unwrap();
+/// let encoded_label = encode_request_data_from_socket(client_addr, "mv-456", "example.com");
+/// assert_eq!("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", encoded_label);
+/// ```
+pub fn encode_request_data_from_socket(
+    client_addr: SocketAddr,
+    content_group_id: &str,
+    fqdn: &str,
+) -> String {
+    encode_request_data_from_ip(client_addr.ip(), content_group_id, fqdn)
+}
+
+/// Returns domain with client routing key prepended as a subdomain, using
+/// already parsed `client_subnet_encoding_data` instead of a client ip.
+///
+/// Behaves the same as [`encode_request_data`] except
+/// `client_subnet_encoding_data` is encoded as is instead of being parsed from
+/// a client ip string, for callers that build it from something other than an
+/// ip.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::encode_request_data_from_subnet_data;
+/// use amazon_cloudfront_client_routing_lib::ip::ClientSubnetEncodingData;
+///
+/// let client_subnet_encoding_data = ClientSubnetEncodingData {
+///     client_subnet: u64::from_be_bytes([1, 2, 3, 0, 0, 0, 0, 0]),
+///     subnet_mask: 24,
+///     is_ipv6: 0,
+/// };
+///
+/// let encoded_label = encode_request_data_from_subnet_data(client_subnet_encoding_data, "mv-456", "example.com");
+/// assert_eq!("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", encoded_label);
+/// ```
+pub fn encode_request_data_from_subnet_data(
+    client_subnet_encoding_data: ClientSubnetEncodingData,
+    content_group_id: &str,
+    fqdn: &str,
+) -> String {
+    with_client_routing_label(
+        client_subnet_encoding_data,
+        hash_cgid(content_group_id),
+        |client_routing_label| prepend_label(client_routing_label, fqdn),
+    )
+}
+
+/// Encodes a client routing label into the thread-local scratch buffer and
+/// passes it to `f`.
+///
+/// The label is copied to the stack and the scratch buffer released before
+/// `f` runs, so `f` can encode again on the same thread, e.g. from a writer
+/// or tracing subscriber.
+fn with_client_routing_label<R>(
+    client_subnet_encoding_data: ClientSubnetEncodingData,
+    cgid: u64,
+    f: impl FnOnce(&str) -> R,
+) -> R {
+    let mut buffer = [0_u8; ENCODED_LABEL_LEN];
+    ENCODE_SCRATCH.with(|scratch| {
+        let (label, client_routing_label) = &mut *scratch.borrow_mut();
+        encode_into(label, client_routing_label, client_subnet_encoding_data, cgid);
+
+        buffer.copy_from_slice(client_routing_label.as_bytes());
+    });
+
+    // unwrap is ok here because the label is Base32, so always ascii
+    f(std::str::from_utf8(&buffer).unwrap())
+}
+
+/// Encodes a client routing label into `client_routing_label`, reusing
+/// `label` and the buffer's capacity.
+fn encode_into(
+    label: &mut ClientRoutingLabel,
+    client_routing_label: &mut String,
+    client_subnet_encoding_data: ClientSubnetEncodingData,
+    cgid: u64,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::trace_span!(
+        "encode",
+        kind = trace::encode_kind(&client_subnet_encoding_data)
+    )
+    .entered();
+
+    // encoding consumes `encodable_data`, so start from a fresh layout
+    label.reset();
+    client_routing_label.clear();
+
+    label.set_data(client_subnet_encoding_data, cgid);
+    label.encode_to(client_routing_label);
+}
+
+/// Returns domain with a client routing key using a 32 bit CRC-32 cgid
+/// prepended as a subdomain.
+///
+/// Behaves the same as [`encode_request_data`] except the cgid is hashed with
+/// [`hash_cgid_crc32`](hash::hash_cgid_crc32) and encoded in the 32 bit cgid
+/// field of [`ClientRoutingLabel::crc32`], so the label is 23 chars instead of
+/// 29. Decode it with a [`LabelDecoder`] for that layout. Requires the `crc`
+/// feature.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+/// use amazon_cloudfront_client_routing_lib::decoder::LabelDecoder;
+/// use amazon_cloudfront_client_routing_lib::encode_request_data_crc32;
+///
+/// let encoded_label = encode_request_data_crc32("1.2.3.4", "mv-456", "example.com");
+/// assert_eq!("abacaqdaaaaaaaamg7c6fza.example.com", encoded_label);
+///
+/// let decoded_label = LabelDecoder::new(ClientRoutingLabel::crc32()).decode(&encoded_label).unwrap();
+/// assert_eq!(1871431112, decoded_label.cgid);
+/// ```
+#[cfg(feature = "crc")]
+pub fn encode_request_data_crc32(client_ip: &str, content_group_id: &str, fqdn: &str) -> String {
+    let mut label = ClientRoutingLabel::crc32();
+
+    label.set_data(
+        parse_client_ip(client_ip),
+        hash::hash_cgid_crc32(content_group_id) as u64,
+    );
+
+    prepend_label(&label.encode(), fqdn)
+}
+
+/// Returns domain with a client routing key that carries no client subnet
+/// prepended as a subdomain.
+///
+/// Intended for when the client IP is genuinely unknown rather than invalid.
+/// `is_ipv6`, `client_subnet`, and `subnet_mask` are all encoded as 0, which
+/// intentionally overlaps with how [`encode_request_data`] encodes an invalid
+/// `client_ip`. On decode both cases are reported by
+/// [`DecodedClientRoutingLabel::has_subnet`] returning false.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{decode_request_data, encode_request_data, encode_request_data_no_subnet};
+///
+/// let encoded_label = encode_request_data_no_subnet("mv-456", "example.com");
+/// assert_eq!("abaaaaaaaaaaaaaanjg3oubcyvrgm.example.com", encoded_label);
+///
+/// // same as an invalid client_ip
+/// assert_eq!(encode_request_data("1.2.a", "mv-456", "example.com"), encoded_label);
+///
+/// let decoded_label = decode_request_data(&encoded_label).unwrap();
+/// assert!(!decoded_label.has_subnet());
+/// ```
+pub fn encode_request_data_no_subnet(content_group_id: &str, fqdn: &str) -> String {
+    let client_subnet_encoding_data = ClientSubnetEncodingData {
+        client_subnet: 0,
+        subnet_mask: 0,
+        is_ipv6: 0,
+    };
+
+    with_client_routing_label(
+        client_subnet_encoding_data,
+        hash_cgid(content_group_id),
+        |client_routing_label| prepend_label(client_routing_label, fqdn),
+    )
+}
+
+/// Returns a zone file line aliasing the domain [`encode_request_data`]
+/// produces to `target` with a CNAME record.
+///
+/// The line is `<owner> <ttl> IN CNAME <target>`, with a trailing dot added
+/// to the owner and `target` if they don't already end in one, so both are
+/// read as absolute names rather than relative to the zone's `$ORIGIN`.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::encode_zone_record;
+///
+/// let zone_record = encode_zone_record("1.2.3.4", "mv-456", "example.com", 300, "origin.example.com");
+/// assert_eq!(
+///     "abacaqdaaaaaaaamnjg3oubcyvrgm.example.com. 300 IN CNAME origin.example.com.",
+///     zone_record
+/// );
+/// ```
+pub fn encode_zone_record(
+    client_ip: &str,
+    content_group_id: &str,
+    fqdn: &str,
+    ttl: u32,
+    target: &str,
+) -> String {
+    let owner = encode_request_data(client_ip, content_group_id, fqdn);
+
+    format!(
+        "{}{} {} IN CNAME {}{}",
+        owner,
+        if owner.ends_with('.') { "" } else { "." },
+        ttl,
+        target,
+        if target.ends_with('.') { "" } else { "." },
+    )
 }
 
 /// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
-/// [`DecodeLengthError`].
+/// [`DecodeError`].
 ///
 /// The decode function takes in a &str param: `domain`. This domain can be a FQDN
 /// or just the dns label generated by the [`encode_request_data`] function. It
 /// decodes the string and formats it into a [`DecodedClientRoutingLabel`]. If the
 /// client routing label is not the first DNS label or is not included in `domain`
-/// a [`DecodeLengthError`] will be returned.
+/// a [`DecodeError::Length`] will be returned, or a
+/// [`DecodeError::LabelTooLong`] if the first DNS label is longer than a DNS
+/// label can be. A trailing dot (the FQDN root) is accepted, but a leading dot
+/// leaves an empty first label and returns [`DecodeError::Empty`]. Only the
+/// first DNS label is read, so empty labels after it, e.g. from a double dot,
+/// are ignored. This is [`decode_request_data_sep`] with a `sep` of `'.'`.
 ///
 /// # Examples:
 /// ```
 /// use amazon_cloudfront_client_routing_lib::decode_request_data;
 ///
+/// // double dot after the client routing label is ignored
+/// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm..example.com");
+/// assert_eq!(24, decoded_label.unwrap().subnet_mask);
+///
 /// // valid client routing label
 /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm");
 /// match decoded_label {
@@ -109,7 +823,7 @@ pub fn encode_request_data(client_ip: &str, content_group_id: &str, fqdn: &str)
 /// // fqdn without valid client routing label
 /// let decoded_label = decode_request_data("example.com");
 /// match decoded_label {
-///     Ok(data) => panic!("Should have thrown a DecodeLengthError"),
+///     Ok(data) => panic!("Should have thrown a DecodeError"),
 ///     Err(e) => {
 ///         assert_eq!(format!("{}", e), "Passed 7 - expected 29 characters");
 ///     }
@@ -118,7 +832,7 @@ pub fn encode_request_data(client_ip: &str, content_group_id: &str, fqdn: &str)
 /// // client routing label needs to be the first DNS label
 /// let decoded_label = decode_request_data("vod1.abacaqdaaaaaaaamnjg3oubcyvrgm.example.com");
 /// match decoded_label {
-///     Ok(data) => panic!("Should have thrown a DecodeLengthError"),
+///     Ok(data) => panic!("Should have thrown a DecodeError"),
 ///     Err(e) => {
 ///         assert_eq!(format!("{}", e), "Passed 4 - expected 29 characters");
 ///     }
@@ -127,20 +841,892 @@ pub fn encode_request_data(client_ip: &str, content_group_id: &str, fqdn: &str)
 /// // invalid
 /// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcy"); // invalid length
 /// match decoded_label {
-///     Ok(data) => panic!("Should have thrown a DecodeLengthError"),
+///     Ok(data) => panic!("Should have thrown a DecodeError"),
 ///     Err(e) => {
 ///         assert_eq!(format!("{}", e), "Passed 25 - expected 29 characters");
 ///     }
 /// };
+///
+/// // fqdn with trailing dot
+/// let decoded_label = decode_request_data("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com.");
+/// assert_eq!(24, decoded_label.unwrap().subnet_mask);
+///
+/// // leading dot
+/// let decoded_label = decode_request_data(".abacaqdaaaaaaaamnjg3oubcyvrgm.example.com");
+/// match decoded_label {
+///     Ok(data) => panic!("Should have thrown a DecodeError::Empty"),
+///     Err(e) => {
+///         assert_eq!(format!("{}", e), "Passed an empty label");
+///     }
+/// };
 /// ```
 pub fn decode_request_data(
     domain: &str,
-) -> Result<DecodedClientRoutingLabel, DecodeLengthError> {
-    let client_routing_label = domain.split(".").next().unwrap_or_default();
-    let client_routing_label: &mut [u8] = &mut Box::from(client_routing_label.as_bytes());
-    client_routing_label.make_ascii_lowercase();
+) -> Result<DecodedClientRoutingLabel, DecodeError> {
+    decode_request_data_sep(domain, '.')
+}
+
+/// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
+/// [`DecodeError`] for a label encoded without its sdk version.
+///
+/// Some labels are produced with the leading 10 version bits stripped and the
+/// version negotiated out-of-band. The supplied `version` picks the field layout
+/// via [`ClientRoutingLabel::versionless`] and is returned as
+/// `client_sdk_version`. Otherwise behaves the same as [`decode_request_data`].
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_with_version;
+///
+/// let decoded_label = decode_request_data_with_version("acaqdaaaaaaaamnjg3oubcyvrgm.example.com", 1).unwrap();
+/// assert_eq!(1, decoded_label.client_sdk_version);
+/// assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+/// assert_eq!(24, decoded_label.subnet_mask);
+///
+/// // labels with an embedded version are too long
+/// let decoded_label = decode_request_data_with_version("abacaqdaaaaaaaamnjg3oubcyvrgm", 1);
+/// assert_eq!("Passed 29 - expected 27 characters", decoded_label.unwrap_err().to_string());
+/// ```
+pub fn decode_request_data_with_version(
+    domain: &str,
+    version: u16,
+) -> Result<DecodedClientRoutingLabel, DecodeError> {
+    let mut decoder = LabelDecoder::new(ClientRoutingLabel::versionless(version));
+
+    let mut decoded_label = decoder.decode(domain)?;
+    decoded_label.client_sdk_version = version;
+
+    Ok(decoded_label)
+}
+
+/// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
+/// [`DecodeError`] for a label encoded with
+/// [`encode_request_data_versionless`].
+///
+/// Assumes the label is the current sdk version, so it's
+/// [`decode_request_data_with_version`] with [`CLIENT_ROUTING_LABEL_VERSION`].
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_versionless;
+///
+/// let decoded_label = decode_request_data_versionless("acaqdaaaaaaaamnjg3oubcyvrgm.example.com").unwrap();
+/// assert_eq!(1, decoded_label.client_sdk_version);
+/// assert_eq!(24, decoded_label.subnet_mask);
+/// ```
+pub fn decode_request_data_versionless(
+    domain: &str,
+) -> Result<DecodedClientRoutingLabel, DecodeError> {
+    decode_request_data_with_version(domain, CLIENT_ROUTING_LABEL_VERSION)
+}
+
+/// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
+/// [`DecodeError`] for a label encoded with [`encode_request_data_token`].
+///
+/// `token_bits` must be the width the label was encoded with. The token is
+/// returned as `cgid`. Otherwise behaves the same as [`decode_request_data`].
+///
+/// # Panics
+///
+/// Panics if `token_bits` is more than 64.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_token;
+///
+/// let decoded_label = decode_request_data_token("abacaqdaaaaaaaamd5a.example.com", 12).unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+/// assert_eq!(1000, decoded_label.cgid);
+/// ```
+pub fn decode_request_data_token(
+    domain: &str,
+    token_bits: u8,
+) -> Result<DecodedClientRoutingLabel, DecodeError> {
+    LabelDecoder::new(ClientRoutingLabel::token(token_bits)).decode(domain)
+}
+
+/// Returns a result containing either the [`DecodedClientRoutingLabel`] and
+/// timestamp of a label encoded with [`encode_request_data_timestamped`], or a
+/// [`DecodeError`].
+///
+/// The timestamp is the low 20 bits of the `epoch_minutes` the label was
+/// encoded with; see [`encode_request_data_timestamped`] for handling its
+/// wraparound. Returns a [`DecodeError::Length`] for labels without a
+/// timestamp. Otherwise behaves the same as [`decode_request_data`], including
+/// the [`DecodeError::Empty`] and [`DecodeError::LabelTooLong`] errors and, with
+/// the `tracing` feature, the `decode` trace span.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{decode_request_data_timestamped, encode_request_data_timestamped};
+///
+/// let encoded_label = encode_request_data_timestamped("1.2.3.4", "mv-456", "example.com", 1234);
+/// let (decoded_label, timestamp) = decode_request_data_timestamped(&encoded_label).unwrap();
+/// assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+/// assert_eq!(1234, timestamp);
+///
+/// let decoded_label = decode_request_data_timestamped("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com");
+/// assert_eq!("Passed 29 - expected 33 characters", decoded_label.unwrap_err().to_string());
+/// ```
+pub fn decode_request_data_timestamped(
+    domain: &str,
+) -> Result<(DecodedClientRoutingLabel, u32), DecodeError> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::trace_span!("decode", kind = tracing::field::Empty).entered();
+
+    let decoded_label = decode_timestamped_label(first_label(domain.as_bytes()));
+
+    #[cfg(feature = "tracing")]
+    trace::record_decode(&span, decoded_label.as_ref().map(|(decoded_label, _timestamp)| decoded_label));
+
+    decoded_label
+}
+
+/// Decodes `client_routing_label` for [`decode_request_data_timestamped`].
+fn decode_timestamped_label(
+    client_routing_label: &[u8],
+) -> Result<(DecodedClientRoutingLabel, u32), DecodeError> {
+    check_label_len(client_routing_label)?;
+    let values = LabelCodec::timestamped().decode(client_routing_label)?;
 
     let mut label = ClientRoutingLabel::default();
+    label.set_field_values([values[0], values[1], values[2], values[3], values[4]]);
+
+    Ok((label.get_decoded_client_routing_label(), values[5] as u32))
+}
+
+/// Returns a result containing either a JSON object of the decoded label or a
+/// [`DecodeError`].
+///
+/// Decodes `domain` the same as [`decode_request_data`] and serializes the
+/// [`DecodedClientRoutingLabel`] with `serde_json`. `client_subnet` is written
+/// as the human readable ip, or null if the label has no subnet. Requires the
+/// `serde` feature.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_json;
+///
+/// let json = decode_request_data_json("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com").unwrap();
+/// assert_eq!(
+///     r#"{"client_sdk_version":1,"is_ipv6":false,"client_subnet":"1.2.3.0","subnet_mask":24,"cgid":15319960192071419084}"#,
+///     json
+/// );
+/// ```
+#[cfg(feature = "serde")]
+pub fn decode_request_data_json(domain: &str) -> Result<String, DecodeError> {
+    let decoded_label = decode_request_data(domain)?;
+
+    // unwrap is ok here because serializing a decoded label never fails
+    Ok(serde_json::to_string(&decoded_label).unwrap())
+}
+
+/// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
+/// [`DecodeError`] for a label that may be prefixed with a generation marker.
+///
+/// If the first char of `domain` is a known generation marker it is stripped
+/// and the rest of the first DNS label is decoded with that generation's
+/// layout. Otherwise behaves the same as [`decode_request_data`]. The only
+/// marker today is `'1'`, for the current layout.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_gen;
+///
+/// let decoded_label = decode_request_data_gen("1abacaqdaaaaaaaamnjg3oubcyvrgm.example.com").unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+///
+/// // no marker
+/// let decoded_label = decode_request_data_gen("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com").unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+/// ```
+pub fn decode_request_data_gen(domain: &str) -> Result<DecodedClientRoutingLabel, DecodeError> {
+    let client_routing_label = normalize_first_label(domain).unwrap_or_default();
+
+    if let Some((marker, label)) = client_routing_label.as_bytes().split_first() {
+        if let Some((_, layout)) = LABEL_GENERATIONS.iter().find(|(m, _)| m == marker) {
+            return LabelDecoder::new(layout()).decode_label(label);
+        }
+    }
+
+    decode_request_data(domain)
+}
+
+/// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
+/// [`DecodeError`] for the first DNS label of `domain` that looks like a client
+/// routing label.
+///
+/// This is a recovery tool for misconfigured setups that don't put the client
+/// routing label first, not the spec behavior, so prefer
+/// [`decode_request_data`]. Tries each DNS label in order and returns the first
+/// one that is [`ENCODED_LABEL_LEN`] chars long and decodes to the current sdk
+/// version. If none do, returns the same error as [`decode_request_data`].
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_scan;
+///
+/// let decoded_label = decode_request_data_scan("vod1.abacaqdaaaaaaaamnjg3oubcyvrgm.example.com").unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+///
+/// let decoded_label = decode_request_data_scan("vod1.example.com");
+/// assert_eq!("Passed 4 - expected 29 characters", decoded_label.unwrap_err().to_string());
+/// ```
+pub fn decode_request_data_scan(domain: &str) -> Result<DecodedClientRoutingLabel, DecodeError> {
+    let mut decoder = LabelDecoder::default();
+
+    let mut labels = Some(domain);
+    while let Some(rest) = labels {
+        let (client_routing_label, rest) = split_first_label(rest);
+        labels = rest;
+        if client_routing_label.len() != ENCODED_LABEL_LEN {
+            continue;
+        }
+
+        if let Ok(decoded_label) = decoder.decode_label(client_routing_label.as_bytes()) {
+            if decoded_label.client_sdk_version == CLIENT_ROUTING_LABEL_VERSION {
+                return Ok(decoded_label);
+            }
+        }
+    }
+
+    decoder.decode(domain)
+}
+
+/// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
+/// [`DecodeError`] for a `domain` whose labels are separated by `sep`.
+///
+/// Behaves the same as [`decode_request_data`], which is this function with a
+/// `sep` of `'.'`, but finds the client routing label by splitting on `sep`.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_sep;
+///
+/// let decoded_label = decode_request_data_sep("abacaqdaaaaaaaamnjg3oubcyvrgm|vod1|example.com", '|').unwrap();
+/// assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+/// assert_eq!(24, decoded_label.subnet_mask);
+/// ```
+pub fn decode_request_data_sep(
+    domain: &str,
+    sep: char,
+) -> Result<DecodedClientRoutingLabel, DecodeError> {
+    LabelDecoder::default().decode_label(first_label_sep(domain.as_bytes(), sep))
+}
+
+/// Decodes `domain` into `out` in place, returning a [`DecodeError`] if it
+/// can't be decoded.
+///
+/// Behaves the same as [`decode_request_data`] but overwrites every field of a
+/// caller-provided [`DecodedClientRoutingLabel`] instead of returning a new one,
+/// so a single `out` can be reused across decodes. On error `out` is left
+/// unchanged.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::client_routing_label::DecodedClientRoutingLabel;
+/// use amazon_cloudfront_client_routing_lib::decode_into;
+///
+/// let mut decoded_label = DecodedClientRoutingLabel::default();
+///
+/// decode_into("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", &mut decoded_label).unwrap();
+/// assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+///
+/// assert!(decode_into("example.com", &mut decoded_label).is_err());
+/// assert_eq!([1, 2, 3, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+/// ```
+pub fn decode_into(domain: &str, out: &mut DecodedClientRoutingLabel) -> Result<(), DecodeError> {
+    *out = LabelDecoder::default().decode(domain)?;
+
+    Ok(())
+}
+
+/// Returns a result containing either the [`DecodedClientRoutingLabel`] of
+/// `domain` along with the first DNS label that produced it, or a
+/// [`DecodeError`].
+///
+/// Behaves the same as [`decode_request_data`]. The returned label is
+/// lowercased the same way it is before decoding, so it's the exact input the
+/// decode saw. Useful for audit logging.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_audited;
+///
+/// let (decoded_label, client_routing_label) =
+///     decode_request_data_audited("ABACAQDAAAAAAAAMNJG3OUBCYVRGM.example.com").unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+/// assert_eq!("abacaqdaaaaaaaamnjg3oubcyvrgm", client_routing_label);
+///
+/// assert!(decode_request_data_audited("example.com").is_err());
+/// ```
+pub fn decode_request_data_audited(
+    domain: &str,
+) -> Result<(DecodedClientRoutingLabel, String), DecodeError> {
+    let client_routing_label = normalize_first_label(domain).unwrap_or_default();
+    let decoded_label = LabelDecoder::default().decode_label(client_routing_label.as_bytes())?;
+
+    Ok((decoded_label, client_routing_label.to_ascii_lowercase()))
+}
+
+/// Returns a result containing either the [`DecodedClientRoutingLabel`] of
+/// `domain` along with the rest of the domain in Unicode form, or a
+/// [`DecodeError`].
+///
+/// The client routing label is always ASCII Base32 and decodes the same as
+/// with [`decode_request_data`], but the labels after it may be
+/// internationalized and arrive Punycode encoded with an `xn--` prefix. Those
+/// are converted to Unicode with the `idna` crate. Labels that aren't valid
+/// IDNA are converted as far as possible rather than failing the decode. The
+/// rest of the domain is empty if `domain` is only a label. Requires the
+/// `idna` feature.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_idna;
+///
+/// let (decoded_label, residual_domain) =
+///     decode_request_data_idna("abacaqdaaaaaaaamnjg3oubcyvrgm.xn--nxasmq6b.example.com").unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+/// assert_eq!("βόλοσ.example.com", residual_domain);
+/// ```
+#[cfg(feature = "idna")]
+pub fn decode_request_data_idna(
+    domain: &str,
+) -> Result<(DecodedClientRoutingLabel, String), DecodeError> {
+    let decoded_label = decode_request_data(domain)?;
+    let residual_domain = match split_first_label(domain) {
+        (_client_routing_label, Some(residual_domain)) => {
+            idna::domain_to_unicode(residual_domain).0
+        }
+        (_client_routing_label, None) => String::new(),
+    };
+
+    Ok((decoded_label, residual_domain))
+}
+
+/// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
+/// [`DecodeError`].
+///
+/// Behaves the same as [`decode_request_data`] but takes `domain` as an
+/// [`OsStr`], e.g. a directory or file name. Returns
+/// [`DecodeError::NonUtf8`] if `domain` isn't valid UTF-8.
+///
+/// # Examples:
+/// ```
+/// use std::ffi::OsStr;
+/// use std::path::Path;
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_os;
+///
+/// let decoded_label = decode_request_data_os(OsStr::new("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com"));
+/// assert_eq!(24, decoded_label.unwrap().subnet_mask);
+///
+/// let path = Path::new("/var/cache/abacaqdaaaaaaaamnjg3oubcyvrgm");
+/// let decoded_label = decode_request_data_os(path.file_name().unwrap());
+/// assert_eq!(24, decoded_label.unwrap().subnet_mask);
+/// ```
+pub fn decode_request_data_os(domain: &OsStr) -> Result<DecodedClientRoutingLabel, DecodeError> {
+    match domain.to_str() {
+        Some(domain) => decode_request_data(domain),
+        None => Err(DecodeError::NonUtf8),
+    }
+}
+
+/// Returns whether the client routing label in `domain` matches the label
+/// [`encode_request_data`] would produce for `expected_ip` and
+/// `expected_cgid`.
+///
+/// Decodes `domain` and compares its `client_subnet`, `subnet_mask`,
+/// `is_ipv6`, and `cgid` to the values encoded for the expected inputs. Useful
+/// for detecting a label that was spoofed or reused for a different client.
+/// Returns false if `domain` can't be decoded.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::verify_label;
+///
+/// assert!(verify_label("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", "1.2.3.4", "mv-456"));
+/// // same /24 subnet
+/// assert!(verify_label("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", "1.2.3.200", "mv-456"));
+///
+/// assert!(!verify_label("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", "1.2.4.4", "mv-456"));
+/// assert!(!verify_label("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", "1.2.3.4", "mv-457"));
+/// assert!(!verify_label("example.com", "1.2.3.4", "mv-456"));
+/// ```
+pub fn verify_label(domain: &str, expected_ip: &str, expected_cgid: &str) -> bool {
+    verify_label_hashed(domain, expected_ip, hash_cgid(expected_cgid))
+}
+
+/// Returns whether the client routing label in `domain` matches the label
+/// [`encode_request_data_salted`] would produce for `expected_ip`,
+/// `expected_cgid`, and `salt`.
+///
+/// Behaves the same as [`verify_label`] but hashes `expected_cgid` with
+/// [`hash_cgid_salted`]. Returns false if `salt` isn't the salt the label was
+/// encoded with.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{encode_request_data_salted, verify_label_salted};
+///
+/// let encoded_label = encode_request_data_salted("1.2.3.4", "mv-456", "example.com", b"salt");
+///
+/// assert!(verify_label_salted(&encoded_label, "1.2.3.4", "mv-456", b"salt"));
+/// assert!(!verify_label_salted(&encoded_label, "1.2.3.4", "mv-457", b"salt"));
+/// assert!(!verify_label_salted(&encoded_label, "1.2.3.4", "mv-456", b""));
+/// ```
+pub fn verify_label_salted(
+    domain: &str,
+    expected_ip: &str,
+    expected_cgid: &str,
+    salt: &[u8],
+) -> bool {
+    verify_label_hashed(domain, expected_ip, hash_cgid_salted(expected_cgid, salt))
+}
+
+/// Returns whether the client routing label in `domain` matches the label
+/// [`encode_request_data_lenprefixed`] would produce for `expected_ip` and
+/// `expected_cgid`.
+///
+/// Behaves the same as [`verify_label`] but hashes `expected_cgid` with
+/// [`hash_cgid_lenprefixed`].
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::{encode_request_data_lenprefixed, verify_label_lenprefixed};
+///
+/// let encoded_label = encode_request_data_lenprefixed("1.2.3.4", "mv-456", "example.com");
+///
+/// assert!(verify_label_lenprefixed(&encoded_label, "1.2.3.4", "mv-456"));
+/// assert!(!verify_label_lenprefixed(&encoded_label, "1.2.3.4", "mv-457"));
+/// ```
+pub fn verify_label_lenprefixed(domain: &str, expected_ip: &str, expected_cgid: &str) -> bool {
+    verify_label_hashed(domain, expected_ip, hash_cgid_lenprefixed(expected_cgid))
+}
+
+/// Returns whether the client routing label in `domain` matches the label
+/// encoded for `expected_ip` and the already hashed `expected_cgid`.
+fn verify_label_hashed(domain: &str, expected_ip: &str, expected_cgid: u64) -> bool {
+    match decode_request_data(domain) {
+        Ok(decoded_label) => decoded_label.matches_hashed_inputs(expected_ip, expected_cgid),
+        Err(_e) => false,
+    }
+}
+
+/// Returns whether the client routing labels in `a` and `b` route to the same
+/// backend.
+///
+/// Decodes both and compares their `client_subnet`, `subnet_mask`,
+/// `is_ipv6`, and `cgid`. `client_sdk_version` is ignored, so labels encoded
+/// by different versions of this library still match. Useful for grouping
+/// cached responses by routing without keeping the decoded labels around.
+/// Returns the [`DecodeError`] of whichever label fails to decode first.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::same_routing;
+///
+/// // different hosts in the same /24 subnet
+/// assert_eq!(Ok(true), same_routing(
+///     "abacaqdaaaaaaaamnjg3oubcyvrgm.example.com",
+///     "abacaqdaaaaaaaamnjg3oubcyvrgm.example.org",
+/// ).map_err(|e| e.to_string()));
+///
+/// // different subnet
+/// assert_eq!(Ok(false), same_routing(
+///     "abacaqdaaaaaaaamnjg3oubcyvrgm.example.com",
+///     "abacaqeaaaaaaaamnjg3oubcyvrgm.example.com",
+/// ).map_err(|e| e.to_string()));
+///
+/// assert!(same_routing("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com", "example.com").is_err());
+/// ```
+pub fn same_routing(a: &str, b: &str) -> Result<bool, DecodeError> {
+    let mut decoder = LabelDecoder::default();
+    let decoded_a = decoder.decode(a)?;
+    let decoded_b = decoder.decode(b)?;
+
+    Ok(decoded_a.has_same_routing(&decoded_b))
+}
+
+/// Returns the first DNS label of `domain`, or [`None`] if it's empty.
+///
+/// Finds the label the same way every decode path does, ending it at the first
+/// `'.'`. The label is a slice of `domain`, so nothing is allocated and case
+/// is preserved; decoding ignores case, so the label isn't lowercased here. A
+/// `domain` that is empty or starts with `'.'` has an empty first label.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::normalize_first_label;
+///
+/// assert_eq!(Some("abacaqdaaaaaaaamnjg3oubcyvrgm"), normalize_first_label("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com"));
+/// assert_eq!(Some("ABACAQDAAAAAAAAMNJG3OUBCYVRGM"), normalize_first_label("ABACAQDAAAAAAAAMNJG3OUBCYVRGM"));
+///
+/// assert_eq!(None, normalize_first_label(".example.com"));
+/// assert_eq!(None, normalize_first_label(""));
+/// ```
+pub fn normalize_first_label(domain: &str) -> Option<&str> {
+    let (client_routing_label, _rest) = split_first_label(domain);
+
+    (!client_routing_label.is_empty()).then_some(client_routing_label)
+}
+
+/// Returns the first DNS label of `domain` and the rest of `domain` after the
+/// `'.'` that ends it, or [`None`] for the rest if there is no `'.'`.
+fn split_first_label(domain: &str) -> (&str, Option<&str>) {
+    // the label ends at an ascii '.' or the end of domain, so it's always on a
+    // char boundary
+    let client_routing_label = &domain[..first_label(domain.as_bytes()).len()];
+
+    (client_routing_label, domain.get(client_routing_label.len() + 1..))
+}
+
+/// Returns a multi-line, human readable report of everything decoded from
+/// `domain`, for pasting into support tickets.
+///
+/// Lists the input, its first DNS label, and the label's length against
+/// [`ENCODED_LABEL_LEN`]. If the label decodes, follows with each field of
+/// [`LABEL_FIELDS`](client_routing_label::LABEL_FIELDS) with its width and
+/// encoded value, the subnet in CIDR notation, and the cgid in hex. Otherwise
+/// ends with the [`DecodeError`]. Every line ends in a newline.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::debug_report;
+///
+/// let report = debug_report("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com");
+/// assert!(report.contains("subnet: 1.2.3.0/24\n"));
+///
+/// let report = debug_report("vod1.example.com");
+/// assert!(report.ends_with("error: Passed 4 - expected 29 characters\n"));
+/// ```
+pub fn debug_report(domain: &str) -> String {
+    let client_routing_label = normalize_first_label(domain).unwrap_or_default();
+    let mut report = format!(
+        "input: {}\nfirst label: {}\nlength: {} of {} chars\n",
+        domain,
+        client_routing_label,
+        client_routing_label.len(),
+        ENCODED_LABEL_LEN,
+    );
+
+    let decoded_label = match decode_request_data(domain) {
+        Ok(decoded_label) => decoded_label,
+        Err(e) => {
+            report.push_str(&format!("error: {}\n", e));
+            return report;
+        }
+    };
+
+    let field_values = ClientRoutingLabel::from_decoded(&decoded_label).field_values();
+    for ((name, num_bits), value) in client_routing_label::LABEL_FIELDS.iter().zip(field_values) {
+        report.push_str(&format!("{} ({} bits): {}\n", name, num_bits, value));
+    }
+    report.push_str(&format!(
+        "subnet: {}\ncgid: {}\n",
+        decoded_label.to_cidr_string().unwrap_or_else(|| String::from("none")),
+        decoded_label.cgid_hex(),
+    ));
+
+    report
+}
+
+/// Returns whether the first DNS label of `domain` looks like a client routing
+/// label.
+///
+/// Only checks that the label is [`ENCODED_LABEL_LEN`] chars long and every
+/// char is in the Base32 alphabet, ignoring case. Nothing is decoded or
+/// allocated, so this is cheaper than checking [`decode_request_data`] for an
+/// error. Since the default [`InvalidCharPolicy`](encode_decode::InvalidCharPolicy)
+/// decodes any char, a label this rejects may still decode.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::looks_like_routing_label;
+///
+/// assert!(looks_like_routing_label("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com"));
+/// assert!(looks_like_routing_label("ABACAQDAAAAAAAAMNJG3OUBCYVRGM"));
+///
+/// assert!(!looks_like_routing_label("www.example.com"));
+/// assert!(!looks_like_routing_label("abacaqdaaaaaaaamnjg3oubcyvrg1"));
+/// ```
+pub fn looks_like_routing_label(domain: &str) -> bool {
+    let client_routing_label = normalize_first_label(domain).unwrap_or_default();
+
+    client_routing_label.len() == ENCODED_LABEL_LEN
+        && client_routing_label.bytes().all(is_base32_char)
+}
+
+/// Returns the [`DecodedClientRoutingLabel`] for `domain`, or [`None`] if it
+/// can't be decoded.
+///
+/// A thin wrapper over [`decode_request_data`] for callers that skip
+/// undecodable domains and don't need to know why decoding failed.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_opt;
+///
+/// let decoded_label = decode_request_data_opt("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com");
+/// assert_eq!(24, decoded_label.unwrap().subnet_mask);
+///
+/// assert!(decode_request_data_opt("example.com").is_none());
+/// ```
+pub fn decode_request_data_opt(domain: &str) -> Option<DecodedClientRoutingLabel> {
+    decode_request_data(domain).ok()
+}
+
+/// Returns the [`DecodedClientRoutingLabel`] for `domain` with its 0-100
+/// plausibility score, or [`None`] if it can't be decoded.
+///
+/// The score is [`DecodedClientRoutingLabel::plausibility`]. Intended for
+/// mining logs for labels, where callers pick a threshold to filter out first
+/// DNS labels that decode but weren't produced by this library. A label that
+/// isn't [`is_consistent`](DecodedClientRoutingLabel::is_consistent) scores no
+/// more than 70, and with the `tracing` feature also emits a warn event.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_with_plausibility;
+///
+/// let (decoded_label, plausibility) = decode_with_plausibility("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com").unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+/// assert_eq!(100, plausibility);
+///
+/// // an Ipv4 label with a /48 mask
+/// let (decoded_label, plausibility) = decode_with_plausibility("abacaqdaaaaaaaawnjg3oubcyvrgm.example.com").unwrap();
+/// assert!(!decoded_label.is_consistent());
+/// assert_eq!(70, plausibility);
+///
+/// assert!(decode_with_plausibility("example.com").is_none());
+/// ```
+pub fn decode_with_plausibility(domain: &str) -> Option<(DecodedClientRoutingLabel, u8)> {
+    let decoded_label = decode_request_data(domain).ok()?;
+
+    #[cfg(feature = "tracing")]
+    if !decoded_label.is_consistent() {
+        tracing::warn!(
+            subnet_mask = decoded_label.subnet_mask,
+            is_ipv6 = decoded_label.is_ipv6,
+            "decoded client routing label has a subnet mask too wide for its ip version"
+        );
+    }
+
+    Some((decoded_label, decoded_label.plausibility()))
+}
+
+/// Decodes every line of `input` as a domain and writes a TSV row of each
+/// decoded label to `output`, returning how many lines were skipped.
+///
+/// Each row holds `client_sdk_version`, `is_ipv6`, the
+/// [`subnet_string`](DecodedClientRoutingLabel::subnet_string), `subnet_mask`,
+/// and `cgid`, separated by tabs and ending in a newline. Lines that can't be
+/// decoded, including blank lines, are skipped and counted. Lines don't have to
+/// be valid UTF-8, and trailing `\r` is stripped so CRLF logs work. Returns any
+/// error from reading `input` or writing `output`.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::process_log;
+///
+/// let input = b"abacaqdaaaaaaaamnjg3oubcyvrgm.example.com\nexample.com\n";
+/// let mut output: Vec<u8> = Vec::new();
+///
+/// let num_skipped = process_log(&input[..], &mut output).unwrap();
+/// assert_eq!(1, num_skipped);
+/// assert_eq!(b"1\tfalse\t1.2.3.0\t24\t15319960192071419084\n".to_vec(), output);
+/// ```
+pub fn process_log<R: io::BufRead, W: io::Write>(mut input: R, mut output: W) -> io::Result<usize> {
+    let mut decoder = LabelDecoder::default();
+    let mut line = Vec::new();
+    let mut num_skipped = 0;
+
+    loop {
+        line.clear();
+        if input.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+
+        let mut domain = &line[..];
+        while let Some((b'\n' | b'\r', rest)) = domain.split_last() {
+            domain = rest;
+        }
+
+        match decoder.decode_bytes(domain) {
+            Ok(decoded_label) => writeln!(
+                output,
+                "{}\t{}\t{}\t{}\t{}",
+                decoded_label.client_sdk_version,
+                decoded_label.is_ipv6,
+                decoded_label.subnet_string(),
+                decoded_label.subnet_mask,
+                decoded_label.cgid,
+            )?,
+            Err(_e) => num_skipped += 1,
+        }
+    }
+
+    Ok(num_skipped)
+}
+
+/// Returns a result containing either a [`DecodedClientRoutingLabel`] or a
+/// [`DecodeError`] for any `domain` that can be viewed as bytes.
+///
+/// Behaves the same as [`decode_request_data`] but accepts `&str`, `String`,
+/// `&[u8]`, `Vec<u8>`, or anything else implementing `AsRef<[u8]>`. The first
+/// DNS label is lowercased into a stack buffer, so no allocation is needed.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_generic;
+///
+/// let decoded_label = decode_request_data_generic("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com").unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+///
+/// let decoded_label = decode_request_data_generic(String::from("ABACAQDAAAAAAAAMNJG3OUBCYVRGM")).unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+///
+/// let decoded_label = decode_request_data_generic(b"abacaqdaaaaaaaamnjg3oubcyvrgm").unwrap();
+/// assert_eq!(24, decoded_label.subnet_mask);
+/// ```
+pub fn decode_request_data_generic<T: AsRef<[u8]>>(
+    domain: T,
+) -> Result<DecodedClientRoutingLabel, DecodeError> {
+    LabelDecoder::default().decode_bytes(domain.as_ref())
+}
+
+/// Number of domains [`decode_request_data_par`] decodes per rayon task.
+///
+/// Large enough that scheduling a task costs little next to decoding its
+/// chunk, small enough that a batch still spreads across every thread.
+#[cfg(feature = "rayon")]
+pub const DECODE_PAR_CHUNK_SIZE: usize = 1024;
+
+/// Returns a [`Vec`] with the result of decoding each of `domains`, in input
+/// order.
+///
+/// Splits `domains` into chunks of [`DECODE_PAR_CHUNK_SIZE`] and decodes them
+/// on rayon's global thread pool, so repeated calls share the same threads
+/// rather than spawning new ones. Each chunk reuses a single [`LabelDecoder`].
+/// Every result is the same as calling [`decode_request_data`] on that domain.
+/// Requires the `rayon` feature.
+///
+/// # Examples:
+/// ```
+/// use amazon_cloudfront_client_routing_lib::decode_request_data_par;
+///
+/// let decoded_labels = decode_request_data_par(&[
+///     "abacaqdaaaaaaaamnjg3oubcyvrgm.example.com",
+///     "example.com",
+/// ]);
+///
+/// assert_eq!(2, decoded_labels.len());
+/// assert_eq!(24, decoded_labels[0].as_ref().unwrap().subnet_mask);
+/// assert!(decoded_labels[1].is_err());
+/// ```
+#[cfg(feature = "rayon")]
+pub fn decode_request_data_par(
+    domains: &[&str],
+) -> Vec<Result<DecodedClientRoutingLabel, DecodeError>> {
+    domains
+        .par_chunks(DECODE_PAR_CHUNK_SIZE)
+        .flat_map_iter(|chunk| {
+            let mut decoder = LabelDecoder::default();
+            chunk.iter().map(move |domain| decoder.decode(domain))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tracing")]
+    use super::decode_with_plausibility;
+    use super::{
+        encode_iter, encode_matrix_with_hasher, encode_request_data, split_first_label, EncodeIter,
+    };
+    use crate::client_routing_label::ClientRoutingLabel;
+    use crate::hash::hash_cgid;
+    use std::collections::HashMap;
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    #[tracing_test::traced_test]
+    fn validate_decode_with_plausibility_warns_on_inconsistent_label() {
+        assert!(decode_with_plausibility("abacaqdaaaaaaaamnjg3oubcyvrgm.example.com").is_some());
+        assert!(!logs_contain("subnet mask too wide"));
+
+        assert!(decode_with_plausibility("abacaqdaaaaaaaawnjg3oubcyvrgm.example.com").is_some());
+        assert!(logs_contain("WARN"));
+        assert!(logs_contain("subnet mask too wide for its ip version"));
+    }
+
+    #[test]
+    fn validate_split_first_label() {
+        assert_eq!(("abc", Some("example.com")), split_first_label("abc.example.com"));
+        assert_eq!(("abc", Some("")), split_first_label("abc."));
+        assert_eq!(("abc", None), split_first_label("abc"));
+        assert_eq!(("", Some("abc")), split_first_label(".abc"));
+        assert_eq!(("", None), split_first_label(""));
+        assert_eq!(("βόλοσ", Some("example.com")), split_first_label("βόλοσ.example.com"));
+    }
+
+    #[test]
+    fn validate_encode_iter_reuses_its_buffer() {
+        let inputs = [
+            ("85.83.215.126", "B086VX9VMK", "example.com"),
+            ("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", "Q9OP1I23", "example.com"),
+        ];
+        let mut encoded_labels = EncodeIter {
+            inputs: inputs.into_iter(),
+            label: ClientRoutingLabel::default(),
+            client_routing_label: String::with_capacity(29),
+        };
+        let buffer = encoded_labels.client_routing_label.as_ptr();
+
+        for (client_ip, content_group_id, fqdn) in inputs {
+            assert_eq!(
+                Some(encode_request_data(client_ip, content_group_id, fqdn)),
+                encoded_labels.next()
+            );
+            assert_eq!(buffer, encoded_labels.client_routing_label.as_ptr());
+        }
+        assert_eq!(None, encoded_labels.next());
+        assert_eq!(2, encode_iter(inputs.into_iter()).count());
+    }
+
+    #[test]
+    fn validate_encode_matrix_hashes_each_cgid_once() {
+        let mut num_hashes: HashMap<String, usize> = HashMap::new();
+        let encoded_labels = encode_matrix_with_hasher(
+            &["85.83.215.126", "819e:5c2e:21e4:0094:4805:1635:f8e4:049b"],
+            &["B086VX9VMK", "Q9OP1I23"],
+            "example.com",
+            |cgid| {
+                *num_hashes.entry(cgid.to_string()).or_default() += 1;
+                hash_cgid(cgid)
+            },
+        );
+
+        assert_eq!(Some(&1), num_hashes.get("B086VX9VMK"));
+        assert_eq!(Some(&1), num_hashes.get("Q9OP1I23"));
+        assert_eq!(2, num_hashes.len());
 
-    label.decode(client_routing_label)
+        assert_eq!(
+            vec![
+                encode_request_data("85.83.215.126", "B086VX9VMK", "example.com"),
+                encode_request_data("85.83.215.126", "Q9OP1I23", "example.com"),
+                encode_request_data(
+                    "819e:5c2e:21e4:0094:4805:1635:f8e4:049b",
+                    "B086VX9VMK",
+                    "example.com"
+                ),
+                encode_request_data(
+                    "819e:5c2e:21e4:0094:4805:1635:f8e4:049b",
+                    "Q9OP1I23",
+                    "example.com"
+                ),
+            ],
+            encoded_labels
+        );
+    }
 }
@@ -0,0 +1,47 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    client_routing_label::DecodedClientRoutingLabel, errors::DecodeError, ip::ClientSubnetEncodingData,
+};
+
+/// Returns the input kind recorded on encode spans: "v4", "v6", or "invalid"
+/// for a client ip that couldn't be parsed.
+pub(crate) fn encode_kind(client_subnet_encoding_data: &ClientSubnetEncodingData) -> &'static str {
+    if client_subnet_encoding_data.subnet_mask == 0 {
+        "invalid"
+    } else if client_subnet_encoding_data.is_ipv6 != 0 {
+        "v6"
+    } else {
+        "v4"
+    }
+}
+
+/// Returns the input kind recorded on decode spans: "v4", "v6", or "invalid"
+/// for a label that carries no client subnet.
+pub(crate) fn decode_kind(decoded_label: &DecodedClientRoutingLabel) -> &'static str {
+    if !decoded_label.has_subnet() {
+        "invalid"
+    } else if decoded_label.is_ipv6 {
+        "v6"
+    } else {
+        "v4"
+    }
+}
+
+/// Records the decoded kind on a decode `span`, or "invalid" along with a warn
+/// event for a failed decode.
+pub(crate) fn record_decode(
+    span: &tracing::Span,
+    decoded_label: Result<&DecodedClientRoutingLabel, &DecodeError>,
+) {
+    match decoded_label {
+        Ok(decoded_label) => {
+            span.record("kind", decode_kind(decoded_label));
+        }
+        Err(e) => {
+            span.record("kind", "invalid");
+            tracing::warn!(error = %e, "failed to decode client routing label");
+        }
+    }
+}
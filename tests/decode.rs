@@ -1,6 +1,24 @@
+// the original decode tests compare against bool literals
+#![allow(clippy::bool_assert_comparison)]
+
 #[cfg(test)]
 mod test_encode_request_data {
-    use amazon_cloudfront_client_routing_lib::decode_request_data;
+    use std::ffi::OsStr;
+
+    use amazon_cloudfront_client_routing_lib::client_routing_label::{
+        ClientRoutingLabel, DecodedClientRoutingLabel,
+    };
+    use amazon_cloudfront_client_routing_lib::errors::DecodeError;
+    use amazon_cloudfront_client_routing_lib::hash::hash_cgid;
+    use amazon_cloudfront_client_routing_lib::ip::{parse_client_ip, parse_client_ip_with_ipv6_mask};
+    use amazon_cloudfront_client_routing_lib::{
+        debug_report, decode_into, decode_request_data, decode_request_data_audited, decode_request_data_gen, decode_request_data_generic,
+        decode_request_data_opt, decode_request_data_os, decode_request_data_scan,
+        decode_request_data_sep, decode_request_data_with_version, decode_with_plausibility,
+        encode_request_data,
+        encode_request_data_no_subnet,
+        looks_like_routing_label, normalize_first_label, process_log, same_routing, verify_label,
+    };
 
     #[test]
     fn validate_decode_with_ipv4() {
@@ -142,4 +160,834 @@ mod test_encode_request_data {
             Err(_e) => (),
         };
     }
+
+    #[test]
+    fn validate_decode_no_subnet_sentinel() {
+        let encoded_label = encode_request_data_no_subnet("DP0124QHYT", "example.com");
+        let decoded_label = match decode_request_data(&encoded_label) {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+
+        assert!(!decoded_label.has_subnet());
+        assert_eq!(16745045142164894816, decoded_label.cgid);
+        assert_eq!(0, decoded_label.subnet_mask);
+        assert!(!decoded_label.is_ipv6);
+        assert_eq!([0, 0, 0, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+    }
+
+    #[test]
+    fn validate_decode_has_subnet() {
+        let decoded_label = match decode_request_data("abfku6xaaaaaaaamotptyubibrji6") {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+        assert!(decoded_label.has_subnet());
+
+        let decoded_label = match decode_request_data("abydhs4fyq6iaaaykudpmaxncecqs") {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+        assert!(decoded_label.has_subnet());
+    }
+
+    #[test]
+    fn validate_decode_client_subnet_byte_order() {
+        let decoded_label = match decode_request_data("abydhs4fyq6iaaaykudpmaxncecqs") {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+
+        assert_eq!(
+            [0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0, 0],
+            decoded_label.client_subnet
+        );
+        assert_eq!(
+            [0, 0, 0xe4, 0x21, 0x2e, 0x5c, 0x9e, 0x81],
+            decoded_label.client_subnet_le()
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn validate_decode_par_matches_sequential() {
+        use amazon_cloudfront_client_routing_lib::decode_request_data_par;
+
+        let domains = [
+            "abfku6xaaaaaaaamotptyubibrji6",
+            "abydhs4fyq6iaaaykudpmaxncecqs.example.com",
+            "abfku6xaaaaaaaamotptyubibrji6.vod1.example.com",
+            "abaaaaaaaaaaaaaaoqysz2z3j45da",
+            "abc4aydaaaaaaaamaaaaaaaaaaaaa",
+            "abydhs4fyq6iaaaykudpmaxnce",
+            "vod1.abfku6xaaaaaaaamotptyubibrji6.example.com",
+            "",
+        ];
+        // spans several chunks, with a partial one at the end
+        let domains: Vec<&str> = domains.iter().cycle().take(5000).copied().collect();
+
+        let decoded_labels = decode_request_data_par(&domains);
+
+        assert_eq!(domains.len(), decoded_labels.len());
+        for (domain, decoded_label) in domains.iter().zip(decoded_labels) {
+            match (decode_request_data(domain), decoded_label) {
+                (Ok(expected), Ok(actual)) => {
+                    assert_eq!(expected.client_sdk_version, actual.client_sdk_version);
+                    assert_eq!(expected.is_ipv6, actual.is_ipv6);
+                    assert_eq!(expected.client_subnet, actual.client_subnet);
+                    assert_eq!(expected.subnet_mask, actual.subnet_mask);
+                    assert_eq!(expected.cgid, actual.cgid);
+                }
+                (Err(expected), Err(actual)) => {
+                    assert_eq!(expected.to_string(), actual.to_string());
+                }
+                _ => panic!("Parallel result differs from sequential for {}", domain),
+            }
+        }
+
+        assert!(decode_request_data_par(&[]).is_empty());
+    }
+
+    #[test]
+    fn validate_decode_generic_with_str_string_and_bytes() {
+        let from_str = match decode_request_data_generic("abfku6xaaaaaaaamotptyubibrji6.example.com") {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+        let from_string =
+            match decode_request_data_generic(String::from("ABFKU6XAAAAAAAAMOTPTYUBIBRJI6.example.com")) {
+                Ok(label) => label,
+                Err(e) => panic!("{}", e),
+            };
+        let from_bytes = match decode_request_data_generic(&b"abfku6xaaaaaaaamotptyubibrji6"[..]) {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+
+        for decoded_label in [from_str, from_string, from_bytes] {
+            assert_eq!(1, decoded_label.client_sdk_version);
+            assert_eq!(16843032286346126622, decoded_label.cgid);
+            assert_eq!(24, decoded_label.subnet_mask);
+            assert!(!decoded_label.is_ipv6);
+            assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+        }
+    }
+
+    #[test]
+    fn validate_decode_generic_with_label_longer_than_dns_label_returns_error() {
+        match decode_request_data_generic("a".repeat(70)) {
+            Ok(_dns_label) => {
+                panic!("Didn't return an error when it should have")
+            }
+            Err(e) => assert_eq!(
+                "Passed 70 - longer than the 63 character max DNS label",
+                e.to_string()
+            ),
+        };
+    }
+
+    #[test]
+    fn validate_decode_with_label_longer_than_dns_label_returns_too_long_error() {
+        let domain = format!("{}.example.com", "a".repeat(100));
+
+        match decode_request_data(&domain) {
+            Ok(_dns_label) => {
+                panic!("Didn't return an error when it should have")
+            }
+            Err(DecodeError::LabelTooLong { len }) => assert_eq!(100, len),
+            Err(e) => panic!("Returned the wrong error: {}", e),
+        };
+    }
+
+    #[test]
+    fn validate_decode_round_trip_ipv6_56() {
+        let mut label = ClientRoutingLabel::default();
+        label.set_data(
+            parse_client_ip_with_ipv6_mask("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", 56),
+            hash_cgid("Q9OP1I23"),
+        );
+
+        let decoded_label = match decode_request_data(&label.encode()) {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+
+        assert_eq!(1, decoded_label.client_sdk_version);
+        assert_eq!(12253709671023643154, decoded_label.cgid);
+        assert_eq!(56, decoded_label.subnet_mask);
+        assert!(decoded_label.is_ipv6);
+        assert_eq!(
+            [0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0, 0],
+            decoded_label.client_subnet
+        );
+    }
+
+    #[test]
+    fn validate_decode_round_trip_ipv6_64() {
+        let mut label = ClientRoutingLabel::default();
+        label.set_data(
+            parse_client_ip_with_ipv6_mask("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", 64),
+            hash_cgid("Q9OP1I23"),
+        );
+
+        let decoded_label = match decode_request_data(&label.encode()) {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+
+        assert_eq!(1, decoded_label.client_sdk_version);
+        assert_eq!(12253709671023643154, decoded_label.cgid);
+        assert_eq!(64, decoded_label.subnet_mask);
+        assert!(decoded_label.is_ipv6);
+        assert!(decoded_label.has_subnet());
+        assert_eq!(
+            [0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0, 0x94],
+            decoded_label.client_subnet
+        );
+    }
+
+    #[test]
+    fn validate_decode_opt_with_valid_label() {
+        let decoded_label = match decode_request_data_opt("abfku6xaaaaaaaamotptyubibrji6.example.com") {
+            Some(label) => label,
+            None => panic!("Returned None when it shouldn't have"),
+        };
+
+        assert_eq!(1, decoded_label.client_sdk_version);
+        assert_eq!(16843032286346126622, decoded_label.cgid);
+        assert_eq!(24, decoded_label.subnet_mask);
+        assert!(!decoded_label.is_ipv6);
+        assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+    }
+
+    #[test]
+    fn validate_decode_opt_with_invalid_labels_returns_none() {
+        assert!(decode_request_data_opt("abydhs4fyq6iaaaykudpmaxnce").is_none());
+        assert!(decode_request_data_opt("abydhs4fyq6iaaaykudpmaxncecqsaaaa").is_none());
+        assert!(decode_request_data_opt("").is_none());
+        assert!(decode_request_data_opt("vod1.abfku6xaaaaaaaamotptyubibrji6.example.com").is_none());
+    }
+
+    #[test]
+    fn validate_decode_with_version_versionless_label() {
+        let mut label = ClientRoutingLabel::versionless(1);
+        label.set_data(
+            parse_client_ip("85.83.215.126"),
+            hash_cgid("B086VX9VMK"),
+        );
+        let encoded_label = label.encode();
+        assert_eq!("fku6xaaaaaaaamotptyubibrji6", encoded_label);
+
+        let decoded_label = match decode_request_data_with_version(&encoded_label, 1) {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+
+        assert_eq!(1, decoded_label.client_sdk_version);
+        assert_eq!(16843032286346126622, decoded_label.cgid);
+        assert_eq!(24, decoded_label.subnet_mask);
+        assert!(!decoded_label.is_ipv6);
+        assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+    }
+
+    #[test]
+    fn validate_decode_with_version_versioned_label_returns_error() {
+        match decode_request_data_with_version("abfku6xaaaaaaaamotptyubibrji6", 1) {
+            Ok(_dns_label) => {
+                panic!("Didn't return an error when it should have")
+            }
+            Err(e) => assert_eq!("Passed 29 - expected 27 characters", e.to_string()),
+        };
+    }
+
+    #[test]
+    fn validate_decode_with_pipe_separator() {
+        let decoded_label = match decode_request_data_sep("abfku6xaaaaaaaamotptyubibrji6|vod1|example.com", '|') {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+
+        assert_eq!(1, decoded_label.client_sdk_version);
+        assert_eq!(16843032286346126622, decoded_label.cgid);
+        assert_eq!(24, decoded_label.subnet_mask);
+        assert!(!decoded_label.is_ipv6);
+        assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+
+        match decode_request_data_sep("abfku6xaaaaaaaamotptyubibrji6.example.com", '|') {
+            Ok(_dns_label) => {
+                panic!("Didn't return an error when it should have")
+            }
+            Err(e) => assert_eq!("Passed 41 - expected 29 characters", e.to_string()),
+        };
+
+        // separators outside ascii are split on as a whole char
+        match decode_request_data_sep("abfku6xaaaaaaaamotptyubibrji6·vod1·example.com", '·') {
+            Ok(decoded_label) => assert_eq!(16843032286346126622, decoded_label.cgid),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+    }
+
+    #[test]
+    fn validate_decode_with_trailing_dot() {
+        let decoded_label = match decode_request_data("abfku6xaaaaaaaamotptyubibrji6.example.com.") {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+
+        assert_eq!(16843032286346126622, decoded_label.cgid);
+        assert_eq!(24, decoded_label.subnet_mask);
+        assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+
+        let decoded_label = match decode_request_data("abfku6xaaaaaaaamotptyubibrji6.") {
+            Ok(label) => label,
+            Err(e) => panic!("{}", e),
+        };
+
+        assert_eq!(16843032286346126622, decoded_label.cgid);
+    }
+
+    #[test]
+    fn validate_decode_with_leading_dot_returns_empty_error() {
+        for domain in [".abfku6xaaaaaaaamotptyubibrji6.example.com", ".", ""] {
+            match decode_request_data(domain) {
+                Ok(_dns_label) => {
+                    panic!("Didn't return an error when it should have")
+                }
+                Err(DecodeError::Empty) => (),
+                Err(e) => panic!("Returned the wrong error: {}", e),
+            };
+        }
+    }
+
+    #[test]
+    fn validate_decode_ignores_double_dots_after_first_label() {
+        for domain in [
+            "abfku6xaaaaaaaamotptyubibrji6..example.com",
+            "abfku6xaaaaaaaamotptyubibrji6.example..com",
+            "abfku6xaaaaaaaamotptyubibrji6...",
+        ] {
+            let decoded_label = match decode_request_data(domain) {
+                Ok(label) => label,
+                Err(e) => panic!("Threw error when shouldn't have for {}: {}", domain, e),
+            };
+
+            assert_eq!(16843032286346126622, decoded_label.cgid);
+            assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+        }
+    }
+
+    #[test]
+    fn validate_decode_with_leading_double_dot_returns_empty_error() {
+        for domain in ["..abfku6xaaaaaaaamotptyubibrji6.example.com", ".."] {
+            match decode_request_data(domain) {
+                Ok(_dns_label) => {
+                    panic!("Didn't return an error when it should have")
+                }
+                Err(DecodeError::Empty) => (),
+                Err(e) => panic!("Returned the wrong error: {}", e),
+            };
+        }
+    }
+
+    #[test]
+    fn validate_decode_into_reused_label() {
+        let mut decoded_label = DecodedClientRoutingLabel::default();
+
+        match decode_into("abydhs4fyq6iaaaykudpmaxncecqs.example.com", &mut decoded_label) {
+            Ok(()) => (),
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(1, decoded_label.client_sdk_version);
+        assert_eq!(12253709671023643154, decoded_label.cgid);
+        assert_eq!(48, decoded_label.subnet_mask);
+        assert!(decoded_label.is_ipv6);
+        assert_eq!(
+            [0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0, 0],
+            decoded_label.client_subnet
+        );
+
+        match decode_into("abc4aydaaaaaaaamaaaaaaaaaaaaa", &mut decoded_label) {
+            Ok(()) => (),
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(1, decoded_label.client_sdk_version);
+        assert_eq!(0, decoded_label.cgid);
+        assert_eq!(24, decoded_label.subnet_mask);
+        assert!(!decoded_label.is_ipv6);
+        assert_eq!([46, 3, 3, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+
+        match decode_into("abydhs4fyq6iaaaykudpmaxnce", &mut decoded_label) {
+            Ok(()) => panic!("Didn't return an error when it should have"),
+            Err(_e) => (),
+        };
+        assert_eq!(24, decoded_label.subnet_mask);
+        assert_eq!([46, 3, 3, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+
+        match decode_into("abaaaaaaaaaaaaaaaaaaaaaaaaaaa", &mut decoded_label) {
+            Ok(()) => (),
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(1, decoded_label.client_sdk_version);
+        assert_eq!(0, decoded_label.cgid);
+        assert_eq!(0, decoded_label.subnet_mask);
+        assert!(!decoded_label.is_ipv6);
+        assert_eq!([0, 0, 0, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+    }
+
+    #[test]
+    fn validate_decode_audited_returns_lowercased_first_label() {
+        let (decoded_label, client_routing_label) =
+            match decode_request_data_audited("AbFkU6xAAAAAAAAMOTPTYUBIBRJI6.Example.com") {
+                Ok(audited) => audited,
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+
+        assert_eq!("abfku6xaaaaaaaamotptyubibrji6", client_routing_label);
+        assert_eq!(16843032286346126622, decoded_label.cgid);
+        assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+
+        match decode_request_data_audited("abfku6.example.com") {
+            Ok(_audited) => panic!("Didn't return an error when it should have"),
+            Err(e) => assert_eq!("Passed 6 - expected 29 characters", e.to_string()),
+        };
+    }
+
+    #[test]
+    fn validate_verify_label_matching_ip() {
+        assert!(verify_label(
+            "abfku6xaaaaaaaamotptyubibrji6.example.com",
+            "85.83.215.126",
+            "B086VX9VMK"
+        ));
+        assert!(verify_label(
+            "abfku6xaaaaaaaamotptyubibrji6",
+            "85.83.215.1",
+            "B086VX9VMK"
+        ));
+        assert!(verify_label(
+            "abydhs4fyq6iaaaykudpmaxncecqs.example.com",
+            "819e:5c2e:21e4:ffff::1",
+            "Q9OP1I23"
+        ));
+        assert!(verify_label(
+            "abaaaaaaaaaaaaaaoqysz2z3j45da.example.com",
+            "122.71",
+            "DP0124QHYT"
+        ));
+    }
+
+    #[test]
+    fn validate_same_routing_across_versions() {
+        // "ab" and "ac" are versions 1 and 2 of the same subnet and cgid, as if
+        // the second label was encoded later by a newer version
+        let same = match same_routing(
+            "abfku6xaaaaaaaamotptyubibrji6.example.com",
+            "acfku6xaaaaaaaamotptyubibrji6.example.com",
+        ) {
+            Ok(same) => same,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert!(same);
+
+        let encoded_label = encode_request_data("85.83.215.1", "B086VX9VMK", "example.com");
+        let same = match same_routing("abfku6xaaaaaaaamotptyubibrji6", &encoded_label) {
+            Ok(same) => same,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert!(same);
+    }
+
+    #[test]
+    fn validate_same_routing_different_cgid_or_subnet() {
+        let encoded_label = encode_request_data("85.83.215.1", "B086VX9VMK", "example.com");
+
+        let other_cgid = encode_request_data("85.83.215.1", "DP0124QHYT", "example.com");
+        match same_routing(&encoded_label, &other_cgid) {
+            Ok(same) => assert!(!same),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        let other_subnet = encode_request_data("85.83.216.1", "B086VX9VMK", "example.com");
+        match same_routing(&encoded_label, &other_subnet) {
+            Ok(same) => assert!(!same),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        match same_routing(&encoded_label, "example.com") {
+            Ok(_same) => panic!("Didn't return an error when it should have"),
+            Err(e) => assert_eq!("Passed 7 - expected 29 characters", e.to_string()),
+        };
+    }
+
+    #[test]
+    fn validate_verify_label_mismatching_ip() {
+        assert!(!verify_label(
+            "abfku6xaaaaaaaamotptyubibrji6.example.com",
+            "85.83.216.126",
+            "B086VX9VMK"
+        ));
+        assert!(!verify_label(
+            "abfku6xaaaaaaaamotptyubibrji6.example.com",
+            "819e:5c2e:21e4:0094:4805:1635:f8e4:049b",
+            "B086VX9VMK"
+        ));
+        assert!(!verify_label(
+            "abfku6xaaaaaaaamotptyubibrji6.example.com",
+            "",
+            "B086VX9VMK"
+        ));
+        assert!(!verify_label(
+            "abfku6xaaaaaaaamotptyubibrji6.example.com",
+            "85.83.215.126",
+            "Q9OP1I23"
+        ));
+        assert!(!verify_label(
+            "vod1.abfku6xaaaaaaaamotptyubibrji6.example.com",
+            "85.83.215.126",
+            "B086VX9VMK"
+        ));
+    }
+
+    #[test]
+    fn validate_decode_request_data_os() {
+        let decoded_label = match decode_request_data_os(OsStr::new("abfku6xaaaaaaaamotptyubibrji6.example.com")) {
+            Ok(decoded_label) => decoded_label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+        assert_eq!(24, decoded_label.subnet_mask);
+
+        match decode_request_data_os(OsStr::new("example.com")) {
+            Ok(_decoded_label) => panic!("Didn't return an error when it should have"),
+            Err(e) => assert_eq!("Passed 7 - expected 29 characters", e.to_string()),
+        };
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn validate_decode_request_data_os_non_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let domain = OsStr::from_bytes(b"abfku6xaaaaaaaamotptyubibrji\xff.example.com");
+        match decode_request_data_os(domain) {
+            Ok(_decoded_label) => panic!("Didn't return an error when it should have"),
+            Err(DecodeError::NonUtf8) => {}
+            Err(e) => panic!("Returned the wrong error: {}", e),
+        };
+    }
+
+    #[test]
+    fn validate_matches_inputs() {
+        for (encoded_label, ip, cgid) in [
+            ("abfku6xaaaaaaaamotptyubibrji6", "85.83.215.126", "B086VX9VMK"),
+            ("abydhs4fyq6iaaaykudpmaxncecqs", "819e:5c2e:21e4:ffff::1", "Q9OP1I23"),
+            ("abaaaaaaaaaaaaaaoqysz2z3j45da", "122.71", "DP0124QHYT"),
+        ] {
+            let decoded_label = match decode_request_data(encoded_label) {
+                Ok(decoded_label) => decoded_label,
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+
+            assert!(decoded_label.matches_inputs(ip, cgid));
+            assert!(!decoded_label.matches_inputs(ip, "B086VX9VML"));
+            assert!(!decoded_label.matches_inputs("85.83.216.126", cgid));
+        }
+    }
+
+    #[test]
+    fn validate_looks_like_routing_label() {
+        assert!(looks_like_routing_label("abfku6xaaaaaaaamotptyubibrji6.example.com"));
+        assert!(looks_like_routing_label("abydhs4fyq6iaaaykudpmaxncecqs"));
+
+        // 29 chars, but '0', '1', '8', and '9' aren't in the alphabet
+        assert!(!looks_like_routing_label("x8k2m9q0w1e4r5t6y7u8i9o0p1a2s.example.com"));
+        assert!(!looks_like_routing_label("www.example.com"));
+        assert!(!looks_like_routing_label("vod1.abfku6xaaaaaaaamotptyubibrji6.example.com"));
+        assert!(!looks_like_routing_label(""));
+    }
+
+    #[test]
+    fn validate_decode_gen_with_marker() {
+        let decoded_label = match decode_request_data_gen("1abfku6xaaaaaaaamotptyubibrji6.example.com") {
+            Ok(label) => label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(1, decoded_label.client_sdk_version);
+        assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+        assert_eq!(24, decoded_label.subnet_mask);
+        assert_eq!(hash_cgid("B086VX9VMK"), decoded_label.cgid);
+
+        match decode_request_data_gen("1abfku6xaaaaaaaamotptyubibr") {
+            Ok(_dns_label) => panic!("Didn't return an error when it should have"),
+            Err(e) => assert_eq!("Passed 26 - expected 29 characters", e.to_string()),
+        };
+        match decode_request_data_gen("1.example.com") {
+            Ok(_dns_label) => panic!("Didn't return an error when it should have"),
+            Err(DecodeError::Empty) => (),
+            Err(e) => panic!("Returned the wrong error: {}", e),
+        };
+    }
+
+    #[test]
+    fn validate_decode_gen_without_marker_falls_back() {
+        for domain in [
+            "abfku6xaaaaaaaamotptyubibrji6.example.com",
+            "abydhs4fyq6iaaaykudpmaxncecqs",
+        ] {
+            let decoded_label = match decode_request_data_gen(domain) {
+                Ok(label) => label,
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+            let expected_label = match decode_request_data(domain) {
+                Ok(label) => label,
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+
+            assert_eq!(expected_label.client_subnet, decoded_label.client_subnet);
+            assert_eq!(expected_label.subnet_mask, decoded_label.subnet_mask);
+            assert_eq!(expected_label.cgid, decoded_label.cgid);
+        }
+
+        assert!(decode_request_data_gen("example.com").is_err());
+    }
+
+    #[test]
+    fn validate_decode_round_trip_max_ipv4() {
+        let encoded_label = encode_request_data("255.255.255.255", "B086VX9VMK", "example.com");
+        assert_eq!("abp7777aaaaaaaamotptyubibrji6.example.com", encoded_label);
+
+        let decoded_label = match decode_request_data(&encoded_label) {
+            Ok(label) => label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!([255, 255, 255, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+        assert_eq!(24, decoded_label.subnet_mask);
+        assert!(!decoded_label.is_ipv6);
+        assert_eq!(
+            Some(String::from("255.255.255.0/24")),
+            decoded_label.to_cidr_string()
+        );
+    }
+
+    #[test]
+    fn validate_decode_round_trip_zero_ipv4() {
+        let encoded_label = encode_request_data("0.0.0.0", "B086VX9VMK", "example.com");
+        let invalid_encoded_label = encode_request_data("0.0.0", "B086VX9VMK", "example.com");
+
+        let decoded_label = match decode_request_data(&encoded_label) {
+            Ok(label) => label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        let invalid_decoded_label = match decode_request_data(&invalid_encoded_label) {
+            Ok(label) => label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        // both have a zero client subnet, only the subnet mask differs
+        assert_eq!([0; 8], decoded_label.client_subnet);
+        assert_eq!([0; 8], invalid_decoded_label.client_subnet);
+        assert_eq!(24, decoded_label.subnet_mask);
+        assert_eq!(0, invalid_decoded_label.subnet_mask);
+        assert!(decoded_label.has_subnet());
+        assert!(!invalid_decoded_label.has_subnet());
+        assert_ne!(encoded_label, invalid_encoded_label);
+    }
+
+    #[test]
+    fn validate_decode_zero_version_is_flagged() {
+        let decoded_label = match decode_request_data("abaaaaaaaaaaaaaaaaaaaaaaaaaaa") {
+            Ok(label) => label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(1, decoded_label.client_sdk_version);
+        assert!(decoded_label.has_valid_version());
+
+        // looks like a label, but was never encoded by this library
+        let domain = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaa.example.com";
+        assert!(looks_like_routing_label(domain));
+        let decoded_label = match decode_request_data(domain) {
+            Ok(label) => label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(0, decoded_label.client_sdk_version);
+        assert!(!decoded_label.has_valid_version());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn validate_decode_json() {
+        use amazon_cloudfront_client_routing_lib::decode_request_data_json;
+
+        let json = match decode_request_data_json("abfku6xaaaaaaaamotptyubibrji6.example.com") {
+            Ok(json) => json,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert!(json.contains(r#""client_subnet":"85.83.215.0""#));
+        assert!(json.contains(&format!(r#""cgid":{}"#, hash_cgid("B086VX9VMK"))));
+        assert!(json.contains(r#""subnet_mask":24"#));
+
+        let json = match decode_request_data_json("abaaaaaaaaaaaaaaoqysz2z3j45da") {
+            Ok(json) => json,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert!(json.contains(r#""client_subnet":null"#));
+
+        assert!(decode_request_data_json("example.com").is_err());
+    }
+
+    #[test]
+    fn validate_decode_scan_recovers_label_not_first() {
+        let decoded_label =
+            match decode_request_data_scan("vod1.abfku6xaaaaaaaamotptyubibrji6.example.com") {
+                Ok(label) => label,
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+        assert_eq!(1, decoded_label.client_sdk_version);
+        assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+        assert_eq!(24, decoded_label.subnet_mask);
+        assert_eq!(hash_cgid("B086VX9VMK"), decoded_label.cgid);
+
+        // a version 0 label of the right length is skipped
+        let decoded_label = match decode_request_data_scan(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaa.abydhs4fyq6iaaaykudpmaxncecqs.example.com",
+        ) {
+            Ok(label) => label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(48, decoded_label.subnet_mask);
+
+        match decode_request_data_scan("vod1.example.com") {
+            Ok(_dns_label) => panic!("Didn't return an error when it should have"),
+            Err(e) => assert_eq!("Passed 4 - expected 29 characters", e.to_string()),
+        };
+    }
+
+    #[test]
+    fn validate_decode_with_plausibility() {
+        for domain in [
+            "abfku6xaaaaaaaamotptyubibrji6.example.com",
+            "abydhs4fyq6iaaaykudpmaxncecqs.example.com",
+            "abaaaaaaaaaaaaaaoqysz2z3j45da.example.com",
+        ] {
+            match decode_with_plausibility(domain) {
+                Some((_decoded_label, plausibility)) => assert_eq!(100, plausibility),
+                None => panic!("Couldn't decode {}", domain),
+            };
+        }
+
+        match decode_with_plausibility("q7x2mzk4hv5wr3n6tj2pcy7bd4fga.example.com") {
+            Some((_decoded_label, plausibility)) => assert!(plausibility < 50),
+            None => panic!("Couldn't decode a 29 char Base32 label"),
+        };
+
+        // an Ipv4 label with a /48 mask loses the mask's points
+        match decode_with_plausibility("abfku6xaaaaaaaawotptyubibrji6.example.com") {
+            Some((decoded_label, plausibility)) => {
+                assert!(!decoded_label.is_consistent());
+                assert_eq!(70, plausibility);
+            }
+            None => panic!("Couldn't decode a 29 char Base32 label"),
+        };
+
+        assert!(decode_with_plausibility("www.example.com").is_none());
+    }
+
+    #[test]
+    fn validate_normalize_first_label() {
+        assert_eq!(
+            Some("abacaqdaaaaaaaamnjg3oubcyvrgm"),
+            normalize_first_label("abacaqdaaaaaaaamnjg3oubcyvrgm")
+        );
+        assert_eq!(
+            Some("abacaqdaaaaaaaamnjg3oubcyvrgm"),
+            normalize_first_label("abacaqdaaaaaaaamnjg3oubcyvrgm.vod1.example.com.")
+        );
+        assert_eq!(None, normalize_first_label(".abacaqdaaaaaaaamnjg3oubcyvrgm.example.com"));
+        assert_eq!(None, normalize_first_label(""));
+        assert_eq!(None, normalize_first_label("."));
+    }
+
+    #[test]
+    fn validate_process_log() {
+        let input: &[u8] = b"abfku6xaaaaaaaamotptyubibrji6.example.com\n\
+            www.example.com\n\
+            \n\
+            abydhs4fyq6iaaaykudpmaxncecqs.example.com\r\n\
+            abfku6.example.com\n\
+            ABACAQDAAAAAAAAMNJG3OUBCYVRGM";
+        let mut output: Vec<u8> = Vec::new();
+
+        let num_skipped = match process_log(input, &mut output) {
+            Ok(num_skipped) => num_skipped,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        assert_eq!(3, num_skipped);
+        assert_eq!(
+            "1\tfalse\t85.83.215.0\t24\t16843032286346126622\n\
+             1\ttrue\t819e:5c2e:21e4::\t48\t12253709671023643154\n\
+             1\tfalse\t1.2.3.0\t24\t15319960192071419084\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn validate_decode_punycode_domain() {
+        match decode_request_data("abfku6xaaaaaaaamotptyubibrji6.xn--nxasmq6b.example.com") {
+            Ok(decoded_label) => {
+                assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+                assert_eq!(24, decoded_label.subnet_mask);
+            }
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+    }
+
+    #[cfg(feature = "idna")]
+    #[test]
+    fn validate_decode_idna() {
+        use amazon_cloudfront_client_routing_lib::decode_request_data_idna;
+
+        let decoded_label = decode_request_data("abfku6xaaaaaaaamotptyubibrji6").unwrap();
+        for (domain, expected_residual_domain) in [
+            ("abfku6xaaaaaaaamotptyubibrji6.xn--nxasmq6b.example.com", "βόλοσ.example.com"),
+            ("abfku6xaaaaaaaamotptyubibrji6.vod1.xn--mnchen-3ya.de", "vod1.münchen.de"),
+            ("abfku6xaaaaaaaamotptyubibrji6.example.com", "example.com"),
+            ("abfku6xaaaaaaaamotptyubibrji6", ""),
+        ] {
+            match decode_request_data_idna(domain) {
+                Ok((idna_decoded_label, residual_domain)) => {
+                    assert_eq!(decoded_label.cgid, idna_decoded_label.cgid);
+                    assert_eq!(expected_residual_domain, residual_domain);
+                }
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+        }
+
+        assert!(decode_request_data_idna("xn--nxasmq6b.example.com").is_err());
+    }
+
+    #[test]
+    fn validate_debug_report() {
+        assert_eq!(
+            "input: ABFKU6XAAAAAAAAMOTPTYUBIBRJI6.example.com\n\
+             first label: ABFKU6XAAAAAAAAMOTPTYUBIBRJI6\n\
+             length: 29 of 29 chars\n\
+             client_sdk_version (10 bits): 1\n\
+             is_ipv6 (1 bits): 0\n\
+             client_subnet (64 bits): 6148494311290830848\n\
+             subnet_mask (6 bits): 24\n\
+             cgid (64 bits): 16843032286346126622\n\
+             subnet: 85.83.215.0/24\n\
+             cgid: e9be78a05018a51e\n",
+            debug_report("ABFKU6XAAAAAAAAMOTPTYUBIBRJI6.example.com")
+        );
+
+        assert_eq!(
+            "input: .example.com\n\
+             first label: \n\
+             length: 0 of 29 chars\n\
+             error: Passed an empty label\n",
+            debug_report(".example.com")
+        );
+    }
 }
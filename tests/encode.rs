@@ -1,6 +1,44 @@
 #[cfg(test)]
 mod test_encode_request_data {
-    use amazon_cloudfront_client_routing_lib::encode_request_data;
+    use amazon_cloudfront_client_routing_lib::{
+        encode_iter, encode_matrix, encode_request_data, encode_request_data_from_ip,
+        encode_request_data_from_socket, encode_request_data_from_subnet_data,
+        encode_request_data_lenprefixed, encode_request_data_no_subnet, encode_request_data_raw_cgid,
+        encode_request_data_salted, encode_request_data_timestamped, encode_request_data_token,
+        encode_request_data_uppercase, encode_request_data_v4_octets,
+        encode_request_data_versionless,
+        encode_request_data_wire,
+        encode_request_data_write, encode_request_data_write_bytes,
+        decode_request_data, decode_request_data_timestamped, decode_request_data_token,
+        decode_request_data_versionless,
+        encode_zone_record,
+        verify_label, verify_label_lenprefixed, verify_label_salted,
+    };
+    use amazon_cloudfront_client_routing_lib::hash::{hash_cgid, hash_cgid_lenprefixed};
+    use amazon_cloudfront_client_routing_lib::ip::{parse_client_ip_with_ipv6_mask, ClientSubnetEncodingData};
+    use amazon_cloudfront_client_routing_lib::client_routing_label::ClientRoutingLabel;
+    use amazon_cloudfront_client_routing_lib::errors::DecodeError;
+    use proptest::prelude::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn validate_encode_zone_record() {
+        assert_eq!(
+            "abfku6xaaaaaaaamotptyubibrji6.example.com. 300 IN CNAME origin.example.com.",
+            encode_zone_record("85.83.215.126", "B086VX9VMK", "example.com", 300, "origin.example.com")
+        );
+        // absolute names keep their single trailing dot
+        assert_eq!(
+            "abydhs4fyq6iaaaykudpmaxncecqs.example.com. 86400 IN CNAME origin.example.com.",
+            encode_zone_record(
+                "819e:5c2e:21e4:0094:4805:1635:f8e4:049b",
+                "Q9OP1I23",
+                "example.com.",
+                86400,
+                "origin.example.com."
+            )
+        );
+    }
 
     #[test]
     fn validate_encode_with_ipv4() {
@@ -9,6 +47,14 @@ mod test_encode_request_data {
         assert_eq!("abfku6xaaaaaaaamotptyubibrji6.example.com", encoded_label);
     }
 
+    #[test]
+    fn validate_encode_with_ipv4_octets() {
+        let encoded_label = encode_request_data_v4_octets([85, 83, 215, 126], "B086VX9VMK", "example.com");
+
+        assert_eq!("abfku6xaaaaaaaamotptyubibrji6.example.com", encoded_label);
+        assert_eq!(encode_request_data("85.83.215.126", "B086VX9VMK", "example.com"), encoded_label);
+    }
+
     #[test]
     fn validate_encode_with_ipv6() {
         let encoded_label = encode_request_data(
@@ -90,4 +136,527 @@ mod test_encode_request_data {
 
         assert_eq!("abaaaaaaaaaaaaaaaaaaaaaaaaaaa.", encoded_label);
     }
+
+    #[test]
+    fn validate_encode_no_subnet() {
+        let encoded_label = encode_request_data_no_subnet("DP0124QHYT", "example.com");
+
+        assert_eq!("abaaaaaaaaaaaaaaoqysz2z3j45da.example.com", encoded_label);
+    }
+
+    #[test]
+    fn validate_encode_no_subnet_matches_invalid_client_ip() {
+        assert_eq!(
+            encode_request_data("122.71", "DP0124QHYT", "example.com"),
+            encode_request_data_no_subnet("DP0124QHYT", "example.com")
+        );
+    }
+
+    #[test]
+    fn validate_encode_twice_on_same_thread_has_no_state_bleed() {
+        let first = encode_request_data("85.83.215.126", "B086VX9VMK", "example.com");
+        let second = encode_request_data(
+            "819e:5c2e:21e4:0094:4805:1635:f8e4:049b",
+            "Q9OP1I23",
+            "example.com",
+        );
+        let third = encode_request_data("", "", "");
+        let fourth = encode_request_data("85.83.215.126", "B086VX9VMK", "example.com");
+
+        assert_eq!("abfku6xaaaaaaaamotptyubibrji6.example.com", first);
+        assert_eq!("abydhs4fyq6iaaaykudpmaxncecqs.example.com", second);
+        assert_eq!("abaaaaaaaaaaaaaaaaaaaaaaaaaaa.", third);
+        assert_eq!(first, fourth);
+    }
+
+    /// Writer that encodes another label on every write, the way a logging
+    /// writer on the same thread might.
+    struct ReencodingWriter(Vec<u8>);
+
+    impl std::io::Write for ReencodingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            assert_eq!(
+                "abydhs4fyq6iaaaykudpmaxncecqs.example.com",
+                encode_request_data("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", "Q9OP1I23", "example.com")
+            );
+            self.0.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn validate_encode_write_bytes_with_writer_that_encodes() {
+        let mut writer = ReencodingWriter(Vec::new());
+        match encode_request_data_write_bytes(&mut writer, "85.83.215.126", "B086VX9VMK", b"example.com") {
+            Ok(()) => (),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        assert_eq!(b"abfku6xaaaaaaaamotptyubibrji6.example.com".to_vec(), writer.0);
+    }
+
+    #[test]
+    fn validate_encode_write_matches_encode() {
+        let inputs = [
+            ("85.83.215.126", "B086VX9VMK", "example.com"),
+            ("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", "Q9OP1I23", "example.com"),
+            ("122.71", "DP0124QHYT", "test.example2.com"),
+            ("", "", ""),
+        ];
+
+        for (client_ip, content_group_id, fqdn) in inputs {
+            let mut encoded_label: Vec<u8> = Vec::new();
+            match encode_request_data_write(&mut encoded_label, client_ip, content_group_id, fqdn) {
+                Ok(()) => (),
+                Err(e) => panic!("{}", e),
+            };
+
+            assert_eq!(
+                encode_request_data(client_ip, content_group_id, fqdn).into_bytes(),
+                encoded_label
+            );
+        }
+    }
+
+    #[test]
+    fn validate_encode_raw_cgid() {
+        let cgid = hash_cgid("B086VX9VMK");
+        let encoded_label =
+            encode_request_data_raw_cgid("85.83.215.126", &cgid.to_string(), "example.com");
+
+        assert_eq!("abfku6xaaaaaaaamotptyubibrji6.example.com", encoded_label);
+        assert_eq!(
+            encode_request_data("85.83.215.126", "B086VX9VMK", "example.com"),
+            encoded_label
+        );
+    }
+
+    #[test]
+    fn validate_encode_raw_cgid_non_numeric() {
+        let encoded_label = encode_request_data_raw_cgid("85.83.215.126", "B086VX9VMK", "example.com");
+
+        assert_eq!("abfku6xaaaaaaaamaaaaaaaaaaaaa.example.com", encoded_label);
+        assert_eq!(
+            encode_request_data("85.83.215.126", "", "example.com"),
+            encoded_label
+        );
+    }
+
+    #[test]
+    fn validate_encode_salted_differs_from_unsalted() {
+        let unsalted_label = encode_request_data("85.83.215.126", "B086VX9VMK", "example.com");
+        let salted_label =
+            encode_request_data_salted("85.83.215.126", "B086VX9VMK", "example.com", b"salt");
+        let other_salted_label =
+            encode_request_data_salted("85.83.215.126", "B086VX9VMK", "example.com", b"pepper");
+
+        assert_ne!(unsalted_label, salted_label);
+        assert_ne!(salted_label, other_salted_label);
+        // only the cgid is salted
+        assert_eq!(unsalted_label[..16], salted_label[..16]);
+        assert_eq!(
+            salted_label,
+            encode_request_data_salted("85.83.215.126", "B086VX9VMK", "example.com", b"salt")
+        );
+        assert_eq!(
+            unsalted_label,
+            encode_request_data_salted("85.83.215.126", "B086VX9VMK", "example.com", b"")
+        );
+    }
+
+    #[test]
+    fn validate_verify_salted_label_needs_salt() {
+        let salted_label =
+            encode_request_data_salted("85.83.215.126", "B086VX9VMK", "example.com", b"salt");
+
+        assert!(verify_label_salted(&salted_label, "85.83.215.126", "B086VX9VMK", b"salt"));
+        assert!(!verify_label_salted(&salted_label, "85.83.215.126", "B086VX9VMK", b"pepper"));
+        assert!(!verify_label_salted(&salted_label, "85.83.215.126", "B086VX9VMK", b""));
+    }
+
+    #[test]
+    fn validate_encode_lenprefixed() {
+        let label = encode_request_data("85.83.215.126", "B086VX9VMK", "example.com");
+        let lenprefixed_label =
+            encode_request_data_lenprefixed("85.83.215.126", "B086VX9VMK", "example.com");
+
+        assert_ne!(label, lenprefixed_label);
+        // only the cgid is hashed differently
+        assert_eq!(label[..16], lenprefixed_label[..16]);
+
+        match decode_request_data(&lenprefixed_label) {
+            Ok(decoded_label) => assert_eq!(hash_cgid_lenprefixed("B086VX9VMK"), decoded_label.cgid),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert!(verify_label_lenprefixed(&lenprefixed_label, "85.83.215.126", "B086VX9VMK"));
+        assert!(!verify_label(&lenprefixed_label, "85.83.215.126", "B086VX9VMK"));
+        assert!(!verify_label_lenprefixed(&label, "85.83.215.126", "B086VX9VMK"));
+    }
+
+    #[test]
+    fn validate_encode_iter_partially_consumed() {
+        let inputs = [
+            ("85.83.215.126", "B086VX9VMK", "example.com"),
+            ("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", "Q9OP1I23", "example.com"),
+            ("122.71", "DP0124QHYT", "example.com"),
+            ("46.3.3.135", "", "example.com"),
+        ];
+
+        let mut encoded_labels = encode_iter(inputs.into_iter());
+        assert_eq!(
+            Some(encode_request_data("85.83.215.126", "B086VX9VMK", "example.com")),
+            encoded_labels.next()
+        );
+
+        let encoded_labels: Vec<String> = encoded_labels
+            .filter(|encoded_label| !encoded_label.starts_with("abaaaaaaaaaaaaaa"))
+            .take(2)
+            .collect();
+        assert_eq!(
+            vec![
+                "abydhs4fyq6iaaaykudpmaxncecqs.example.com",
+                "abc4aydaaaaaaaamaaaaaaaaaaaaa.example.com",
+            ],
+            encoded_labels
+        );
+    }
+
+    #[test]
+    fn validate_encode_from_subnet_data() {
+        // 85.83.215.126 masked to /24
+        let client_subnet_encoding_data = ClientSubnetEncodingData {
+            client_subnet: u64::from_be_bytes([85, 83, 215, 0, 0, 0, 0, 0]),
+            subnet_mask: 24,
+            is_ipv6: 0,
+        };
+
+        let encoded_label = encode_request_data_from_subnet_data(
+            client_subnet_encoding_data,
+            "B086VX9VMK",
+            "example.com",
+        );
+
+        assert_eq!("abfku6xaaaaaaaamotptyubibrji6.example.com", encoded_label);
+        assert_eq!(
+            encode_request_data("85.83.215.126", "B086VX9VMK", "example.com"),
+            encoded_label
+        );
+    }
+
+    #[test]
+    fn validate_encode_from_subnet_data_ipv6_mask_0_has_no_subnet() {
+        let client_subnet_encoding_data = ClientSubnetEncodingData {
+            client_subnet: u64::from_be_bytes([0x81, 0x9e, 0x5c, 0x2e, 0x21, 0xe4, 0, 0]),
+            subnet_mask: 0,
+            is_ipv6: 1,
+        };
+
+        let encoded_label = encode_request_data_from_subnet_data(
+            client_subnet_encoding_data,
+            "B086VX9VMK",
+            "example.com",
+        );
+
+        assert_eq!(encode_request_data("", "B086VX9VMK", "example.com"), encoded_label);
+        let decoded_label = match decode_request_data(&encoded_label) {
+            Ok(decoded_label) => decoded_label,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert!(!decoded_label.has_subnet());
+    }
+
+    #[test]
+    fn validate_encode_from_socket() {
+        let client_addr: std::net::SocketAddr = match "85.83.215.126:443".parse() {
+            Ok(client_addr) => client_addr,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        let encoded_label = encode_request_data_from_socket(client_addr, "B086VX9VMK", "example.com");
+
+        assert_eq!("abfku6xaaaaaaaamotptyubibrji6.example.com", encoded_label);
+        assert_eq!(
+            encode_request_data_from_ip(client_addr.ip(), "B086VX9VMK", "example.com"),
+            encoded_label
+        );
+
+        let client_addr: std::net::SocketAddr = match "[2001:db8:1:2::1]:8443".parse() {
+            Ok(client_addr) => client_addr,
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+        assert_eq!(
+            encode_request_data("2001:db8:1:2::1", "B086VX9VMK", "example.com"),
+            encode_request_data_from_socket(client_addr, "B086VX9VMK", "example.com")
+        );
+    }
+
+    #[test]
+    fn validate_encode_wire() {
+        let encoded_label = encode_request_data_wire("85.83.215.126", "B086VX9VMK");
+
+        assert_eq!(30, encoded_label.len());
+        assert_eq!(29, encoded_label[0]);
+        assert_eq!(b"abfku6xaaaaaaaamotptyubibrji6", &encoded_label[1..]);
+    }
+
+    #[test]
+    fn validate_encode_write_bytes_passes_non_ascii_fqdn_through() {
+        // "bücher" in UTF-8 followed by a byte that isn't valid UTF-8
+        let fqdn = b"b\xc3\xbccher.\xfe.example.com";
+
+        let mut encoded_label: Vec<u8> = Vec::new();
+        match encode_request_data_write_bytes(&mut encoded_label, "85.83.215.126", "B086VX9VMK", fqdn) {
+            Ok(()) => (),
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+
+        assert_eq!(b"abfku6xaaaaaaaamotptyubibrji6.", &encoded_label[..30]);
+        assert_eq!(fqdn, &encoded_label[30..]);
+    }
+
+    #[test]
+    fn validate_encode_uppercase_round_trip() {
+        let inputs = [
+            ("85.83.215.126", "B086VX9VMK"),
+            ("819e:5c2e:21e4:0094:4805:1635:f8e4:049b", "Q9OP1I23"),
+            ("1.2.a", "DP0124QHYT"),
+        ];
+
+        for (client_ip, cgid) in inputs {
+            let lowercase = encode_request_data(client_ip, cgid, "example.com");
+            let uppercase = encode_request_data_uppercase(client_ip, cgid, "example.com");
+
+            assert_eq!(lowercase[..29].to_ascii_uppercase(), uppercase[..29]);
+            assert_eq!(".example.com", &uppercase[29..]);
+
+            let decoded_lowercase = match decode_request_data(&lowercase) {
+                Ok(decoded) => decoded,
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+            let decoded_uppercase = match decode_request_data(&uppercase) {
+                Ok(decoded) => decoded,
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+
+            assert_eq!(decoded_lowercase.client_sdk_version, decoded_uppercase.client_sdk_version);
+            assert_eq!(decoded_lowercase.is_ipv6, decoded_uppercase.is_ipv6);
+            assert_eq!(decoded_lowercase.client_subnet, decoded_uppercase.client_subnet);
+            assert_eq!(decoded_lowercase.subnet_mask, decoded_uppercase.subnet_mask);
+            assert_eq!(decoded_lowercase.cgid, decoded_uppercase.cgid);
+        }
+
+        assert_eq!(
+            "ABFKU6XAAAAAAAAMOTPTYUBIBRJI6.example.com",
+            encode_request_data_uppercase("85.83.215.126", "B086VX9VMK", "example.com")
+        );
+    }
+
+    #[test]
+    fn validate_encode_matrix_2x2() {
+        let encoded_labels = encode_matrix(
+            &["85.83.215.126", "819e:5c2e:21e4:0094:4805:1635:f8e4:049b"],
+            &["B086VX9VMK", "Q9OP1I23"],
+            "example.com",
+        );
+
+        assert_eq!(4, encoded_labels.len());
+        assert_eq!("abfku6xaaaaaaaamotptyubibrji6.example.com", encoded_labels[0]);
+        assert_eq!(
+            encode_request_data("85.83.215.126", "Q9OP1I23", "example.com"),
+            encoded_labels[1]
+        );
+        assert_eq!(
+            encode_request_data(
+                "819e:5c2e:21e4:0094:4805:1635:f8e4:049b",
+                "B086VX9VMK",
+                "example.com"
+            ),
+            encoded_labels[2]
+        );
+        assert_eq!("abydhs4fyq6iaaaykudpmaxncecqs.example.com", encoded_labels[3]);
+
+        assert!(encode_matrix(&[], &["B086VX9VMK"], "example.com").is_empty());
+        assert!(encode_matrix(&["85.83.215.126"], &[], "example.com").is_empty());
+    }
+
+    #[test]
+    fn validate_encode_timestamped_round_trip() {
+        let encoded_label =
+            encode_request_data_timestamped("85.83.215.126", "B086VX9VMK", "example.com", 913_742);
+        assert_eq!(33 + ".example.com".len(), encoded_label.len());
+        // the default fields come first
+        let label = encode_request_data("85.83.215.126", "B086VX9VMK", "example.com");
+        assert_eq!(label[..28], encoded_label[..28]);
+
+        match decode_request_data_timestamped(&encoded_label) {
+            Ok((decoded_label, timestamp)) => {
+                assert_eq!(1, decoded_label.client_sdk_version);
+                assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+                assert_eq!(24, decoded_label.subnet_mask);
+                assert_eq!(hash_cgid("B086VX9VMK"), decoded_label.cgid);
+                assert_eq!(913_742, timestamp);
+            }
+            Err(e) => panic!("Threw error when shouldn't have: {}", e),
+        };
+    }
+
+    #[test]
+    fn validate_encode_timestamped_wraps() {
+        let max_timestamp = (1 << 20) - 1;
+
+        for (epoch_minutes, expected_timestamp) in [
+            (max_timestamp, max_timestamp),
+            (1 << 20, 0),
+            ((1 << 20) + 5, 5),
+            (u32::MAX, max_timestamp),
+        ] {
+            let encoded_label =
+                encode_request_data_timestamped("1.2.3.4", "mv-456", "example.com", epoch_minutes);
+
+            match decode_request_data_timestamped(&encoded_label) {
+                Ok((_decoded_label, timestamp)) => assert_eq!(expected_timestamp, timestamp),
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+        }
+
+        // age across the wrap
+        let now: u32 = (1 << 20) + 5;
+        assert_eq!(6, now.wrapping_sub(max_timestamp) & max_timestamp);
+    }
+
+    #[test]
+    fn validate_decode_timestamped_with_leading_dot_returns_empty_error() {
+        for domain in [".abacaqdaaaaaaaamnjg3oubcyvrgmai2j.example.com", ".", ""] {
+            match decode_request_data_timestamped(domain) {
+                Ok(_decoded_label) => panic!("Didn't return an error when it should have"),
+                Err(DecodeError::Empty) => (),
+                Err(e) => panic!("Returned the wrong error: {}", e),
+            };
+        }
+    }
+
+    #[test]
+    fn validate_decode_timestamped_with_label_longer_than_dns_label_returns_too_long_error() {
+        let domain = format!("{}.example.com", "a".repeat(100));
+
+        match decode_request_data_timestamped(&domain) {
+            Ok(_decoded_label) => panic!("Didn't return an error when it should have"),
+            Err(DecodeError::LabelTooLong { len }) => assert_eq!(100, len),
+            Err(e) => panic!("Returned the wrong error: {}", e),
+        };
+    }
+
+    #[test]
+    fn validate_encode_versionless_round_trip() {
+        for (client_ip, client_subnet, subnet_mask) in [
+            ("85.83.215.126", [85, 83, 215, 0, 0, 0, 0, 0], 24),
+            ("2001:db8:1:2::1", [0x20, 0x01, 0x0d, 0xb8, 0, 1, 0, 0], 48),
+        ] {
+            let encoded_label = encode_request_data_versionless(client_ip, "B086VX9VMK", "example.com");
+            let (client_routing_label, fqdn) = encoded_label.split_once('.').unwrap();
+            assert_eq!(27, client_routing_label.len());
+            assert_eq!("example.com", fqdn);
+
+            match decode_request_data_versionless(&encoded_label) {
+                Ok(decoded_label) => {
+                    assert_eq!(1, decoded_label.client_sdk_version);
+                    assert_eq!(client_ip.contains(':'), decoded_label.is_ipv6);
+                    assert_eq!(client_subnet, decoded_label.client_subnet);
+                    assert_eq!(subnet_mask, decoded_label.subnet_mask);
+                    assert_eq!(hash_cgid("B086VX9VMK"), decoded_label.cgid);
+                }
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+
+            // a versioned decode expects 2 more chars
+            assert!(decode_request_data(&encoded_label).is_err());
+        }
+    }
+
+    /// Checks that decoding `encoded_label` and encoding the result again gives
+    /// back the same label, and that the re-encoded label decodes the same.
+    fn assert_reencode_fixed_point(encoded_label: &str) -> Result<(), TestCaseError> {
+        let client_routing_label = encoded_label.split('.').next().unwrap();
+        let decoded_label = match decode_request_data(encoded_label) {
+            Ok(decoded_label) => decoded_label,
+            Err(e) => return Err(TestCaseError::fail(format!("Threw error when shouldn't have: {}", e))),
+        };
+
+        let reencoded_label = ClientRoutingLabel::from_decoded(&decoded_label).encode();
+        prop_assert_eq!(client_routing_label, reencoded_label.as_str());
+
+        let redecoded_label = match decode_request_data(&reencoded_label) {
+            Ok(redecoded_label) => redecoded_label,
+            Err(e) => return Err(TestCaseError::fail(format!("Threw error when shouldn't have: {}", e))),
+        };
+        prop_assert_eq!(decoded_label.is_ipv6, redecoded_label.is_ipv6);
+        prop_assert_eq!(decoded_label.client_subnet, redecoded_label.client_subnet);
+        prop_assert_eq!(decoded_label.subnet_mask, redecoded_label.subnet_mask);
+        prop_assert_eq!(decoded_label.cgid, redecoded_label.cgid);
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn validate_reencode_ipv4_is_fixed_point(octets in any::<[u8; 4]>(), cgid in "\\PC*") {
+            let client_ip = Ipv4Addr::from(octets).to_string();
+            assert_reencode_fixed_point(&encode_request_data(&client_ip, &cgid, "example.com"))?;
+        }
+
+        #[test]
+        fn validate_reencode_ipv6_is_fixed_point(
+            segments in any::<[u16; 8]>(),
+            ipv6_subnet_mask in 1u8..=64,
+            cgid in "\\PC*",
+        ) {
+            let client_ip = Ipv6Addr::from(segments).to_string();
+            assert_reencode_fixed_point(&encode_request_data(&client_ip, &cgid, "example.com"))?;
+
+            let client_subnet_encoding_data = parse_client_ip_with_ipv6_mask(&client_ip, ipv6_subnet_mask);
+            assert_reencode_fixed_point(&encode_request_data_from_subnet_data(
+                client_subnet_encoding_data,
+                &cgid,
+                "example.com",
+            ))?;
+        }
+    }
+
+    #[test]
+    fn validate_encode_token_round_trip() {
+        for token in [0, 1, 1000, (1 << 12) - 1] {
+            let encoded_label = encode_request_data_token("85.83.215.126", token, 12, "example.com");
+            let (client_routing_label, _fqdn) = encoded_label.split_once('.').unwrap();
+            // 93 bits
+            assert_eq!(19, client_routing_label.len());
+            // the subnet is encoded the same as the default layout
+            assert_eq!(
+                encode_request_data("85.83.215.126", "B086VX9VMK", "example.com")[..16],
+                encoded_label[..16]
+            );
+
+            match decode_request_data_token(&encoded_label, 12) {
+                Ok(decoded_label) => {
+                    assert_eq!(1, decoded_label.client_sdk_version);
+                    assert_eq!([85, 83, 215, 0, 0, 0, 0, 0], decoded_label.client_subnet);
+                    assert_eq!(24, decoded_label.subnet_mask);
+                    assert_eq!(token, decoded_label.cgid);
+                }
+                Err(e) => panic!("Threw error when shouldn't have: {}", e),
+            };
+        }
+
+        let encoded_label = encode_request_data_token("85.83.215.126", 1000, 12, "example.com");
+        assert!(decode_request_data_token(&encoded_label, 16).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "cgid value 4096 doesn't fit in 12 bits")]
+    fn validate_encode_token_too_wide() {
+        encode_request_data_token("85.83.215.126", 1 << 12, 12, "example.com");
+    }
 }